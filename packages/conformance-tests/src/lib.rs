@@ -1,4 +1,5 @@
 #![cfg(test)]
 
 mod name_server;
+mod requirements;
 mod resolver;
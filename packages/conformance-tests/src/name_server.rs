@@ -1,2 +1,4 @@
 mod rfc4035;
+mod rfc6891;
 mod scenarios;
+mod zone_transfer;
@@ -3,6 +3,7 @@ use dns_test::name_server::NameServer;
 use dns_test::record::{Record, RecordType};
 use dns_test::{Network, Result, FQDN};
 
+// RFC 4035 section 3.1.1 MUST [rrsig-in-answer]
 #[test]
 #[ignore]
 fn rrsig_in_answer_section() -> Result<()> {
@@ -32,6 +33,7 @@ fn rrsig_in_answer_section() -> Result<()> {
     Ok(())
 }
 
+// RFC 4035 section 3.1.1 MUST [rrsig-in-authority]
 #[test]
 #[ignore]
 fn rrsig_in_authority_section() -> Result<()> {
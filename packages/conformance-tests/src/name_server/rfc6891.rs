@@ -0,0 +1,24 @@
+use dns_test::client::{Client, Transport};
+use dns_test::message::{Header, Message};
+use dns_test::name_server::NameServer;
+use dns_test::record::RecordType;
+use dns_test::{Network, Result, FQDN};
+
+// RFC 6891 section 6.1.1 MUST [multiple-opt-formerr]: "If a query message with more than one OPT
+// RR is received, a FORMERR (RCODE=1) MUST be returned"
+#[test]
+fn more_than_one_opt_record_is_formerr() -> Result<()> {
+    let network = Network::new()?;
+    let ns = NameServer::new(&dns_test::SUBJECT, FQDN::ROOT, &network)?.start()?;
+
+    let client = Client::new(&network)?;
+    let mut query = Message::query(&FQDN::ROOT, RecordType::SOA);
+    query.edns(1232, false).edns(1232, false);
+    let reply = client
+        .send_raw(ns.ipv4_addr(), Transport::Udp, &query.encode())?
+        .expect("no reply");
+
+    assert!(Header::parse(&reply)?.status()?.is_formerr());
+
+    Ok(())
+}
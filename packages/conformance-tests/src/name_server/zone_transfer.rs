@@ -0,0 +1,106 @@
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use dns_test::client::{Client, DigSettings};
+use dns_test::name_server::NameServer;
+use dns_test::record::{Record, RecordType};
+use dns_test::{Network, Result, TsigKey, FQDN};
+
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[test]
+fn serves_zone_over_axfr() -> Result<()> {
+    let network = &Network::new()?;
+    let needle_fqdn = FQDN("example.nameservers.com.")?;
+    let needle_ipv4_addr = Ipv4Addr::new(1, 2, 3, 4);
+
+    let mut ns = NameServer::new(&dns_test::SUBJECT, FQDN::NAMESERVERS, network)?;
+    ns.add(Record::a(needle_fqdn.clone(), needle_ipv4_addr))
+        .allow_transfer();
+    let ns = ns.start()?;
+
+    let client = Client::new(network)?;
+    let zone = client.axfr(ns.ipv4_addr(), &FQDN::NAMESERVERS, None)?;
+
+    assert_eq!(ns.zone_file().soa.settings.serial, zone.soa.settings.serial);
+    assert_eq!(ns.zone_file().records.len(), zone.records.len());
+    assert!(zone.records.iter().any(|record| matches!(
+        record,
+        Record::A(a) if a.fqdn == needle_fqdn && a.ipv4_addr == needle_ipv4_addr
+    )));
+
+    Ok(())
+}
+
+#[ignore]
+#[test]
+fn refuses_axfr_not_signed_with_tsig_key() -> Result<()> {
+    let network = &Network::new()?;
+    let key = TsigKey::generate(FQDN("transfer-key.")?)?;
+
+    let mut ns = NameServer::new(&dns_test::SUBJECT, FQDN::NAMESERVERS, network)?;
+    ns.allow_transfer().tsig_key(key.clone());
+    let ns = ns.start()?;
+
+    let client = Client::new(network)?;
+    assert!(client
+        .axfr(ns.ipv4_addr(), &FQDN::NAMESERVERS, None)
+        .is_err());
+
+    let zone = client.axfr(ns.ipv4_addr(), &FQDN::NAMESERVERS, Some(&key))?;
+    assert_eq!(ns.zone_file().soa.settings.serial, zone.soa.settings.serial);
+
+    Ok(())
+}
+
+#[ignore]
+#[test]
+fn secondary_serves_zone_transferred_from_primary() -> Result<()> {
+    let network = &Network::new()?;
+    let needle_fqdn = FQDN("example.nameservers.com.")?;
+    let needle_ipv4_addr = Ipv4Addr::new(1, 2, 3, 4);
+
+    let mut primary = NameServer::new(&dns_test::PEER, FQDN::NAMESERVERS, network)?;
+    primary
+        .add(Record::a(needle_fqdn.clone(), needle_ipv4_addr))
+        .allow_transfer();
+    let secondary = NameServer::secondary(&dns_test::SUBJECT, &primary)?;
+    let primary = primary.start()?;
+    let secondary = secondary.start()?;
+
+    secondary.wait_for_serial(primary.soa_serial()?, TRANSFER_TIMEOUT)?;
+
+    let client = Client::new(network)?;
+    let ans = client.dig(
+        DigSettings::default(),
+        secondary.ipv4_addr(),
+        RecordType::A,
+        &needle_fqdn,
+    )?;
+
+    assert!(ans.status.is_noerror());
+    assert!(ans.flags.authoritative_answer);
+
+    let [a] = ans.answer.try_into().unwrap();
+    let a = a.try_into_a().unwrap();
+    assert_eq!(needle_ipv4_addr, a.ipv4_addr);
+
+    Ok(())
+}
+
+#[ignore]
+#[test]
+fn secondary_transfers_zone_with_tsig() -> Result<()> {
+    let network = &Network::new()?;
+    let key = TsigKey::generate(FQDN("transfer-key.")?)?;
+
+    let mut primary = NameServer::new(&dns_test::PEER, FQDN::NAMESERVERS, network)?;
+    primary.allow_transfer().tsig_key(key);
+    let secondary = NameServer::secondary(&dns_test::SUBJECT, &primary)?;
+    let primary = primary.start()?;
+    let secondary = secondary.start()?;
+
+    secondary.wait_for_serial(primary.soa_serial()?, TRANSFER_TIMEOUT)?;
+
+    Ok(())
+}
@@ -0,0 +1,12 @@
+use std::path::Path;
+
+use dns_test::coverage::Report;
+use dns_test::Result;
+
+// fails when a requirement tag names an identifier that is not in `requirements/*.toml` or
+// disagrees with the catalog about its section or keyword
+#[test]
+fn requirement_tags_match_the_catalogs() -> Result<()> {
+    Report::load(Path::new(env!("CARGO_MANIFEST_DIR")))?;
+    Ok(())
+}
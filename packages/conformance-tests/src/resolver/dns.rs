@@ -1,3 +1,4 @@
 //! plain DNS functionality
 
+mod rfc6891;
 mod scenarios;
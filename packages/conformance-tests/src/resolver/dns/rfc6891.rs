@@ -0,0 +1,32 @@
+use dns_test::client::{Client, Transport};
+use dns_test::message::{Header, Message};
+use dns_test::name_server::{Graph, NameServer, Sign};
+use dns_test::record::RecordType;
+use dns_test::{Network, Resolver, Result, FQDN};
+
+// RFC 6891 section 6.1.1 MUST [multiple-opt-formerr]: "If a query message with more than one OPT
+// RR is received, a FORMERR (RCODE=1) MUST be returned"
+#[test]
+fn more_than_one_opt_record_is_formerr() -> Result<()> {
+    let network = Network::new()?;
+    let leaf_ns = NameServer::new(&dns_test::PEER, FQDN::NAMESERVERS, &network)?;
+
+    let Graph {
+        nameservers: _nameservers,
+        root,
+        ..
+    } = Graph::build(leaf_ns, Sign::No)?;
+
+    let resolver = Resolver::new(&network, root).start(&dns_test::SUBJECT)?;
+
+    let client = Client::new(&network)?;
+    let mut query = Message::query(&FQDN::NAMESERVERS, RecordType::SOA);
+    query.recurse().edns(1232, false).edns(1232, false);
+    let reply = client
+        .send_raw(resolver.ipv4_addr(), Transport::Udp, &query.encode())?
+        .expect("no reply");
+
+    assert!(Header::parse(&reply)?.status()?.is_formerr());
+
+    Ok(())
+}
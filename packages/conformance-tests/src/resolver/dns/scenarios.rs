@@ -1,9 +1,10 @@
 use std::net::Ipv4Addr;
+use std::time::Duration;
 
 use dns_test::client::{Client, DigSettings};
 use dns_test::name_server::{Graph, NameServer, Sign};
 use dns_test::record::{Record, RecordType};
-use dns_test::{Network, Resolver, Result, FQDN};
+use dns_test::{Faults, Impairment, Network, Resolver, Result, FQDN};
 
 #[test]
 fn can_resolve() -> Result<()> {
@@ -66,3 +67,41 @@ fn nxdomain() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn can_resolve_through_slow_authoritative_server() -> Result<()> {
+    let expected_ipv4_addr = Ipv4Addr::new(1, 2, 3, 4);
+    let needle_fqdn = FQDN("example.nameservers.com.")?;
+
+    let network = Network::new()?;
+
+    let mut leaf_ns = NameServer::new(&dns_test::PEER, FQDN::NAMESERVERS, &network)?;
+    leaf_ns.add(Record::a(needle_fqdn.clone(), expected_ipv4_addr));
+
+    let Graph {
+        nameservers, root, ..
+    } = Graph::build(leaf_ns, Sign::No)?;
+
+    let resolver = Resolver::new(&network, root).start(&dns_test::SUBJECT)?;
+
+    // the leaf name server answers the resolver with a noticeable but tolerable delay
+    nameservers[0].impair(
+        Impairment::default()
+            .delay(Duration::from_millis(300))
+            .jitter(Duration::from_millis(50))
+            .peer(resolver.ipv4_addr()),
+    )?;
+
+    let client = Client::new(&network)?;
+    let settings = *DigSettings::default().recurse();
+    let output = client.dig(settings, resolver.ipv4_addr(), RecordType::A, &needle_fqdn)?;
+
+    assert!(output.status.is_noerror());
+
+    let [answer] = output.answer.try_into().unwrap();
+    let a = answer.try_into_a().unwrap();
+
+    assert_eq!(expected_ipv4_addr, a.ipv4_addr);
+
+    Ok(())
+}
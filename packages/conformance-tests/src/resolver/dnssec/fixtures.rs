@@ -2,23 +2,41 @@ use std::net::Ipv4Addr;
 
 use base64::prelude::*;
 use dns_test::{
+    fixture::SharedGraph,
     name_server::{Graph, NameServer, Running, Sign},
     record::Record,
     Network, Resolver, Result, TrustAnchor, FQDN,
 };
 
-pub fn bad_signature_in_leaf_nameserver(
+/// A signed graph with no records beyond those of the name servers, shared by the tests that
+/// only query it
+pub static SECURE: SharedGraph = SharedGraph::new(|network| {
+    let leaf_ns = NameServer::new(&dns_test::PEER, FQDN::NAMESERVERS, network)?;
+    Graph::build(leaf_ns, Sign::Yes)
+});
+
+/// The A record whose RRSIG is corrupted in `BAD_SIGNATURE`
+pub const BAD_SIGNATURE_NEEDLE: (&str, Ipv4Addr) =
+    ("example.nameservers.com.", Ipv4Addr::new(1, 2, 3, 4));
+
+/// `bad_signature_graph` for `BAD_SIGNATURE_NEEDLE`, shared by the tests that only query it
+pub static BAD_SIGNATURE: SharedGraph = SharedGraph::new(|network| {
+    let (leaf_fqdn, leaf_ipv4_addr) = BAD_SIGNATURE_NEEDLE;
+    bad_signature_graph(network, &FQDN(leaf_fqdn)?, leaf_ipv4_addr)
+});
+
+/// Signed name servers where the RRSIG over the A record of `leaf_fqdn` has been corrupted
+pub fn bad_signature_graph(
+    network: &Network,
     leaf_fqdn: &FQDN,
     leaf_ipv4_addr: Ipv4Addr,
-) -> Result<(Resolver, Graph)> {
+) -> Result<Graph> {
     assert_eq!(Some(FQDN::NAMESERVERS), leaf_fqdn.parent());
 
-    let network = Network::new()?;
-
-    let mut leaf_ns = NameServer::new(&dns_test::PEER, FQDN::NAMESERVERS, &network)?;
+    let mut leaf_ns = NameServer::new(&dns_test::PEER, FQDN::NAMESERVERS, network)?;
     leaf_ns.add(Record::a(leaf_fqdn.clone(), leaf_ipv4_addr));
 
-    let graph = Graph::build(
+    Graph::build(
         leaf_ns,
         Sign::AndAmend(&|zone, records| {
             if zone == &FQDN::NAMESERVERS {
@@ -39,14 +57,7 @@ pub fn bad_signature_in_leaf_nameserver(
                 assert_eq!(modified, 1, "sanity check");
             }
         }),
-    )?;
-
-    let trust_anchor = graph.trust_anchor.as_ref().unwrap();
-    let resolver = Resolver::new(&network, graph.root.clone())
-        .trust_anchor(trust_anchor)
-        .start(&dns_test::SUBJECT)?;
-
-    Ok((resolver, graph))
+    )
 }
 
 pub fn minimally_secure(
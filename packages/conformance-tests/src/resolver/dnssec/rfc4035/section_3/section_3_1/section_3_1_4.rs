@@ -6,6 +6,7 @@ use dns_test::{
     Network, Resolver, Result, FQDN,
 };
 
+// RFC 4035 section 3.1.4.1 MUST [ds-query-to-parent]
 #[test]
 fn on_clients_ds_query_it_queries_the_parent_zone() -> Result<()> {
     let network = Network::new()?;
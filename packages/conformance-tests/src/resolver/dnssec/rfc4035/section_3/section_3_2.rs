@@ -8,6 +8,10 @@ use dns_test::{
     Network, Resolver, Result, FQDN,
 };
 
+use crate::resolver::dnssec::fixtures;
+
+// RFC 4035 section 3.2.1 MUST [strip-dnssec-rrs]
+// RFC 4035 section 3.2.1 MUST [set-do-bit]
 #[test]
 fn do_bit_not_set_in_request() -> Result<()> {
     let network = &Network::new()?;
@@ -52,15 +56,12 @@ fn do_bit_not_set_in_request() -> Result<()> {
     Ok(())
 }
 
+// RFC 4035 section 3.2.1 MUST NOT [keep-requested-dnssec-rrs]
 #[test]
 fn if_do_bit_not_set_in_request_then_requested_dnssec_record_is_not_stripped() -> Result<()> {
-    let network = &Network::new()?;
-    let ns = NameServer::new(&dns_test::PEER, FQDN::ROOT, network)?
-        .sign()?
-        .start()?;
-    let resolver = Resolver::new(network, ns.root_hint()).start(&dns_test::SUBJECT)?;
+    let resolver = fixtures::SECURE.resolver(&dns_test::SUBJECT)?;
 
-    let client = Client::new(network)?;
+    let client = Client::new(resolver.network())?;
     let settings = *DigSettings::default().recurse();
     let ans = client.dig(
         settings,
@@ -77,6 +78,7 @@ fn if_do_bit_not_set_in_request_then_requested_dnssec_record_is_not_stripped() -
     Ok(())
 }
 
+// RFC 4035 section 3.2.1 MUST [set-do-bit]
 #[test]
 fn do_bit_set_in_request() -> Result<()> {
     let network = &Network::new()?;
@@ -1,21 +1,17 @@
-use std::net::Ipv4Addr;
-
 use dns_test::{
     client::{Client, DigSettings},
-    name_server::NameServer,
     record::RecordType,
-    Network, Resolver, Result, FQDN,
+    Result, FQDN,
 };
 
 use crate::resolver::dnssec::fixtures;
 
+// RFC 4035 section 3.2.2 MUST [copy-cd-bit]
 #[test]
 fn copies_cd_bit_from_query_to_response() -> Result<()> {
-    let network = &Network::new()?;
-    let ns = NameServer::new(&dns_test::PEER, FQDN::ROOT, network)?.start()?;
-    let resolver = Resolver::new(network, ns.root_hint()).start(&dns_test::SUBJECT)?;
+    let resolver = fixtures::SECURE.resolver(&dns_test::SUBJECT)?;
 
-    let client = Client::new(network)?;
+    let client = Client::new(resolver.network())?;
     let settings = *DigSettings::default().checking_disabled().recurse();
     let ans = client.dig(settings, resolver.ipv4_addr(), RecordType::SOA, &FQDN::ROOT)?;
 
@@ -24,13 +20,13 @@ fn copies_cd_bit_from_query_to_response() -> Result<()> {
     Ok(())
 }
 
+// RFC 4035 section 3.2.2 SHOULD [cd-bit-returns-unauthenticated-data]
 #[test]
 fn if_cd_bit_is_set_then_respond_with_data_that_fails_authentication() -> Result<()> {
-    let needle_fqdn = FQDN("example.nameservers.com.")?;
-    let needle_ipv4_addr = Ipv4Addr::new(1, 2, 3, 4);
+    let (needle_fqdn, needle_ipv4_addr) = fixtures::BAD_SIGNATURE_NEEDLE;
+    let needle_fqdn = FQDN(needle_fqdn)?;
 
-    let (resolver, _graph) =
-        fixtures::bad_signature_in_leaf_nameserver(&needle_fqdn, needle_ipv4_addr)?;
+    let resolver = fixtures::BAD_SIGNATURE.resolver(&dns_test::SUBJECT)?;
 
     let resolver_addr = resolver.ipv4_addr();
 
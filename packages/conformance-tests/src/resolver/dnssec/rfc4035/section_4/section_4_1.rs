@@ -4,6 +4,8 @@ use dns_test::record::RecordType;
 use dns_test::tshark::{Capture, Direction};
 use dns_test::{Network, Resolver, Result, FQDN};
 
+// RFC 4035 section 4.1 MUST [edns-do-bit]
+// RFC 4035 section 4.1 MUST [resolver-message-size-1220]
 #[test]
 fn edns_support() -> Result<()> {
     let network = &Network::new()?;
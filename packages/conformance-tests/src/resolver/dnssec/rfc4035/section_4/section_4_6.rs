@@ -9,6 +9,7 @@ use dns_test::{
 
 use crate::resolver::dnssec::fixtures;
 
+// RFC 4035 section 4.6 MUST [clear-ad-bit]
 #[test]
 fn clears_ad_bit_in_outgoing_queries() -> Result<()> {
     let leaf_ipv4_addr = Ipv4Addr::new(1, 2, 3, 4);
@@ -1,20 +1,18 @@
-use std::net::Ipv4Addr;
-
 use dns_test::client::{Client, DigSettings};
 use dns_test::record::RecordType;
 use dns_test::{Result, FQDN};
 
 use crate::resolver::dnssec::fixtures;
 
-// TODO find out which RFC section states this
+// RFC 4035 section 5.5 MUST [servfail-on-bad]: "If the validation was being done to service a
+// recursive query, the name server MUST return RCODE 2 to the originating client"
 #[ignore]
 #[test]
 fn if_cd_bit_is_clear_and_data_is_not_authentic_then_respond_with_servfail() -> Result<()> {
-    let needle_fqdn = FQDN("example.nameservers.com.")?;
-    let needle_ipv4_addr = Ipv4Addr::new(1, 2, 3, 4);
+    let (needle_fqdn, _) = fixtures::BAD_SIGNATURE_NEEDLE;
+    let needle_fqdn = FQDN(needle_fqdn)?;
 
-    let (resolver, _graph) =
-        fixtures::bad_signature_in_leaf_nameserver(&needle_fqdn, needle_ipv4_addr)?;
+    let resolver = fixtures::BAD_SIGNATURE.resolver(&dns_test::SUBJECT)?;
 
     let resolver_addr = resolver.ipv4_addr();
 
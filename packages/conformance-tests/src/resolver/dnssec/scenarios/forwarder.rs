@@ -0,0 +1,105 @@
+use std::net::Ipv4Addr;
+
+use dns_test::client::{Client, DigSettings};
+use dns_test::name_server::{Graph, NameServer, Sign};
+use dns_test::record::{Record, RecordType};
+use dns_test::{Network, Resolver, Result, FQDN};
+
+use crate::resolver::dnssec::fixtures;
+
+// the validating resolver is the upstream of the forwarder under test, which does not validate
+#[test]
+fn can_resolve_when_only_upstream_validates() -> Result<()> {
+    let expected_ipv4_addr = Ipv4Addr::new(1, 2, 3, 4);
+    let needle_fqdn = FQDN("example.nameservers.com.")?;
+
+    let network = Network::new()?;
+    let mut leaf_ns = NameServer::new(&dns_test::PEER, FQDN::NAMESERVERS, &network)?;
+    leaf_ns.add(Record::a(needle_fqdn.clone(), expected_ipv4_addr));
+
+    let Graph {
+        root,
+        trust_anchor,
+        nameservers: _nameservers,
+    } = Graph::build(leaf_ns, Sign::Yes)?;
+
+    let upstream = Resolver::new(&network, root)
+        .trust_anchor(&trust_anchor.unwrap())
+        .start(&dns_test::PEER)?;
+    let forwarder =
+        Resolver::forwarder(&network, upstream.ipv4_addr()).start(&dns_test::SUBJECT)?;
+
+    let client = Client::new(&network)?;
+    let settings = *DigSettings::default().recurse();
+    let output = client.dig(settings, forwarder.ipv4_addr(), RecordType::A, &needle_fqdn)?;
+
+    assert!(output.status.is_noerror());
+
+    let [a] = output.answer.try_into().unwrap();
+    let a = a.try_into_a().unwrap();
+
+    assert_eq!(needle_fqdn, a.fqdn);
+    assert_eq!(expected_ipv4_addr, a.ipv4_addr);
+
+    Ok(())
+}
+
+// TODO the hickory forwarder does not support local validation yet; starting it with a trust
+// anchor fails
+#[ignore]
+#[test]
+fn can_validate_at_both_hops() -> Result<()> {
+    let expected_ipv4_addr = Ipv4Addr::new(1, 2, 3, 4);
+    let needle_fqdn = FQDN("example.nameservers.com.")?;
+
+    let network = Network::new()?;
+    let mut leaf_ns = NameServer::new(&dns_test::PEER, FQDN::NAMESERVERS, &network)?;
+    leaf_ns.add(Record::a(needle_fqdn.clone(), expected_ipv4_addr));
+
+    let Graph {
+        root,
+        trust_anchor,
+        nameservers: _nameservers,
+    } = Graph::build(leaf_ns, Sign::Yes)?;
+
+    let trust_anchor = trust_anchor.unwrap();
+    let upstream = Resolver::new(&network, root)
+        .trust_anchor(&trust_anchor)
+        .start(&dns_test::PEER)?;
+    let forwarder = Resolver::forwarder(&network, upstream.ipv4_addr())
+        .trust_anchor(&trust_anchor)
+        .start(&dns_test::SUBJECT)?;
+
+    let client = Client::new(&network)?;
+    let settings = *DigSettings::default().recurse().authentic_data();
+    let output = client.dig(settings, forwarder.ipv4_addr(), RecordType::A, &needle_fqdn)?;
+
+    assert!(output.status.is_noerror());
+    assert!(output.flags.authenticated_data);
+
+    Ok(())
+}
+
+// the upstream answers SERVFAIL; a forward-only forwarder must not work around that
+#[test]
+fn bogus_answer_rejected_upstream_is_not_forwarded() -> Result<()> {
+    let needle_fqdn = FQDN("example.nameservers.com.")?;
+    let needle_ipv4_addr = Ipv4Addr::new(1, 2, 3, 4);
+
+    let network = Network::new()?;
+    let graph = fixtures::bad_signature_graph(&network, &needle_fqdn, needle_ipv4_addr)?;
+
+    let upstream = Resolver::new(&network, graph.root.clone())
+        .trust_anchor(graph.trust_anchor.as_ref().unwrap())
+        .start(&dns_test::PEER)?;
+    let forwarder =
+        Resolver::forwarder(&network, upstream.ipv4_addr()).start(&dns_test::SUBJECT)?;
+
+    let client = Client::new(&network)?;
+    let settings = *DigSettings::default().recurse();
+    let output = client.dig(settings, forwarder.ipv4_addr(), RecordType::A, &needle_fqdn)?;
+
+    assert!(output.status.is_servfail());
+
+    Ok(())
+}
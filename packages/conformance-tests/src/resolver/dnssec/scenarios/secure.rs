@@ -7,6 +7,7 @@ use dns_test::{Network, Resolver, Result, TrustAnchor, FQDN};
 
 use crate::resolver::dnssec::fixtures;
 
+// RFC 4035 section 4.2 MUST [verify-signatures]
 // no DS records are involved; this is a single-link chain of trust
 #[ignore]
 #[test]
@@ -44,6 +45,7 @@ fn can_validate_without_delegation() -> Result<()> {
     Ok(())
 }
 
+// RFC 4035 section 4.2 MUST [verify-signatures]
 #[ignore]
 #[test]
 fn can_validate_with_delegation() -> Result<()> {
@@ -0,0 +1,107 @@
+use std::env;
+use std::path::PathBuf;
+
+use dns_test::fuzz::{Campaign, Scenario};
+use dns_test::{Implementation, Result};
+
+fn main() -> Result<()> {
+    let subject: &Implementation = &dns_test::SUBJECT;
+
+    match Args::from_env()? {
+        Args::Replay(path) => {
+            let scenario = Scenario::load(&path)?;
+            println!("replaying {} against {subject}...", path.display());
+            match scenario.check(subject)? {
+                Some(violation) => println!("VIOLATION: {violation}"),
+                None => println!("no invariant was violated"),
+            }
+        }
+
+        Args::Campaign {
+            seed,
+            iterations,
+            corpus_dir,
+        } => {
+            let mut campaign = Campaign::new(seed);
+            campaign.iterations(iterations).corpus_dir(&corpus_dir);
+
+            println!("fuzzing {subject} with seed {seed}...");
+            let findings = campaign.run(subject, |iteration, scenario, result| {
+                let mutations = scenario
+                    .mutations
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let outcome = match result {
+                    Ok(None) => "ok".to_string(),
+                    Ok(Some(violation)) => format!("VIOLATION: {violation}"),
+                    Err(e) => format!("skipped: {e}"),
+                };
+                println!("#{iteration} [{mutations}]: {outcome}");
+            })?;
+
+            println!("\n{} finding(s)", findings.len());
+            for finding in findings {
+                if let Some(path) = &finding.path {
+                    println!("- {}: {}", path.display(), finding.violation);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+enum Args {
+    Campaign {
+        seed: u64,
+        iterations: usize,
+        corpus_dir: PathBuf,
+    },
+    Replay(PathBuf),
+}
+
+impl Args {
+    fn from_env() -> Result<Self> {
+        let args: Vec<_> = env::args().skip(1).collect();
+
+        let mut seed = 0;
+        let mut iterations = 100;
+        let mut corpus_dir = PathBuf::from(".");
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(value) = args.next() else {
+                return cli_error();
+            };
+
+            match arg.as_str() {
+                "--seed" => seed = value.parse()?,
+                "--iterations" => iterations = value.parse()?,
+                "--corpus" => corpus_dir = PathBuf::from(value),
+                "--replay" => return Ok(Self::Replay(PathBuf::from(value))),
+                _ => return cli_error(),
+            }
+        }
+
+        Ok(Self::Campaign {
+            seed,
+            iterations,
+            corpus_dir,
+        })
+    }
+}
+
+fn cli_error<T>() -> Result<T> {
+    eprintln!(
+        "usage: fuzz [--seed N] [--iterations N] [--corpus DIR]
+       fuzz --replay FILE
+Options:
+  --seed        seed of the pseudo-random scenarios (default: 0)
+  --iterations  number of scenarios to check (default: 100)
+  --corpus      directory the violating scenarios are saved to (default: .)
+  --replay      check the scenario saved in FILE instead"
+    );
+
+    Err("CLI error".into())
+}
@@ -1,10 +1,13 @@
+use core::fmt::Write;
+use core::result::Result as CoreResult;
 use core::str::FromStr;
 use std::net::Ipv4Addr;
 
 use crate::container::{Container, Image, Network};
 use crate::record::{Record, RecordType};
 use crate::trust_anchor::TrustAnchor;
-use crate::{Error, Result, FQDN};
+use crate::zone_file::ZoneFile;
+use crate::{Error, Result, TsigKey, FQDN};
 
 pub struct Client {
     inner: Container,
@@ -41,14 +44,16 @@ impl Client {
 
         self.inner.cp(TRUST_ANCHOR_PATH, &trust_anchor.delv())?;
 
-        self.inner.stdout(&[
-            "delv",
-            &format!("@{server}"),
-            "-a",
-            TRUST_ANCHOR_PATH,
-            fqdn.as_str(),
-            record_type.as_str(),
-        ])
+        self.query(server, || {
+            self.inner.stdout(&[
+                "delv",
+                &format!("@{server}"),
+                "-a",
+                TRUST_ANCHOR_PATH,
+                fqdn.as_str(),
+                record_type.as_str(),
+            ])
+        })
     }
 
     pub fn dig(
@@ -58,19 +63,269 @@ impl Client {
         record_type: RecordType,
         fqdn: &FQDN,
     ) -> Result<DigOutput> {
-        let output = self.inner.stdout(&[
-            "dig",
-            settings.rdflag(),
-            settings.do_bit(),
-            settings.adflag(),
-            settings.cdflag(),
-            &format!("@{server}"),
-            record_type.as_str(),
-            fqdn.as_str(),
-        ])?;
+        let output = self.query(server, || {
+            self.inner.stdout(&[
+                "dig",
+                settings.rdflag(),
+                settings.do_bit(),
+                settings.adflag(),
+                settings.cdflag(),
+                &format!("@{server}"),
+                record_type.as_str(),
+                fqdn.as_str(),
+            ])
+        })?;
 
         output.parse()
     }
+
+    /// Transfers the zone `zone` from `server` over AXFR, signing the request with `tsig_key` if
+    /// provided
+    ///
+    /// Returns an error if the transfer fails, e.g. because `server` refused it
+    pub fn axfr(
+        &self,
+        server: Ipv4Addr,
+        zone: &FQDN,
+        tsig_key: Option<&TsigKey>,
+    ) -> Result<ZoneFile> {
+        let server_arg = format!("@{server}");
+        let mut args = vec![
+            "dig",
+            "+noall",
+            "+answer",
+            &server_arg,
+            "AXFR",
+            zone.as_str(),
+        ];
+        let tsig_arg = tsig_key.map(TsigKey::as_arg);
+        if let Some(tsig_arg) = &tsig_arg {
+            args.extend(["-y", tsig_arg]);
+        }
+
+        let output = self.query(server, || self.inner.stdout(&args))?;
+
+        ZoneFile::parse_axfr(&output)
+            .map_err(|e| format!("zone transfer from {server} failed: {e}").into())
+    }
+
+    /// Sends the dynamic `update` (RFC2136) to `server`, signing it with `tsig_key` if provided,
+    /// and returns the response code
+    ///
+    /// A rejected update, e.g. because a prerequisite was not met, is not an error; check the
+    /// returned status instead
+    pub fn update(
+        &self,
+        server: Ipv4Addr,
+        update: &Update,
+        tsig_key: Option<&TsigKey>,
+    ) -> Result<DigStatus> {
+        const SCRIPT_PATH: &str = "/tmp/nsupdate.txt";
+
+        self.inner.cp(SCRIPT_PATH, &update.script(server))?;
+
+        let mut args = vec!["nsupdate"];
+        let tsig_arg = tsig_key.map(TsigKey::as_arg);
+        if let Some(tsig_arg) = &tsig_arg {
+            args.extend(["-y", tsig_arg]);
+        }
+        args.push(SCRIPT_PATH);
+
+        // `nsupdate` exits with a non-zero status when the update is rejected
+        let output = self.query(server, || self.inner.output(&args))?;
+
+        // rejected updates are reported as "update failed: NXRRSET"; otherwise the `answer`
+        // command prints the response header
+        for line in output.stdout.lines().chain(output.stderr.lines()) {
+            if let Some(status) = line.trim().strip_prefix("update failed: ") {
+                return status.trim().parse();
+            }
+
+            if let Some((_, rest)) = line.split_once("opcode: UPDATE, status: ") {
+                if let Some((status, _)) = rest.split_once(',') {
+                    return status.parse();
+                }
+            }
+        }
+
+        Err(format!(
+            "could not find the response code in the output of `nsupdate`:\n{}{}",
+            output.stdout, output.stderr
+        )
+        .into())
+    }
+
+    /// Sends `message`, verbatim, to port 53 of `server` and returns the reply, e.g. to a
+    /// `message::Message` that `dig` cannot express
+    ///
+    /// Over UDP, the first datagram `server` sends back is returned as soon as it arrives. Over
+    /// TCP, `message` is prefixed with its length and the first length-prefixed reply is returned
+    /// once `server` closes the connection or stays idle for 2 seconds. Returns `None` if `server`
+    /// does not reply within 2 seconds
+    pub fn send_raw(
+        &self,
+        server: Ipv4Addr,
+        transport: Transport,
+        message: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        let mut hex = String::with_capacity(2 * (message.len() + 2));
+        if transport == Transport::Tcp {
+            let len = u16::try_from(message.len())
+                .map_err(|_| "message does not fit in a TCP length prefix")?;
+            write_hex(&mut hex, &len.to_be_bytes());
+        }
+        write_hex(&mut hex, message);
+
+        // errors, e.g. an unreachable port, are reported as no reply: their messages are
+        // discarded, as the runtime merges stderr into stdout, and the exit status of the script
+        // is the one of the final `xxd`
+        let script = match transport {
+            // each `read` of a UDP socket returns one datagram so `dd` stops after the first
+            // reply; `iflag=fullblock` sends `message` in a single datagram
+            Transport::Udp => format!(
+                "{{ exec 3<>/dev/udp/{server}/53 && \
+                 printf %s {hex} | xxd -r -p | dd iflag=fullblock bs=65535 count=1 status=none >&3 && \
+                 timeout {RAW_TIMEOUT_SECS} dd bs=65535 count=1 status=none <&3; }} 2>/dev/null | xxd -p"
+            ),
+            Transport::Tcp => format!(
+                "printf %s {hex} | xxd -r -p | nc -w {RAW_TIMEOUT_SECS} {server} 53 2>/dev/null | xxd -p"
+            ),
+        };
+        let output = self.query(server, || self.inner.stdout(&["bash", "-c", &script]))?;
+
+        let hex = output.split_whitespace().collect::<String>();
+        if hex.is_empty() {
+            return Ok(None);
+        }
+        if hex.len() % 2 != 0 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(format!("could not decode the raw reply:\n{output}").into());
+        }
+
+        let mut reply = (0..hex.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&hex[index..index + 2], 16))
+            .collect::<CoreResult<Vec<_>, _>>()?;
+
+        if transport == Transport::Tcp {
+            if reply.len() < 2 {
+                return Err("TCP reply is missing its length prefix".into());
+            }
+            let len = usize::from(u16::from_be_bytes([reply[0], reply[1]]));
+            reply.drain(..2);
+            reply.truncate(len);
+        }
+
+        Ok(Some(reply))
+    }
+
+    /// Runs `query` against `server` reporting a server crash, rather than the query error, if the
+    /// server has exited before or during the query
+    fn query<T>(&self, server: Ipv4Addr, query: impl FnOnce() -> Result<T>) -> Result<T> {
+        let network = self.inner.network();
+        network.check_server(server)?;
+
+        let res = query();
+        if res.is_err() {
+            network.check_server(server)?;
+        }
+        res
+    }
+}
+
+const RAW_TIMEOUT_SECS: u8 = 2;
+
+fn write_hex(hex: &mut String, bytes: &[u8]) {
+    for byte in bytes {
+        write!(hex, "{byte:02x}").unwrap();
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transport {
+    Udp,
+    Tcp,
+}
+
+/// A dynamic update (RFC2136) message, i.e. an `nsupdate` script
+///
+/// The update is applied atomically and only if all its prerequisites are met
+pub struct Update {
+    zone: FQDN,
+    prerequisites: Vec<String>,
+    updates: Vec<String>,
+}
+
+impl Update {
+    /// Starts an empty update of the zone `zone`
+    pub fn new(zone: FQDN) -> Self {
+        Self {
+            zone,
+            prerequisites: vec![],
+            updates: vec![],
+        }
+    }
+
+    /// Adds `record` to the zone
+    pub fn add(&mut self, record: impl Into<Record>) -> &mut Self {
+        self.updates.push(format!("update add {}", record.into()));
+        self
+    }
+
+    /// Deletes `record`, i.e. the record with the same name, type and data, from the zone
+    pub fn delete(&mut self, record: impl Into<Record>) -> &mut Self {
+        self.updates
+            .push(format!("update delete {}", record.into()));
+        self
+    }
+
+    /// Deletes all the records of type `record_type` at `fqdn`
+    pub fn delete_rrset(&mut self, fqdn: &FQDN, record_type: RecordType) -> &mut Self {
+        self.updates
+            .push(format!("update delete {fqdn} IN {record_type}"));
+        self
+    }
+
+    /// Deletes all the records at `fqdn`
+    pub fn delete_name(&mut self, fqdn: &FQDN) -> &mut Self {
+        self.updates.push(format!("update delete {fqdn}"));
+        self
+    }
+
+    /// Requires that at least one record exists at `fqdn`; YXDOMAIN prerequisite
+    pub fn require_name(&mut self, fqdn: &FQDN) -> &mut Self {
+        self.prerequisites.push(format!("prereq yxdomain {fqdn}"));
+        self
+    }
+
+    /// Requires that no record exists at `fqdn`; NXDOMAIN prerequisite
+    pub fn require_no_name(&mut self, fqdn: &FQDN) -> &mut Self {
+        self.prerequisites.push(format!("prereq nxdomain {fqdn}"));
+        self
+    }
+
+    /// Requires that records of type `record_type` exist at `fqdn`; YXRRSET prerequisite
+    pub fn require_rrset(&mut self, fqdn: &FQDN, record_type: RecordType) -> &mut Self {
+        self.prerequisites
+            .push(format!("prereq yxrrset {fqdn} IN {record_type}"));
+        self
+    }
+
+    /// Requires that no record of type `record_type` exists at `fqdn`; NXRRSET prerequisite
+    pub fn require_no_rrset(&mut self, fqdn: &FQDN, record_type: RecordType) -> &mut Self {
+        self.prerequisites
+            .push(format!("prereq nxrrset {fqdn} IN {record_type}"));
+        self
+    }
+
+    fn script(&self, server: Ipv4Addr) -> String {
+        let mut script = format!("server {server}\nzone {}\n", self.zone);
+        for line in self.prerequisites.iter().chain(&self.updates) {
+            script.push_str(line);
+            script.push('\n');
+        }
+        script.push_str("send\nanswer\n");
+        script
+    }
 }
 
 #[derive(Clone, Copy, Default)]
@@ -253,7 +508,7 @@ impl FromStr for DigOutput {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ExtendedDnsError {
     DnskeyMissing,
     DnssecBogus,
@@ -279,7 +534,7 @@ impl FromStr for ExtendedDnsError {
     }
 }
 
-#[derive(Debug, Default, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct DigFlags {
     pub authenticated_data: bool,
     pub authoritative_answer: bool,
@@ -326,10 +581,17 @@ impl FromStr for DigFlags {
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum DigStatus {
+    FORMERR,
     NOERROR,
+    NOTAUTH,
+    NOTIMP,
+    NOTZONE,
     NXDOMAIN,
+    NXRRSET,
     REFUSED,
     SERVFAIL,
+    YXDOMAIN,
+    YXRRSET,
 }
 
 impl DigStatus {
@@ -338,6 +600,16 @@ impl DigStatus {
         matches!(self, Self::NOERROR)
     }
 
+    #[must_use]
+    pub fn is_formerr(&self) -> bool {
+        matches!(self, Self::FORMERR)
+    }
+
+    #[must_use]
+    pub fn is_notimp(&self) -> bool {
+        matches!(self, Self::NOTIMP)
+    }
+
     #[must_use]
     pub fn is_nxdomain(&self) -> bool {
         matches!(self, Self::NXDOMAIN)
@@ -347,6 +619,72 @@ impl DigStatus {
     pub fn is_servfail(&self) -> bool {
         matches!(self, Self::SERVFAIL)
     }
+
+    #[must_use]
+    pub fn is_refused(&self) -> bool {
+        matches!(self, Self::REFUSED)
+    }
+
+    #[must_use]
+    pub fn is_yxdomain(&self) -> bool {
+        matches!(self, Self::YXDOMAIN)
+    }
+
+    #[must_use]
+    pub fn is_nxrrset(&self) -> bool {
+        matches!(self, Self::NXRRSET)
+    }
+
+    #[must_use]
+    pub fn is_yxrrset(&self) -> bool {
+        matches!(self, Self::YXRRSET)
+    }
+
+    #[must_use]
+    pub fn is_notauth(&self) -> bool {
+        matches!(self, Self::NOTAUTH)
+    }
+
+    /// Returns the RCODE value of this status (RFC1035 & RFC2136)
+    #[must_use]
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::NOERROR => 0,
+            Self::FORMERR => 1,
+            Self::SERVFAIL => 2,
+            Self::NXDOMAIN => 3,
+            Self::NOTIMP => 4,
+            Self::REFUSED => 5,
+            Self::YXDOMAIN => 6,
+            Self::YXRRSET => 7,
+            Self::NXRRSET => 8,
+            Self::NOTAUTH => 9,
+            Self::NOTZONE => 10,
+        }
+    }
+}
+
+impl TryFrom<u8> for DigStatus {
+    type Error = Error;
+
+    fn try_from(code: u8) -> Result<Self> {
+        let status = match code {
+            0 => Self::NOERROR,
+            1 => Self::FORMERR,
+            2 => Self::SERVFAIL,
+            3 => Self::NXDOMAIN,
+            4 => Self::NOTIMP,
+            5 => Self::REFUSED,
+            6 => Self::YXDOMAIN,
+            7 => Self::YXRRSET,
+            8 => Self::NXRRSET,
+            9 => Self::NOTAUTH,
+            10 => Self::NOTZONE,
+            _ => return Err(format!("unknown RCODE: {code}").into()),
+        };
+
+        Ok(status)
+    }
 }
 
 impl FromStr for DigStatus {
@@ -354,10 +692,17 @@ impl FromStr for DigStatus {
 
     fn from_str(input: &str) -> Result<Self> {
         let status = match input {
-            "NXDOMAIN" => Self::NXDOMAIN,
+            "FORMERR" => Self::FORMERR,
             "NOERROR" => Self::NOERROR,
+            "NOTAUTH" => Self::NOTAUTH,
+            "NOTIMP" => Self::NOTIMP,
+            "NOTZONE" => Self::NOTZONE,
+            "NXDOMAIN" => Self::NXDOMAIN,
+            "NXRRSET" => Self::NXRRSET,
             "REFUSED" => Self::REFUSED,
             "SERVFAIL" => Self::SERVFAIL,
+            "YXDOMAIN" => Self::YXDOMAIN,
+            "YXRRSET" => Self::YXRRSET,
             _ => return Err(format!("unknown status: {input}").into()),
         };
 
@@ -440,6 +785,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn update_script() -> Result<()> {
+        let fqdn = FQDN("example.com.")?;
+        let mut update = Update::new(FQDN::COM);
+        update
+            .require_no_rrset(&fqdn, RecordType::A)
+            .add(Record::a(fqdn.clone(), Ipv4Addr::new(1, 2, 3, 4)))
+            .delete_name(&FQDN("old.com.")?);
+
+        let expected = "server 172.21.0.2
+zone com.
+prereq nxrrset example.com. IN A
+update add example.com.\t86400\tIN\tA\t1.2.3.4
+update delete old.com.
+send
+answer
+";
+        assert_eq!(expected, update.script(Ipv4Addr::new(172, 21, 0, 2)));
+
+        Ok(())
+    }
+
     #[test]
     fn ede() -> Result<()> {
         let input = "; <<>> DiG 9.18.24-1-Debian <<>> +recurse +nodnssec +adflag +nocdflag @192.168.176.5 A example.nameservers.com.
@@ -1,17 +1,29 @@
+mod faults;
+mod logs;
 mod network;
+pub(crate) mod runtime;
 
 use core::{fmt, str};
+use std::collections::BTreeMap;
 use std::net::Ipv4Addr;
+use std::path::Path;
 use std::process::{self, ChildStdout, ExitStatus};
 use std::process::{Command, Stdio};
 use std::sync::atomic::AtomicUsize;
-use std::sync::{atomic, Arc, Once};
+use std::sync::{atomic, Arc, Mutex, OnceLock, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 use std::{env, fs};
 
 use tempfile::{NamedTempFile, TempDir};
 
+pub(crate) use crate::container::faults::sealed::HasContainer;
+pub use crate::container::faults::{Faults, Impairment};
+pub(crate) use crate::container::logs::Logs;
 pub use crate::container::network::Network;
-use crate::{Error, Implementation, Repository, Result};
+use crate::container::runtime::runtime;
+use crate::implementation::{Profile, Source};
+use crate::{Error, Implementation, Repository, Result, FQDN};
 
 #[derive(Clone)]
 pub struct Container {
@@ -25,41 +37,175 @@ pub enum Image {
     Bind,
     Client,
     Hickory(Repository<'static>),
+    Rogue,
     Unbound,
 }
 
 impl Image {
-    fn dockerfile(&self) -> &'static str {
+    fn dockerfile(&self) -> String {
         match self {
-            Self::Bind => include_str!("docker/bind.Dockerfile"),
-            Self::Client => include_str!("docker/client.Dockerfile"),
-            Self::Hickory { .. } => include_str!("docker/hickory.Dockerfile"),
-            Self::Unbound => include_str!("docker/unbound.Dockerfile"),
+            Self::Bind => include_str!("docker/bind.Dockerfile").to_string(),
+            Self::Client => include_str!("docker/client.Dockerfile").to_string(),
+            Self::Hickory(repo) => minijinja::render!(
+                include_str!("docker/hickory.Dockerfile"),
+                binary => matches!(repo.source(), Source::Binary(_)),
+                features => repo.cargo_features().join(","),
+                debug => repo.cargo_profile() == Profile::Debug,
+            ),
+            Self::Rogue => include_str!("docker/rogue.Dockerfile").to_string(),
+            Self::Unbound => include_str!("docker/unbound.Dockerfile").to_string(),
         }
     }
 
-    fn once(&self) -> &'static Once {
+    /// Returns the tag of this image, building the image if no image with that tag exists
+    ///
+    /// The tag contains a hash of the Dockerfile and the build context so a new image is only
+    /// built when either of them changes. This is done at most once per process for each distinct
+    /// set of build inputs, e.g. each hickory `Repository`
+    fn tag(&self) -> String {
+        static TAGS: Mutex<BTreeMap<String, Arc<OnceLock<String>>>> = Mutex::new(BTreeMap::new());
+
+        // the map lock is only held to look up the entry so that different images can be built
+        // concurrently while concurrent requests for the same image wait for a single build
+        let cell = TAGS
+            .lock()
+            .unwrap()
+            .entry(self.build_key())
+            .or_default()
+            .clone();
+        cell.get_or_init(|| self.build(offline())).clone()
+    }
+
+    /// Identifies the inputs of the image build: the Dockerfile and, for hickory, where its
+    /// sources come from
+    fn build_key(&self) -> String {
+        let dockerfile = self.dockerfile();
         match self {
-            Self::Bind => {
-                static BIND_ONCE: Once = Once::new();
-                &BIND_ONCE
-            }
+            Self::Hickory(repo) => format!("{self}\n{:?}\n{dockerfile}", repo.source()),
+            _ => format!("{self}\n{dockerfile}"),
+        }
+    }
 
-            Self::Client => {
-                static CLIENT_ONCE: Once = Once::new();
-                &CLIENT_ONCE
+    fn build(&self, offline: bool) -> String {
+        let runtime = runtime();
+        // remote sources are looked up before they are cloned, which requires network access
+        let source_tags = self.source_tags(offline);
+        if let Some(source_tag) = source_tags.first() {
+            if runtime.image_exists(source_tag) {
+                return source_tag.clone();
             }
+        }
 
-            Self::Hickory { .. } => {
-                static HICKORY_ONCE: Once = Once::new();
-                &HICKORY_ONCE
-            }
+        let docker_build_dir = TempDir::new().expect("could not create temporary directory");
+        let docker_build_dir = docker_build_dir.path();
+
+        if let Self::Hickory(repo) = self {
+            prepare_hickory_build_dir(repo, docker_build_dir)
+                .expect("could not prepare the hickory build directory");
+        }
+
+        if let Self::Rogue = self {
+            prepare_rogue_build_dir(docker_build_dir)
+                .expect("could not prepare the rogue-server build directory");
+        }
+
+        let dockerfile = self.dockerfile();
+        let mut hasher = ContentHasher::default();
+        hasher.write(dockerfile.as_bytes());
+        hasher
+            .write_dir(docker_build_dir)
+            .expect("could not hash the docker build directory");
+        let image_tag = format!("{PACKAGE_NAME}-{self}:{:016x}", hasher.finish());
 
-            Self::Unbound => {
-                static UNBOUND_ONCE: Once = Once::new();
-                &UNBOUND_ONCE
+        if !runtime.image_exists(&image_tag) {
+            self.build_image(&image_tag, docker_build_dir, offline);
+        }
+
+        for source_tag in &source_tags {
+            runtime
+                .tag(&image_tag, source_tag)
+                .expect("could not tag the image");
+        }
+
+        image_tag
+    }
+
+    fn build_image(&self, image_tag: &str, docker_build_dir: &Path, offline: bool) {
+        let dockerfile = self.dockerfile();
+
+        if offline {
+            if let Self::Hickory(repo) = self {
+                if matches!(repo.source(), Source::Git { .. }) {
+                    vendor_crates(&docker_build_dir.join("src"))
+                        .expect("could not vendor the hickory dependencies");
+                }
             }
         }
+
+        fs::write(docker_build_dir.join("Dockerfile"), dockerfile)
+            .expect("could not create Dockerfile");
+        fs::write(docker_build_dir.join(".dockerignore"), "src/.git")
+            .expect("could not create .dockerignore file");
+
+        if offline {
+            eprintln!("image `{image_tag}` was not found; building it without network access");
+        }
+        let mut command = runtime().build(image_tag, docker_build_dir, offline);
+
+        exec_or_panic(&mut command, verbose_docker_build());
+    }
+
+    /// Returns the tags an image built from a remote hickory repository is also known by, so
+    /// that later builds find it without cloning the repository; the first one is looked up
+    ///
+    /// Images built from other sources are only known by the hash of their build directory
+    fn source_tags(&self, offline: bool) -> Vec<String> {
+        let Self::Hickory(repo) = self else {
+            return vec![];
+        };
+        let Source::Git {
+            url,
+            revision,
+            working_tree: false,
+        } = repo.source()
+        else {
+            return vec![];
+        };
+        if Path::new(&**url).exists() {
+            return vec![];
+        }
+
+        let tag = |revision: &str| {
+            let mut hasher = ContentHasher::default();
+            hasher.write_prefixed(self.dockerfile().as_bytes());
+            hasher.write_prefixed(url.as_bytes());
+            hasher.write_prefixed(revision.as_bytes());
+            format!("{PACKAGE_NAME}-{self}:src-{:016x}", hasher.finish())
+        };
+
+        let revision = revision.as_deref().unwrap_or("HEAD");
+        if is_commit_id(revision) {
+            return vec![tag(revision)];
+        }
+
+        // branches and tags move so they are resolved to a commit. Without network access, the
+        // image last built from the branch or tag is used instead
+        if offline {
+            return vec![tag(revision)];
+        }
+        let mut ls_remote = Command::new("git");
+        ls_remote.args(["ls-remote", &**url, revision]);
+        match checked_output(&mut ls_remote) {
+            Ok(output) => match String::from_utf8_lossy(&output.stdout)
+                .split_whitespace()
+                .next()
+            {
+                Some(commit) => vec![tag(commit), tag(revision)],
+                // e.g. an abbreviated commit ID; only a clone can tell
+                None => vec![],
+            },
+            Err(_) => vec![tag(revision)],
+        }
     }
 }
 
@@ -79,6 +225,7 @@ impl fmt::Display for Image {
             Self::Client => "client",
             Self::Bind => "bind",
             Self::Hickory { .. } => "hickory",
+            Self::Rogue => "rogue",
             Self::Unbound => "unbound",
         };
         f.write_str(s)
@@ -88,76 +235,21 @@ impl fmt::Display for Image {
 impl Container {
     /// Starts the container in a "parked" state
     pub fn run(image: &Image, network: &Network) -> Result<Self> {
-        // TODO make this configurable and support hickory & bind
-        let dockerfile = image.dockerfile();
-        let docker_build_dir = TempDir::new()?;
-        let docker_build_dir = docker_build_dir.path();
-        fs::write(docker_build_dir.join("Dockerfile"), dockerfile)?;
+        let image_tag = image.tag();
 
-        let image_tag = format!("{PACKAGE_NAME}-{image}");
-
-        let mut command = Command::new("docker");
-        command
-            .args(["build", "-t"])
-            .arg(&image_tag)
-            .arg(docker_build_dir);
-
-        let repo = if let Image::Hickory(repo) = image {
-            Some(repo)
-        } else {
-            None
-        };
-
-        image.once().call_once(|| {
-            if let Some(repo) = repo {
-                let mut cp_r = Command::new("git");
-                cp_r.args([
-                    "clone",
-                    "--depth",
-                    "1",
-                    repo.as_str(),
-                    &docker_build_dir.join("src").display().to_string(),
-                ]);
-
-                exec_or_panic(&mut cp_r, false);
-            }
-
-            fs::write(docker_build_dir.join(".dockerignore"), "src/.git")
-                .expect("could not create .dockerignore file");
-
-            exec_or_panic(&mut command, verbose_docker_build());
-        });
-
-        let mut command = Command::new("docker");
         let pid = process::id();
         let count = container_count();
         let name = format!("{PACKAGE_NAME}-{image}-{pid}-{count}");
-        command
-            .args([
-                "run",
-                "--rm",
-                "--detach",
-                "--cap-add=NET_RAW",
-                "--cap-add=NET_ADMIN",
-                "--network",
-                network.name(),
-                "--name",
-                &name,
-                "-it",
-            ])
-            .arg(image_tag)
-            .args(["sleep", "infinity"]);
-
-        let output: Output = checked_output(&mut command)?.try_into()?;
-        let id = output.stdout;
-
-        let ipv4_addr = get_ipv4_addr(&id)?;
+        let runtime = runtime();
+        let id = runtime.run(&image_tag, network.name(), &name)?;
+        let ipv4_addr = runtime.ipv4_addr(&id)?;
 
         let inner = Inner {
             id,
             name,
             ipv4_addr,
             network: network.clone(),
+            impaired_links: Mutex::new(0),
         };
         Ok(Self {
             inner: Arc::new(inner),
@@ -170,12 +262,7 @@ impl Container {
         let mut temp_file = NamedTempFile::new()?;
         fs::write(&mut temp_file, file_contents)?;
 
-        let src_path = temp_file.path().display().to_string();
-        let dest_path = format!("{}:{path_in_container}", self.inner.id);
-
-        let mut command = Command::new("docker");
-        command.args(["cp", &src_path, &dest_path]);
-        checked_output(&mut command)?;
+        runtime().cp(temp_file.path(), &self.inner.id, path_in_container)?;
 
         self.status_ok(&["chmod", CHMOD_RW_EVERYONE, path_in_container])?;
 
@@ -184,10 +271,7 @@ impl Container {
 
     /// Similar to `std::process::Command::output` but runs `command_and_args` in the container
     pub fn output(&self, command_and_args: &[&str]) -> Result<Output> {
-        let mut command = Command::new("docker");
-        command
-            .args(["exec", "-t", &self.inner.id])
-            .args(command_and_args);
+        let mut command = runtime().exec(&self.inner.id, command_and_args)?;
 
         command.output()?.try_into()
     }
@@ -212,10 +296,7 @@ impl Container {
 
     /// Similar to `std::process::Command::status` but runs `command_and_args` in the container
     pub fn status(&self, command_and_args: &[&str]) -> Result<ExitStatus> {
-        let mut command = Command::new("docker");
-        command
-            .args(["exec", "-t", &self.inner.id])
-            .args(command_and_args);
+        let mut command = runtime().exec(&self.inner.id, command_and_args)?;
 
         Ok(command.status()?)
     }
@@ -231,15 +312,17 @@ impl Container {
         }
     }
 
-    pub fn spawn(&self, cmd: &[&str]) -> Result<Child> {
-        let mut command = Command::new("docker");
+    pub fn spawn(&self, cmd: &[impl AsRef<str>]) -> Result<Child> {
+        let cmd = cmd.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+        let mut command = runtime().exec(&self.inner.id, &cmd)?;
         command.stdout(Stdio::piped()).stderr(Stdio::piped());
-        command.args(["exec", "-t", &self.inner.id]).args(cmd);
 
         let inner = command.spawn()?;
         Ok(Child {
-            inner: Some(inner),
-            _container: self.inner.clone(),
+            inner: Arc::new(Mutex::new(Some(inner))),
+            logs: None,
+            server: None,
+            container: self.inner.clone(),
         })
     }
 
@@ -254,6 +337,266 @@ impl Container {
     pub(crate) fn network(&self) -> &Network {
         &self.inner.network
     }
+
+    /// Starts monitoring the server running as `child`
+    ///
+    /// From this point on, queries sent by `Client`s to this container will fail if the server
+    /// has exited, and dropping `child` panics if the server exited on its own
+    pub(crate) fn monitor(&self, child: &mut Child, server: &dyn fmt::Display) -> Monitor {
+        let monitor = child.monitor(server);
+        self.network()
+            .register_server(self.ipv4_addr(), monitor.clone());
+        monitor
+    }
+}
+
+/// How to tell that a server that has just been started is ready to answer queries
+pub(crate) enum Probe<'a> {
+    /// The server answers a SOA query for the given zone sent from within its own container
+    Query(&'a FQDN),
+    /// The server prints a line that contains the given text
+    LogMarker(&'static str),
+}
+
+impl Container {
+    /// Blocks until the server running as `child` is ready to answer queries, according to
+    /// `probe`
+    ///
+    /// Returns an error, which includes the server's logs, if the server exits or is not ready
+    /// within `DNS_TEST_READINESS_TIMEOUT` seconds
+    pub(crate) fn wait_until_ready(
+        &self,
+        child: &mut Child,
+        logs: &Logs,
+        probe: Probe,
+        server: &dyn fmt::Display,
+    ) -> Result<()> {
+        const POLL_PERIOD: Duration = Duration::from_millis(100);
+
+        let timeout = readiness_timeout();
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = child.try_wait()? {
+                logs.wait_for_eof(Instant::now() + POLL_PERIOD);
+                return Err(format!(
+                    "`{server}` exited with {status} before it was ready\n--- LOGS ---\n{}",
+                    logs.contents()
+                )
+                .into());
+            }
+
+            let is_ready = match probe {
+                Probe::Query(zone) => self
+                    .output(&[
+                        "dig",
+                        "@127.0.0.1",
+                        "+norecurse",
+                        "+time=1",
+                        "+tries=1",
+                        "SOA",
+                        zone.as_str(),
+                    ])?
+                    .status
+                    // `dig` exits with a non-zero status when it gets no response
+                    .success(),
+
+                Probe::LogMarker(marker) => logs
+                    .wait_for(marker, deadline.min(Instant::now() + POLL_PERIOD))
+                    .is_some(),
+            };
+
+            if is_ready {
+                return Ok(());
+            }
+
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "`{server}` was not ready after {timeout:?}\n--- LOGS ---\n{}",
+                    logs.contents()
+                )
+                .into());
+            }
+
+            if matches!(probe, Probe::Query(_)) {
+                thread::sleep(POLL_PERIOD);
+            }
+        }
+    }
+}
+
+pub(crate) fn readiness_timeout() -> Duration {
+    const DEFAULT_SECS: u64 = 10;
+
+    let secs = env::var("DNS_TEST_READINESS_TIMEOUT")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(DEFAULT_SECS);
+    Duration::from_secs(secs)
+}
+
+/// The sources of the `rogue-server` package, embedded in this library
+///
+/// Must list every file of the package; a test checks that it does
+const ROGUE_SERVER_FILES: &[(&str, &str)] = &[
+    ("Cargo.toml", include_str!("../../rogue-server/Cargo.toml")),
+    (
+        "src/config.rs",
+        include_str!("../../rogue-server/src/config.rs"),
+    ),
+    (
+        "src/main.rs",
+        include_str!("../../rogue-server/src/main.rs"),
+    ),
+    (
+        "src/wire.rs",
+        include_str!("../../rogue-server/src/wire.rs"),
+    ),
+];
+
+/// Populates the docker build directory with the sources of the `rogue-server` package
+/// (`./rogue-server`)
+fn prepare_rogue_build_dir(docker_build_dir: &Path) -> Result<()> {
+    let package_dir = docker_build_dir.join("rogue-server");
+    fs::create_dir_all(package_dir.join("src"))?;
+    for (path, contents) in ROGUE_SERVER_FILES {
+        fs::write(package_dir.join(path), contents)?;
+    }
+
+    Ok(())
+}
+
+/// Populates the docker build directory with either a checkout of the hickory repository (`./src`)
+/// or a copy of an already-built binary (`./hickory-dns`)
+fn prepare_hickory_build_dir(repo: &Repository, docker_build_dir: &Path) -> Result<()> {
+    let src_dir = docker_build_dir.join("src");
+
+    match repo.source() {
+        Source::Binary(path) => {
+            fs::copy(path, docker_build_dir.join("hickory-dns"))?;
+        }
+
+        Source::Git {
+            url,
+            revision: None,
+            working_tree: false,
+        } => {
+            let mut clone = Command::new("git");
+            clone.args(["clone", "--depth", "1", &**url]).arg(&src_dir);
+
+            exec_or_panic(&mut clone, false);
+        }
+
+        Source::Git {
+            url,
+            revision: Some(revision),
+            ..
+        } => {
+            // a shallow clone only works for branches and tags; fall back to a full clone
+            // followed by a checkout when `revision` is a commit
+            let mut shallow_clone = Command::new("git");
+            shallow_clone
+                .args(["clone", "--depth", "1", "--branch", revision, &**url])
+                .arg(&src_dir);
+
+            if !shallow_clone.output()?.status.success() {
+                let _ = fs::remove_dir_all(&src_dir);
+
+                let mut clone = Command::new("git");
+                clone.args(["clone", &**url]).arg(&src_dir);
+                exec_or_panic(&mut clone, false);
+
+                let mut checkout = Command::new("git");
+                checkout
+                    .arg("-C")
+                    .arg(&src_dir)
+                    .args(["checkout", "--detach", revision]);
+                exec_or_panic(&mut checkout, false);
+            }
+        }
+
+        Source::Git {
+            url,
+            working_tree: true,
+            ..
+        } => {
+            // copy tracked and untracked files, but not ignored ones (e.g. `target`), as they
+            // currently are on disk
+            let mut ls_files = Command::new("git");
+            ls_files.args([
+                "-C",
+                &**url,
+                "ls-files",
+                "-z",
+                "--cached",
+                "--others",
+                "--exclude-standard",
+            ]);
+            let output = checked_output(&mut ls_files)?;
+
+            let repo_dir = Path::new(&**url);
+            for path in output.stdout.split(|byte| *byte == 0) {
+                if path.is_empty() {
+                    continue;
+                }
+
+                let path = Path::new(str::from_utf8(path)?);
+                let from = repo_dir.join(path);
+                // `--cached` lists files that have been deleted but not yet committed
+                if !from.is_file() {
+                    continue;
+                }
+
+                let to = src_dir.join(path);
+                if let Some(parent) = to.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(from, to)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Vendors the dependencies of the cargo project in `src_dir`, using only crates that are already
+/// present in the local cargo registry, and configures cargo to use the vendored crates
+fn vendor_crates(src_dir: &Path) -> Result<()> {
+    // the hickory Dockerfile passes this file to `cargo install`, which ignores the project's
+    // own configuration
+    let config = src_dir.join(".cargo").join("config.toml");
+    if src_dir.join("vendor").exists() {
+        // assume the repository already vendors its dependencies
+        return if config.exists() {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} does not configure the vendored crates",
+                config.display()
+            )
+            .into())
+        };
+    }
+
+    let mut vendor = Command::new("cargo");
+    vendor
+        .args(["vendor", "--offline", "--locked"])
+        .current_dir(src_dir);
+    let output = checked_output(&mut vendor)?;
+
+    // `cargo vendor` prints the configuration required to use the vendored crates
+    fs::create_dir_all(src_dir.join(".cargo"))?;
+    fs::write(config, output.stdout)?;
+
+    Ok(())
+}
+
+/// Returns `true` if `revision` is a full, hexadecimal, commit ID
+fn is_commit_id(revision: &str) -> bool {
+    revision.len() == 40 && revision.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+fn offline() -> bool {
+    env::var("DNS_TEST_OFFLINE").is_ok()
 }
 
 fn verbose_docker_build() -> bool {
@@ -275,6 +618,68 @@ fn exec_or_panic(command: &mut Command, verbose: bool) {
     }
 }
 
+/// 64-bit FNV-1a hash. Unlike `std::hash::DefaultHasher`, its output is stable across Rust
+/// versions and machines, which is required to share image tags between them
+pub(crate) struct ContentHasher {
+    state: u64,
+}
+
+impl Default for ContentHasher {
+    fn default() -> Self {
+        Self {
+            state: 0xcbf29ce484222325,
+        }
+    }
+}
+
+impl ContentHasher {
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= u64::from(*byte);
+            self.state = self.state.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn write_prefixed(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+
+    /// Hashes the relative path and contents of all the files in `dir`, except for the ones in
+    /// `.git` directories, in a deterministic order
+    fn write_dir(&mut self, dir: &Path) -> Result<()> {
+        let mut pending = vec![dir.to_path_buf()];
+        let mut files = vec![];
+        while let Some(path) = pending.pop() {
+            for entry in fs::read_dir(&path)? {
+                let entry = entry?;
+                let path = entry.path();
+                if entry.file_type()?.is_dir() {
+                    if entry.file_name() != ".git" {
+                        pending.push(path);
+                    }
+                } else {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort();
+
+        for file in files {
+            let relative_path = file.strip_prefix(dir)?;
+            // length prefixes keep the boundary between a path and the contents unambiguous
+            self.write_prefixed(relative_path.to_string_lossy().as_bytes());
+            self.write_prefixed(&fs::read(&file)?);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.state
+    }
+}
+
 fn container_count() -> usize {
     static COUNT: AtomicUsize = AtomicUsize::new(0);
 
@@ -287,6 +692,9 @@ struct Inner {
     // TODO probably also want the IPv6 address
     ipv4_addr: Ipv4Addr,
     network: Network,
+    /// number of `netem` instances installed by `Container::impair`; held while the `tc`
+    /// queueing disciplines are changed
+    impaired_links: Mutex<usize>,
 }
 
 /// NOTE unlike `std::process::Child`, the drop implementation of this type will `kill` the
@@ -295,8 +703,12 @@ struct Inner {
 // runs inside of, to prevent the scenario of the container being destroyed _before_
 // the child is killed
 pub struct Child {
-    inner: Option<process::Child>,
-    _container: Arc<Inner>,
+    // shared with `Monitor`s
+    inner: Arc<Mutex<Option<process::Child>>>,
+    logs: Option<(Logs, JoinHandle<()>)>,
+    /// set when a `Monitor` has been created; see `Child::monitor`
+    server: Option<String>,
+    container: Arc<Inner>,
 }
 
 impl Child {
@@ -306,22 +718,144 @@ impl Child {
     pub fn stdout(&mut self) -> Result<ChildStdout> {
         Ok(self
             .inner
+            .lock()
+            .unwrap()
             .as_mut()
             .and_then(|child| child.stdout.take())
             .ok_or("could not retrieve child's stdout")?)
     }
 
+    /// Starts collecting the child's stdout in the background
+    ///
+    /// When logs are being collected, the stdout returned by `wait` contains them
+    pub(crate) fn collect_logs(&mut self) -> Result<Logs> {
+        let stdout = self.stdout()?;
+        let (logs, handle) = Logs::collect(stdout);
+        self.logs = Some((logs.clone(), handle));
+        Ok(logs)
+    }
+
+    /// Returns the exit status of the child if it has exited
+    pub(crate) fn try_wait(&mut self) -> Result<Option<ExitStatus>> {
+        Ok(self
+            .inner
+            .lock()
+            .unwrap()
+            .as_mut()
+            .expect("unreachable")
+            .try_wait()?)
+    }
+
+    /// Returns a handle that can be used to check whether the `server` process exited on its own
+    ///
+    /// Once this method has been called, dropping the `Child` panics if the process exited on its
+    /// own, i.e. not through `wait`, unless the thread is already panicking
+    ///
+    /// # Panics
+    ///
+    /// this method panics if the logs are not being collected
+    pub(crate) fn monitor(&mut self, server: impl fmt::Display) -> Monitor {
+        let (logs, _) = self
+            .logs
+            .as_ref()
+            .expect("`collect_logs` must be called before `monitor`");
+        let server = server.to_string();
+        self.server = Some(server.clone());
+
+        Monitor {
+            process: Arc::downgrade(&self.inner),
+            logs: logs.clone(),
+            server,
+            container: self.container.name.clone(),
+        }
+    }
+
     pub fn wait(mut self) -> Result<Output> {
-        let output = self.inner.take().expect("unreachable").wait_with_output()?;
-        output.try_into()
+        self.reap()
+    }
+
+    /// Like `wait` but leaves behind a `Child` that no longer refers to a process
+    pub(crate) fn reap(&mut self) -> Result<Output> {
+        let child = self.inner.lock().unwrap().take().expect("unreachable");
+        let output = child.wait_with_output()?;
+        let mut output: Output = output.try_into()?;
+
+        if let Some((logs, handle)) = self.logs.take() {
+            // wait until all the output has been collected
+            let _ = handle.join();
+            output.stdout = logs.contents();
+        }
+
+        Ok(output)
     }
 }
 
 impl Drop for Child {
     fn drop(&mut self) {
-        if let Some(mut inner) = self.inner.take() {
-            let _ = inner.kill();
+        let Some(mut inner) = self.inner.lock().unwrap().take() else {
+            return;
+        };
+
+        if let Some(server) = &self.server {
+            if let Ok(Some(status)) = inner.try_wait() {
+                if !thread::panicking() {
+                    let logs = self.logs.as_ref().map(|(logs, _)| logs.contents());
+                    panic!(
+                        "`{server}` exited unexpectedly with {status}\n--- LOGS ---\n{}",
+                        logs.unwrap_or_default()
+                    );
+                }
+            }
         }
+
+        let _ = inner.kill();
+    }
+}
+
+/// A handle to a server process that can be used to detect that the server exited on its own
+///
+/// Unlike `Child`, this handle does not keep the process alive
+#[derive(Clone)]
+pub(crate) struct Monitor {
+    process: Weak<Mutex<Option<process::Child>>>,
+    logs: Logs,
+    server: String,
+    /// name of the container the server runs in
+    container: String,
+}
+
+impl Monitor {
+    /// Returns `false` if the process exited on its own
+    ///
+    /// A process that was terminated through `Child::wait` or whose `Child` was dropped is
+    /// considered alive
+    pub(crate) fn is_alive(&self) -> bool {
+        self.exit_status().is_none()
+    }
+
+    /// Returns an error that includes the last lines the process logged if the process exited on
+    /// its own
+    pub(crate) fn check(&self, ipv4_addr: Ipv4Addr) -> Result<()> {
+        const NUM_LINES: usize = 20;
+
+        if let Some(status) = self.exit_status() {
+            let Self {
+                server, container, ..
+            } = self;
+            let tail = self.logs.tail(NUM_LINES);
+            Err(format!(
+                "`{server}` (container `{container}`) at {ipv4_addr} exited with {status}\n--- LAST {NUM_LINES} LOG LINES ---\n{tail}"
+            )
+            .into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn exit_status(&self) -> Option<ExitStatus> {
+        let process = self.process.upgrade()?;
+        let mut process = process.lock().unwrap();
+        process.as_mut()?.try_wait().ok()?
     }
 }
 
@@ -337,12 +871,12 @@ impl TryFrom<process::Output> for Output {
 
     fn try_from(output: process::Output) -> Result<Self> {
         let mut stderr = String::from_utf8(output.stderr)?;
-        while stderr.ends_with(|c| matches!(c, '\n' | '\r')) {
+        while stderr.ends_with(['\n', '\r']) {
             stderr.pop();
         }
 
         let mut stdout = String::from_utf8(output.stdout)?;
-        while stdout.ends_with(|c| matches!(c, '\n' | '\r')) {
+        while stdout.ends_with(['\n', '\r']) {
             stdout.pop();
         }
 
@@ -363,36 +897,10 @@ fn checked_output(command: &mut Command) -> Result<process::Output> {
     }
 }
 
-fn get_ipv4_addr(container_id: &str) -> Result<Ipv4Addr> {
-    let mut command = Command::new("docker");
-    command
-        .args([
-            "inspect",
-            "-f",
-            "{{range.NetworkSettings.Networks}}{{.IPAddress}}{{end}}",
-        ])
-        .arg(container_id);
-
-    let output = command.output()?;
-    if !output.status.success() {
-        return Err(format!("`{command:?}` failed").into());
-    }
-
-    let ipv4_addr = str::from_utf8(&output.stdout)?.trim().to_string();
-
-    Ok(ipv4_addr.parse()?)
-}
-
 // this ensures the container gets deleted and does not linger after the test runner process ends
 impl Drop for Inner {
     fn drop(&mut self) {
-        // running this to completion would block the current thread for several seconds so just
-        // fire and forget
-        let _ = Command::new("docker")
-            .args(["rm", "-f", &self.id])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .status();
+        runtime().remove(&self.id, Some(self.network.name()));
     }
 }
 
@@ -400,6 +908,27 @@ impl Drop for Inner {
 mod tests {
     use super::*;
 
+    #[test]
+    fn every_rogue_server_file_is_embedded() -> Result<()> {
+        let package_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../rogue-server");
+        let mut files = vec!["Cargo.toml".to_string()];
+        for entry in fs::read_dir(package_dir.join("src"))? {
+            let name = entry?.file_name().into_string().unwrap();
+            files.push(format!("src/{name}"));
+        }
+        files.sort();
+
+        let mut embedded = ROGUE_SERVER_FILES
+            .iter()
+            .map(|(path, _)| path.to_string())
+            .collect::<Vec<_>>();
+        embedded.sort();
+
+        assert_eq!(files, embedded, "update `ROGUE_SERVER_FILES`");
+
+        Ok(())
+    }
+
     #[test]
     fn run_works() -> Result<()> {
         let network = Network::new()?;
@@ -423,6 +952,186 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn hickory_dockerfile() {
+        let repo = Repository(env!("CARGO_MANIFEST_DIR"))
+            .features(["recursor", "dnssec-openssl"])
+            .profile(Profile::Release);
+        let dockerfile = Image::Hickory(repo).dockerfile();
+
+        assert!(dockerfile.contains("--features recursor,dnssec-openssl &&"));
+        assert!(!dockerfile.contains("--debug"));
+
+        let dockerfile = Image::Hickory(Repository::binary(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/Cargo.toml"
+        )))
+        .dockerfile();
+
+        assert!(dockerfile.contains("COPY ./hickory-dns /usr/local/bin/hickory-dns"));
+        assert!(!dockerfile.contains("cargo install"));
+    }
+
+    #[test]
+    fn hickory_dockerfile_without_features() {
+        let repo = Repository(env!("CARGO_MANIFEST_DIR")).features::<&str>([]);
+        let dockerfile = Image::Hickory(repo).dockerfile();
+
+        assert!(!dockerfile.contains("--features"));
+        assert!(dockerfile.contains("--target-dir /var/cache/hickory-target --debug &&"));
+    }
+
+    #[test]
+    fn content_hash_is_stable() {
+        let mut hasher = ContentHasher::default();
+        hasher.write(b"a");
+
+        // reference value of the 64-bit FNV-1a hash
+        assert_eq!(0xaf63dc4c8601ec8c, hasher.finish());
+    }
+
+    #[test]
+    fn content_hash_separates_paths_from_contents() -> Result<()> {
+        let hash = |files: &[(&str, &str)]| -> Result<u64> {
+            let dir = TempDir::new()?;
+            for (path, contents) in files {
+                fs::write(dir.path().join(path), contents)?;
+            }
+            let mut hasher = ContentHasher::default();
+            hasher.write_dir(dir.path())?;
+            Ok(hasher.finish())
+        };
+
+        assert_ne!(hash(&[("ab", "c")])?, hash(&[("a", "bc")])?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn remote_hickory_images_are_found_by_source() {
+        const URL: &str = "https://github.com/hickory-dns/hickory-dns";
+        const COMMIT: &str = "107635c6c5934524894736f1b141198d0fa62fec";
+
+        let local = Image::Hickory(Repository(env!("CARGO_MANIFEST_DIR")));
+        assert!(local.source_tags(true).is_empty());
+
+        // commits are not resolved so this does not need network access
+        let commit = Image::Hickory(Repository(URL).revision(COMMIT));
+        let [commit_tag] = &commit.source_tags(false)[..] else {
+            panic!("expected a single tag")
+        };
+        assert_eq!(commit.source_tags(true), [commit_tag.as_str()]);
+        assert!(commit_tag.starts_with("dns-test-hickory:src-"));
+
+        let release = Image::Hickory(Repository(URL).revision(COMMIT).profile(Profile::Release));
+        assert_ne!(commit.source_tags(false), release.source_tags(false));
+
+        let branch = Image::Hickory(Repository(URL).revision("main"));
+        assert_ne!(commit.source_tags(true), branch.source_tags(true));
+        assert_eq!(1, branch.source_tags(true).len());
+    }
+
+    #[test]
+    fn hickory_images_are_cached_per_repository() {
+        let debug = Image::Hickory(Repository(env!("CARGO_MANIFEST_DIR")));
+        let release =
+            Image::Hickory(Repository(env!("CARGO_MANIFEST_DIR")).profile(Profile::Release));
+        let other = Image::Hickory(Repository(concat!(env!("CARGO_MANIFEST_DIR"), "/src")));
+
+        assert_ne!(debug.build_key(), release.build_key());
+        assert_ne!(debug.build_key(), other.build_key());
+        assert_eq!(
+            debug.build_key(),
+            Image::Hickory(Repository(env!("CARGO_MANIFEST_DIR"))).build_key()
+        );
+    }
+
+    #[test]
+    fn hickory_builds_offline() -> Result<()> {
+        // a stand-in for the hickory repository whose only dependency is already in the local
+        // cargo registry, as it's also a dependency of this crate
+        const FILES: &[(&str, &str)] = &[
+            (
+                "Cargo.toml",
+                "[workspace]\nmembers = [\"bin\"]\nresolver = \"2\"\n",
+            ),
+            (
+                "Cargo.lock",
+                r#"version = 3
+
+[[package]]
+name = "hickory-dns"
+version = "0.1.0"
+dependencies = [
+ "lazy_static",
+]
+
+[[package]]
+name = "lazy_static"
+version = "1.4.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "e2abad23fbc42b3700f2f279844dc832adb2b2eb069b2df918f455c4e18cc646"
+"#,
+            ),
+            (
+                "bin/Cargo.toml",
+                r#"[package]
+name = "hickory-dns"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+lazy_static = "=1.4.0"
+"#,
+            ),
+            (
+                "bin/src/main.rs",
+                r#"lazy_static::lazy_static! { static ref GREETING: &'static str = "built offline"; }
+
+fn main() {
+    println!("{}", *GREETING);
+}
+"#,
+            ),
+        ];
+
+        let repo_dir = TempDir::new()?;
+        for (path, contents) in FILES {
+            let path = repo_dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(path, contents)?;
+        }
+        for args in [
+            &["init", "--quiet"][..],
+            &["add", "."],
+            &[
+                "-c",
+                "user.name=dns-test",
+                "-c",
+                "user.email=dns-test@localhost",
+                "commit",
+                "--quiet",
+                "-m",
+                "initial",
+            ],
+        ] {
+            let mut git = Command::new("git");
+            git.arg("-C").arg(repo_dir.path()).args(args);
+            checked_output(&mut git)?;
+        }
+
+        let url = repo_dir.path().to_str().unwrap().to_string();
+        let image = Image::Hickory(Repository(url).features::<&str>([]));
+        image.build(true);
+
+        // `Container::run` finds the image that was just built
+        let network = Network::new()?;
+        let container = Container::run(&image, &network)?;
+        assert_eq!("built offline", container.stdout(&["hickory-dns"])?);
+
+        Ok(())
+    }
+
     #[test]
     fn cp_works() -> Result<()> {
         let network = Network::new()?;
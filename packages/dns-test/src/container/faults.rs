@@ -0,0 +1,316 @@
+//! Network fault injection
+//!
+//! Latency, jitter and packet loss are emulated with `tc`'s `netem` queueing discipline; dropped,
+//! blackholed and redirected traffic is implemented with `iptables` rules. Both act on the
+//! container's own network stack so they do not affect other containers
+
+use core::fmt::Write;
+use std::net::Ipv4Addr;
+use std::sync::PoisonError;
+use std::time::Duration;
+
+use super::Container;
+use crate::Result;
+
+const INTERFACE: &str = "eth0";
+
+/// Maximum number of bands of the `prio` queueing discipline
+const MAX_BANDS: usize = 16;
+
+/// Degradation of the packets a server sends, either to a single peer or to every peer
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Impairment {
+    delay: Duration,
+    jitter: Duration,
+    loss_percent: f64,
+    peer: Option<Ipv4Addr>,
+}
+
+impl Impairment {
+    /// Delays every packet by `delay`
+    pub fn delay(&mut self, delay: Duration) -> &mut Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Adds a random variation of up to `jitter` to the delay; this may reorder packets
+    pub fn jitter(&mut self, jitter: Duration) -> &mut Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Drops `percent` percent of the packets, chosen at random
+    pub fn loss(&mut self, percent: f64) -> &mut Self {
+        self.loss_percent = percent;
+        self
+    }
+
+    /// Only degrades the packets sent to `ipv4_addr`; by default the packets sent to any peer
+    /// are degraded
+    pub fn peer(&mut self, ipv4_addr: Ipv4Addr) -> &mut Self {
+        self.peer = Some(ipv4_addr);
+        self
+    }
+
+    fn netem_args(&self) -> String {
+        let Self {
+            delay,
+            jitter,
+            loss_percent,
+            peer: _,
+        } = self;
+
+        format!(
+            "delay {}ms {}ms loss {loss_percent}%",
+            delay.as_millis(),
+            jitter.as_millis()
+        )
+    }
+}
+
+/// Network faults that can be injected into a server, e.g. a `NameServer` or a `Resolver`
+///
+/// The faults act on the server's own container so they don't affect the other servers
+pub trait Faults: sealed::HasContainer {
+    /// Degrades the packets the server sends, e.g. by adding latency or dropping some of them
+    ///
+    /// Apply it to the peer as well to also degrade the packets the server receives. When several
+    /// impairments apply to a peer the one added first wins. At most 15 impairments can be
+    /// active at once
+    fn impair(&self, impairment: &Impairment) -> Result<()> {
+        self.container().impair(impairment)
+    }
+
+    /// Drops all UDP traffic from and to `peer`, or from and to any peer if `None`, but not TCP
+    /// traffic
+    fn drop_udp(&self, peer: Option<Ipv4Addr>) -> Result<()> {
+        self.container().drop_udp(peer)
+    }
+
+    /// Makes the server unreachable: all the traffic from and to it is dropped
+    fn blackhole(&self) -> Result<()> {
+        self.container().blackhole()
+    }
+
+    /// Removes the faults injected with `impair`, `drop_udp` and `blackhole`, as well as the
+    /// redirections set up with `ResolverSettings::redirect`
+    fn clear_faults(&self) -> Result<()> {
+        self.container().clear_faults()
+    }
+}
+
+impl<T: sealed::HasContainer> Faults for T {}
+
+pub(crate) mod sealed {
+    use crate::container::Container;
+
+    /// Keeps `Faults` from being implemented outside this crate
+    pub trait HasContainer {
+        fn container(&self) -> &Container;
+    }
+}
+
+impl Container {
+    /// Degrades the packets this container sends according to `impairment`
+    ///
+    /// Each impairment gets its own `netem` instance; when several impairments apply to a peer
+    /// the one added first wins. At most 15 impairments can be active at once
+    pub(crate) fn impair(&self, impairment: &Impairment) -> Result<()> {
+        // held until the script has run so that concurrent calls neither add a band before the
+        // root qdisc exists nor undo each other's qdiscs
+        let mut links = self
+            .inner
+            .impaired_links
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if *links >= MAX_BANDS - 1 {
+            return Err(format!(
+                "at most {} impairments can be active at once",
+                MAX_BANDS - 1
+            )
+            .into());
+        }
+        // band 1 carries the traffic that is not impaired
+        let band = *links + 2;
+
+        let mut script = String::new();
+        if band == 2 {
+            let priomap = ["0"; MAX_BANDS].join(" ");
+            writeln!(
+                script,
+                "tc qdisc add dev {INTERFACE} root handle 1: prio bands {MAX_BANDS} priomap {priomap}"
+            )
+            .unwrap();
+        }
+
+        let destination = impairment
+            .peer
+            .map_or_else(|| "0.0.0.0/0".to_string(), |peer| format!("{peer}/32"));
+        writeln!(
+            script,
+            "tc qdisc add dev {INTERFACE} parent 1:{band:x} handle {band:x}0: netem {}
+tc filter add dev {INTERFACE} parent 1: protocol ip prio {band} u32 match ip dst {destination} flowid 1:{band:x}",
+            impairment.netem_args()
+        )
+        .unwrap();
+
+        let result = self.status_ok(&["sh", "-c", &format!("set -e\n{script}")]);
+        if result.is_ok() {
+            *links += 1;
+        } else {
+            // undo whatever part of the script succeeded so that the band can be used again
+            let undo = if band == 2 {
+                format!("tc qdisc del dev {INTERFACE} root")
+            } else {
+                format!(
+                    "tc filter del dev {INTERFACE} parent 1: prio {band}
+tc qdisc del dev {INTERFACE} parent 1:{band:x}"
+                )
+            };
+            let _ = self.status(&["sh", "-c", &format!("{undo} 2>/dev/null; true")]);
+        }
+        result
+    }
+
+    /// Drops all UDP traffic from and to `peer`, or from and to any peer if `None`; TCP traffic
+    /// is not affected
+    pub(crate) fn drop_udp(&self, peer: Option<Ipv4Addr>) -> Result<()> {
+        let (source, destination) = match peer {
+            Some(peer) => (format!("-s {peer}"), format!("-d {peer}")),
+            None => (String::new(), String::new()),
+        };
+
+        let script = format!(
+            "iptables -A INPUT ! -i lo -p udp {source} -j DROP
+iptables -A OUTPUT ! -o lo -p udp {destination} -j DROP"
+        );
+        self.status_ok(&["sh", "-c", &script])
+    }
+
+    /// Drops all the traffic from and to other containers, making this one unreachable
+    pub(crate) fn blackhole(&self) -> Result<()> {
+        let script = "iptables -A INPUT ! -i lo -j DROP
+iptables -A OUTPUT ! -o lo -j DROP";
+        self.status_ok(&["sh", "-c", script])
+    }
+
+    /// Sends the DNS traffic, UDP and TCP, this container addresses to `from` to `to` instead;
+    /// the replies from `to` look like they come from `from`
+    pub(crate) fn redirect_dns(&self, from: Ipv4Addr, to: Ipv4Addr) -> Result<()> {
+        let script = format!(
+            "iptables -t nat -A OUTPUT -d {from} -p udp --dport 53 -j DNAT --to-destination {to}
+iptables -t nat -A OUTPUT -d {from} -p tcp --dport 53 -j DNAT --to-destination {to}"
+        );
+        self.status_ok(&["sh", "-c", &script])
+    }
+
+    /// Removes the redirections set up with `redirect_dns`
+    pub(crate) fn clear_redirects(&self) -> Result<()> {
+        self.status_ok(&["iptables", "-t", "nat", "-F", "OUTPUT"])
+    }
+
+    /// Removes all the faults injected with `impair`, `drop_udp`, `blackhole` and
+    /// `redirect_dns`
+    pub(crate) fn clear_faults(&self) -> Result<()> {
+        let script = format!(
+            "tc qdisc del dev {INTERFACE} root 2>/dev/null || true
+iptables -F INPUT
+iptables -F OUTPUT
+iptables -t nat -F OUTPUT"
+        );
+        let mut links = self
+            .inner
+            .impaired_links
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.status_ok(&["sh", "-c", &script])?;
+        *links = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::client::{Client, DigSettings};
+    use crate::name_server::NameServer;
+    use crate::record::RecordType;
+    use crate::{Implementation, Network, FQDN};
+
+    use super::sealed::HasContainer;
+    use super::*;
+
+    #[test]
+    fn netem_args() {
+        let mut impairment = Impairment::default();
+        impairment
+            .delay(Duration::from_millis(100))
+            .jitter(Duration::from_millis(10))
+            .loss(2.5);
+
+        assert_eq!("delay 100ms 10ms loss 2.5%", impairment.netem_args());
+    }
+
+    #[test]
+    fn faults_can_be_injected_and_cleared() -> Result<()> {
+        let network = Network::new()?;
+        let ns = NameServer::new(&Implementation::Unbound, FQDN::ROOT, &network)?.start()?;
+        let client = Client::new(&network)?;
+        let dig = || {
+            client.dig(
+                DigSettings::default(),
+                ns.ipv4_addr(),
+                RecordType::SOA,
+                &FQDN::ROOT,
+            )
+        };
+
+        const DELAY: Duration = Duration::from_millis(500);
+        ns.impair(Impairment::default().delay(DELAY).peer(client.ipv4_addr()))?;
+        let start = Instant::now();
+        assert!(dig()?.status.is_noerror());
+        assert!(start.elapsed() >= DELAY);
+
+        ns.blackhole()?;
+        assert!(dig().is_err());
+
+        ns.clear_faults()?;
+        assert!(dig()?.status.is_noerror());
+
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_impairments_are_all_installed() -> Result<()> {
+        let network = Network::new()?;
+        let ns = NameServer::new(&Implementation::Unbound, FQDN::ROOT, &network)?.start()?;
+
+        // the errors are turned into strings as they cannot be sent between threads
+        let results = std::thread::scope(|scope| {
+            let handles = (1..=4)
+                .map(|peer| {
+                    let ns = &ns;
+                    scope.spawn(move || {
+                        ns.impair(Impairment::default().peer(Ipv4Addr::new(192, 0, 2, peer)))
+                            .map_err(|e| e.to_string())
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        for result in results {
+            result?;
+        }
+
+        let qdiscs = ns
+            .container()
+            .stdout(&["tc", "qdisc", "show", "dev", INTERFACE])?;
+        assert_eq!(4, qdiscs.matches("netem").count(), "{qdiscs}");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+use core::fmt;
+use std::io::{BufRead, BufReader, Read};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::Result;
+
+/// The output of a process, collected in the background as the process produces it
+#[derive(Clone)]
+pub(crate) struct Logs {
+    inner: Arc<(Mutex<State>, Condvar)>,
+}
+
+#[derive(Default)]
+struct State {
+    lines: Vec<String>,
+    eof: bool,
+}
+
+impl Logs {
+    /// Spawns a thread that reads `reader` line by line until EOF
+    pub(crate) fn collect(reader: impl Read + Send + 'static) -> (Self, JoinHandle<()>) {
+        let logs = Self {
+            inner: Arc::new((Mutex::new(State::default()), Condvar::new())),
+        };
+
+        let collector = logs.clone();
+        let handle = thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            let mut buf = vec![];
+            loop {
+                buf.clear();
+                match reader.read_until(b'\n', &mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {
+                        let line = String::from_utf8_lossy(&buf);
+                        // `docker exec -t` produces CRLF line endings
+                        let line = line.trim_end_matches(['\n', '\r']).to_string();
+                        collector.update(|state| state.lines.push(line));
+                    }
+                }
+            }
+            collector.update(|state| state.eof = true);
+        });
+
+        (logs, handle)
+    }
+
+    fn update(&self, f: impl FnOnce(&mut State)) {
+        let (state, condvar) = &*self.inner;
+        f(&mut state.lock().unwrap());
+        condvar.notify_all();
+    }
+
+    /// Returns all the lines collected so far
+    pub(crate) fn contents(&self) -> String {
+        let (state, _) = &*self.inner;
+        state.lock().unwrap().lines.join("\n")
+    }
+
+    /// Returns the last `n` lines collected so far
+    pub(crate) fn tail(&self, n: usize) -> String {
+        let (state, _) = &*self.inner;
+        let lines = &state.lock().unwrap().lines;
+        lines[lines.len().saturating_sub(n)..].join("\n")
+    }
+
+    /// Blocks until the process closes its output or `deadline` is reached
+    pub(crate) fn wait_for_eof(&self, deadline: Instant) {
+        let (state, condvar) = &*self.inner;
+        let mut state = state.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            if state.eof || now >= deadline {
+                return;
+            }
+
+            state = condvar.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    /// Blocks until a line that contains `needle` is collected, the process closes its output or
+    /// `deadline` is reached. Returns the first such line, if any
+    pub(crate) fn wait_for(&self, needle: &str, deadline: Instant) -> Option<String> {
+        let (state, condvar) = &*self.inner;
+        let mut state = state.lock().unwrap();
+        let mut checked = 0;
+        loop {
+            if let Some(line) = state.lines[checked..]
+                .iter()
+                .find(|line| line.contains(needle))
+            {
+                return Some(line.clone());
+            }
+            checked = state.lines.len();
+
+            let now = Instant::now();
+            if state.eof || now >= deadline {
+                return None;
+            }
+
+            state = condvar.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    /// Like `wait_for` but returns an error, which includes all the logs, if `server` does not log
+    /// a line that contains `pattern` within `timeout`
+    pub(crate) fn expect(
+        &self,
+        pattern: &str,
+        timeout: Duration,
+        server: &dyn fmt::Display,
+    ) -> Result<String> {
+        self.wait_for(pattern, Instant::now() + timeout)
+            .ok_or_else(|| {
+                format!(
+                    "`{server}` did not log `{pattern}` within {timeout:?}\n--- LOGS ---\n{}",
+                    self.contents()
+                )
+                .into()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn collects_lines() {
+        let (logs, handle) = Logs::collect(Cursor::new(b"starting\r\nready to serve\n"));
+        let deadline = Instant::now() + Duration::from_secs(1);
+
+        assert_eq!(
+            Some("ready to serve"),
+            logs.wait_for("ready", deadline).as_deref()
+        );
+        handle.join().unwrap();
+
+        assert_eq!(None, logs.wait_for("not there", deadline));
+        assert_eq!("starting\nready to serve", logs.contents());
+        assert_eq!("ready to serve", logs.tail(1));
+
+        let error = logs
+            .expect("not there", Duration::ZERO, &"server")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("ready to serve"));
+    }
+}
@@ -1,11 +1,15 @@
 use std::{
-    process::{self, Command, Stdio},
+    collections::HashMap,
+    net::Ipv4Addr,
+    process,
     sync::{
         atomic::{self, AtomicUsize},
-        Arc,
+        Arc, Mutex, Once,
     },
 };
 
+use crate::container::runtime::{self, runtime};
+use crate::container::Monitor;
 use crate::Result;
 
 /// Represents a network in which to put containers into.
@@ -22,15 +26,41 @@ impl Network {
     pub fn netmask(&self) -> &str {
         &self.0.config.subnet
     }
+
+    /// Registers the server running at `ipv4_addr` so that queries sent to it can check whether
+    /// it's still alive
+    pub(crate) fn register_server(&self, ipv4_addr: Ipv4Addr, monitor: Monitor) {
+        self.0.servers.lock().unwrap().insert(ipv4_addr, monitor);
+    }
+
+    /// Returns an error if the server running at `ipv4_addr` has exited on its own
+    ///
+    /// Addresses that do not belong to a registered server are considered alive
+    pub(crate) fn check_server(&self, ipv4_addr: Ipv4Addr) -> Result<()> {
+        let monitor = self.0.servers.lock().unwrap().get(&ipv4_addr).cloned();
+        match monitor {
+            Some(monitor) => monitor.check(ipv4_addr),
+            None => Ok(()),
+        }
+    }
 }
 
 struct NetworkInner {
     name: String,
     config: NetworkConfig,
+    servers: Mutex<HashMap<Ipv4Addr, Monitor>>,
 }
 
 impl Network {
     pub fn new() -> Result<Self> {
+        // remove whatever earlier, forcefully terminated, processes left behind
+        static SWEEP: Once = Once::new();
+        SWEEP.call_once(|| {
+            if let Err(e) = runtime::gc() {
+                eprintln!("could not remove leaked containers and networks: {e}");
+            }
+        });
+
         let pid = process::id();
         let network_name = env!("CARGO_PKG_NAME");
         Ok(Self(Arc::new(NetworkInner::new(pid, network_name)?)))
@@ -40,11 +70,7 @@ impl Network {
 /// This ensure the Docker network is deleted after the test runner process ends.
 impl Drop for NetworkInner {
     fn drop(&mut self) {
-        let _ = Command::new("docker")
-            .args(["network", "rm", "--force", self.name.as_str()])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .status();
+        runtime().remove_network(&self.name);
     }
 }
 
@@ -53,27 +79,13 @@ impl NetworkInner {
         let count = network_count();
         let network_name = format!("{network_name}-{pid}-{count}");
 
-        let mut command = Command::new("docker");
-        command
-            .args(["network", "create"])
-            .args(["--internal", "--attachable"])
-            .arg(&network_name);
-
-        // create network
-        let output = command.output()?;
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
-
-        if !output.status.success() {
-            return Err(format!("--- STDOUT ---\n{stdout}\n--- STDERR ---\n{stderr}").into());
-        }
-
-        // inspect & parse network details
-        let config = get_network_config(&network_name)?;
+        let subnet = runtime().create_network(&network_name)?;
+        let config = NetworkConfig { subnet };
 
         Ok(Self {
             name: network_name,
             config,
+            servers: Mutex::new(HashMap::new()),
         })
     }
 }
@@ -84,27 +96,6 @@ pub struct NetworkConfig {
     subnet: String,
 }
 
-/// Return network config
-fn get_network_config(network_name: &str) -> Result<NetworkConfig> {
-    let mut command = Command::new("docker");
-    command
-        .args([
-            "network",
-            "inspect",
-            "-f",
-            "{{range .IPAM.Config}}{{.Subnet}}{{end}}",
-        ])
-        .arg(network_name);
-
-    let output = command.output()?;
-    if !output.status.success() {
-        return Err(format!("{command:?} failed").into());
-    }
-
-    let subnet = std::str::from_utf8(&output.stdout)?.trim().to_string();
-    Ok(NetworkConfig { subnet })
-}
-
 fn network_count() -> usize {
     static COUNT: AtomicUsize = AtomicUsize::new(1);
 
@@ -118,13 +109,7 @@ mod tests {
     use super::*;
 
     fn exists_network(network_name: &str) -> bool {
-        let mut command = Command::new("docker");
-        command.args(["network", "ls", "--format={{ .Name }}"]);
-
-        let output = command.output().expect("Failed to get output");
-        let stdout = String::from_utf8_lossy(&output.stdout);
-
-        stdout.trim().lines().any(|line| line == network_name)
+        runtime().network_exists(network_name)
     }
 
     #[test]
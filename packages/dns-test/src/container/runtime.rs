@@ -0,0 +1,399 @@
+//! Container runtimes
+//!
+//! The runtime is selected with the `DNS_TEST_RUNTIME` environment variable
+
+mod netns;
+mod owner;
+
+use core::mem;
+use std::env;
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::process::{self, Command, Stdio};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+use self::netns::Netns;
+pub(crate) use self::owner::Owner;
+use super::checked_output;
+use crate::Result;
+
+/// Label with the PID of the process that created a container or network
+const PID_LABEL: &str = "dns-test.pid";
+/// Label with the `Owner`, in JSON form, of a container or network
+const OWNER_LABEL: &str = "dns-test.owner";
+
+lazy_static! {
+    static ref RUNTIME: Box<dyn Runtime> = parse_runtime();
+}
+
+/// Returns the runtime selected by the `DNS_TEST_RUNTIME` environment variable
+pub(crate) fn runtime() -> &'static dyn Runtime {
+    &**RUNTIME
+}
+
+fn parse_runtime() -> Box<dyn Runtime> {
+    let Ok(runtime) = env::var("DNS_TEST_RUNTIME") else {
+        return Box::new(Cli::DOCKER);
+    };
+
+    match runtime.as_str() {
+        "docker" => Box::new(Cli::DOCKER),
+        "podman" => Box::new(Cli::PODMAN),
+        "netns" => Box::new(Netns::new()),
+        _ => panic!("unknown runtime: {runtime}; expected one of 'docker', 'podman' or 'netns'"),
+    }
+}
+
+/// The operations `Container` and `Network` need from a container runtime
+pub(crate) trait Runtime: Send + Sync {
+    /// Returns `true` if an image tagged `tag` is available locally
+    fn image_exists(&self, tag: &str) -> bool;
+
+    /// Returns the command that builds the image described by the Dockerfile in `build_dir`
+    fn build(&self, tag: &str, build_dir: &Path, offline: bool) -> Command;
+
+    /// Makes the image tagged `image_tag` also available as `alias`
+    fn tag(&self, image_tag: &str, alias: &str) -> Result<()>;
+
+    /// Starts a container that does nothing and returns its ID
+    fn run(&self, image_tag: &str, network: &str, name: &str) -> Result<String>;
+
+    fn ipv4_addr(&self, id: &str) -> Result<Ipv4Addr>;
+
+    /// Returns the command that runs `command_and_args` inside the container
+    ///
+    /// The stderr of the command will be merged into its stdout
+    fn exec(&self, id: &str, command_and_args: &[&str]) -> Result<Command>;
+
+    /// Copies the file at `src_path` on the host to `path_in_container`
+    fn cp(&self, src_path: &Path, id: &str, path_in_container: &str) -> Result<()>;
+
+    /// Removes the container without waiting for the operation to complete
+    ///
+    /// `network` is the network the container is attached to, if known; removing that network
+    /// waits for the container to be gone
+    fn remove(&self, id: &str, network: Option<&str>);
+
+    /// Creates an internal network and returns its subnet mask in CIDR form
+    fn create_network(&self, name: &str) -> Result<String>;
+
+    /// Removes the network once the removals of the containers attached to it have completed
+    fn remove_network(&self, name: &str);
+
+    /// Lists all the containers and networks created by `dns-test`, by any process
+    fn list(&self) -> Result<Vec<Resource>>;
+
+    /// Returns `true` if the network named `name` exists
+    #[cfg(test)]
+    fn network_exists(&self, name: &str) -> bool;
+}
+
+/// A container or network created by `dns-test`
+pub(crate) struct Resource {
+    pub(crate) kind: ResourceKind,
+    /// container ID or network name
+    pub(crate) id: String,
+    /// The process that created the resource
+    pub(crate) owner: Owner,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum ResourceKind {
+    Container,
+    Network,
+}
+
+/// Removes the containers and networks whose owner process is known to have exited
+///
+/// Resources created on other hosts or in other PID namespaces are left alone
+///
+/// Returns the number of removed resources
+pub(crate) fn gc() -> Result<usize> {
+    let runtime = runtime();
+    let mut resources = runtime.list()?;
+    resources.retain(|resource| resource.owner.is_dead());
+
+    // networks cannot be removed while containers are attached to them
+    resources.sort_by_key(|resource| resource.kind == ResourceKind::Network);
+    for resource in &resources {
+        match resource.kind {
+            ResourceKind::Container => runtime.remove(&resource.id, None),
+            ResourceKind::Network => runtime.remove_network(&resource.id),
+        }
+    }
+
+    Ok(resources.len())
+}
+
+fn labels() -> [String; 2] {
+    let owner = Owner::current();
+    [
+        format!("{PID_LABEL}={}", owner.pid),
+        format!(
+            "{OWNER_LABEL}={}",
+            serde_json::to_string(owner).expect("could not serialize the owner")
+        ),
+    ]
+}
+
+/// Removals of containers that are still running in the background, along with the network the
+/// container is attached to, if known
+static PENDING_REMOVALS: Mutex<Vec<(Option<String>, process::Child)>> = Mutex::new(Vec::new());
+
+/// A runtime driven through a Docker compatible command line interface
+struct Cli {
+    program: &'static str,
+    network_create_args: &'static [&'static str],
+    subnet_format: &'static str,
+}
+
+impl Cli {
+    const DOCKER: Self = Self {
+        program: "docker",
+        network_create_args: &["--internal", "--attachable"],
+        subnet_format: "{{range .IPAM.Config}}{{.Subnet}}{{end}}",
+    };
+
+    const PODMAN: Self = Self {
+        program: "podman",
+        network_create_args: &["--internal"],
+        subnet_format: "{{range .Subnets}}{{.Subnet}}{{end}}",
+    };
+
+    fn command(&self) -> Command {
+        Command::new(self.program)
+    }
+
+    /// Lists the resources of `kind` that have the PID label
+    fn list_kind(&self, kind: ResourceKind) -> Result<Vec<Resource>> {
+        let (list, inspect, format) = match kind {
+            ResourceKind::Container => (
+                ["ps", "--all", "--quiet"].as_slice(),
+                ["inspect", "-f"].as_slice(),
+                format!("{{{{index .Config.Labels \"{OWNER_LABEL}\"}}}}"),
+            ),
+            ResourceKind::Network => (
+                ["network", "ls", "--quiet"].as_slice(),
+                ["network", "inspect", "-f"].as_slice(),
+                format!("{{{{index .Labels \"{OWNER_LABEL}\"}}}}"),
+            ),
+        };
+
+        let mut command = self.command();
+        command
+            .args(list)
+            .args(["--filter", &format!("label={PID_LABEL}")]);
+        let output = checked_output(&mut command)?;
+
+        let mut resources = vec![];
+        for id in String::from_utf8(output.stdout)?.lines() {
+            let mut command = self.command();
+            command.args(inspect).arg(&format).arg(id);
+            // the resource may have been removed in the meantime
+            let Ok(output) = checked_output(&mut command) else {
+                continue;
+            };
+
+            // resources without a (valid) owner are never removed
+            if let Ok(owner) = serde_json::from_slice(&output.stdout) {
+                resources.push(Resource {
+                    kind,
+                    id: id.to_string(),
+                    owner,
+                });
+            }
+        }
+
+        Ok(resources)
+    }
+
+    /// Fire and forget: runs `command`, which removes a container attached to `network`, in the
+    /// background
+    ///
+    /// The child is reaped by a later call of this function or of `wait_for_removals`
+    fn spawn_quietly(mut command: Command, network: Option<&str>) {
+        let child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+
+        let mut pending = PENDING_REMOVALS.lock().unwrap();
+        pending.retain_mut(|(_, child)| !matches!(child.try_wait(), Ok(Some(_))));
+        if let Ok(child) = child {
+            pending.push((network.map(str::to_string), child));
+        }
+    }
+
+    /// Blocks until the removals of the containers that are, or may be, attached to `network`
+    /// have completed
+    fn wait_for_removals(network: &str) {
+        let pending = {
+            let mut pending = PENDING_REMOVALS.lock().unwrap();
+            let (attached, others) =
+                mem::take(&mut *pending)
+                    .into_iter()
+                    .partition(|(attached_to, _)| {
+                        attached_to
+                            .as_deref()
+                            .is_none_or(|attached_to| attached_to == network)
+                    });
+            *pending = others;
+            attached
+        };
+
+        for (_, mut child) in pending {
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Runtime for Cli {
+    fn image_exists(&self, tag: &str) -> bool {
+        self.command()
+            .args(["image", "inspect", tag])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+
+    fn build(&self, tag: &str, build_dir: &Path, offline: bool) -> Command {
+        let mut command = self.command();
+        command.args(["build", "-t", tag]);
+        if offline {
+            command.args(["--network", "none", "--build-arg", "CARGO_NET_OFFLINE=true"]);
+        }
+        command.arg(build_dir);
+        command
+    }
+
+    fn tag(&self, image_tag: &str, alias: &str) -> Result<()> {
+        let mut command = self.command();
+        command.args(["tag", image_tag, alias]);
+        checked_output(&mut command)?;
+        Ok(())
+    }
+
+    fn run(&self, image_tag: &str, network: &str, name: &str) -> Result<String> {
+        let mut command = self.command();
+        command
+            .args([
+                "run",
+                "--rm",
+                "--detach",
+                "--cap-add=NET_RAW",
+                "--cap-add=NET_ADMIN",
+                "--network",
+                network,
+                "--name",
+                name,
+                "-it",
+            ])
+            .args(labels().iter().flat_map(|label| ["--label", label]))
+            .arg(image_tag)
+            .args(["sleep", "infinity"]);
+
+        let output = checked_output(&mut command)?;
+        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+    }
+
+    fn ipv4_addr(&self, id: &str) -> Result<Ipv4Addr> {
+        let mut command = self.command();
+        command
+            .args([
+                "inspect",
+                "-f",
+                "{{range.NetworkSettings.Networks}}{{.IPAddress}}{{end}}",
+            ])
+            .arg(id);
+
+        let output = checked_output(&mut command)?;
+        let ipv4_addr = String::from_utf8(output.stdout)?.trim().to_string();
+
+        Ok(ipv4_addr.parse()?)
+    }
+
+    fn exec(&self, id: &str, command_and_args: &[&str]) -> Result<Command> {
+        let mut command = self.command();
+        // `-t` merges stderr into stdout
+        command.args(["exec", "-t", id]).args(command_and_args);
+        Ok(command)
+    }
+
+    fn cp(&self, src_path: &Path, id: &str, path_in_container: &str) -> Result<()> {
+        let mut command = self.command();
+        command
+            .arg("cp")
+            .arg(src_path)
+            .arg(format!("{id}:{path_in_container}"));
+        checked_output(&mut command)?;
+
+        Ok(())
+    }
+
+    fn remove(&self, id: &str, network: Option<&str>) {
+        let mut command = self.command();
+        command.args(["rm", "-f", id]);
+        Self::spawn_quietly(command, network);
+    }
+
+    fn create_network(&self, name: &str) -> Result<String> {
+        let mut command = self.command();
+        command
+            .args(["network", "create"])
+            .args(self.network_create_args)
+            .args(labels().iter().flat_map(|label| ["--label", label]))
+            .arg(name);
+
+        // create network
+        let output = command.output()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        if !output.status.success() {
+            return Err(format!("--- STDOUT ---\n{stdout}\n--- STDERR ---\n{stderr}").into());
+        }
+
+        // inspect & parse network details
+        let mut command = self.command();
+        command
+            .args(["network", "inspect", "-f", self.subnet_format])
+            .arg(name);
+
+        let output = checked_output(&mut command)?;
+        let subnet = String::from_utf8(output.stdout)?.trim().to_string();
+
+        Ok(subnet)
+    }
+
+    fn remove_network(&self, name: &str) {
+        // a network cannot be removed while containers are still attached to it
+        Self::wait_for_removals(name);
+
+        let mut command = self.command();
+        command
+            .args(["network", "rm", "--force", name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        let _ = command.status();
+    }
+
+    fn list(&self) -> Result<Vec<Resource>> {
+        let mut resources = self.list_kind(ResourceKind::Container)?;
+        resources.extend(self.list_kind(ResourceKind::Network)?);
+        Ok(resources)
+    }
+
+    #[cfg(test)]
+    fn network_exists(&self, name: &str) -> bool {
+        self.command()
+            .args(["network", "inspect", name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+}
@@ -0,0 +1,397 @@
+//! A runtime that runs the DNS software installed on the host inside per-container Linux network
+//! and mount namespaces
+//!
+//! Each `Network` is a bridge on the host and each container is a network namespace connected to
+//! that bridge through a veth pair. `/etc` and `/var` are overlay mounts, and `/tmp` a tmpfs,
+//! private to each container so that configuration files, zone files and PID files do not clash.
+//!
+//! This runtime must run as root and requires `ip` (iproute2), `nsenter` and `unshare`
+//! (util-linux) on the host. Images are not used; all the software that the containers would
+//! provide (e.g. `unbound`, `nsd`, `named`, `hickory-dns`, `ldnsutils`, `dig`, `tshark`) must be
+//! installed on the host.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{Owner, Resource, ResourceKind, Runtime};
+use crate::container::{checked_output, ContentHasher};
+use crate::Result;
+
+const STATE_DIR: &str = "/run/dns-test";
+/// File that contains the `Owner`, in JSON form, of a container or network
+const OWNER_FILE: &str = "owner";
+/// File that contains the subnet claimed by a network
+const SUBNET_FILE: &str = "subnet";
+
+pub(super) struct Netns {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    networks: HashMap<String, NetworkState>,
+    containers: HashMap<String, ContainerState>,
+}
+
+struct NetworkState {
+    bridge: String,
+    /// the first three octets of the /24 subnet
+    prefix: [u8; 3],
+    next_host: u8,
+}
+
+struct ContainerState {
+    holder: process::Child,
+    ipv4_addr: Ipv4Addr,
+}
+
+impl Netns {
+    pub(super) fn new() -> Self {
+        Self {
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    fn holder_pid(&self, id: &str) -> Result<u32> {
+        let state = self.state.lock().unwrap();
+        let container = state
+            .containers
+            .get(id)
+            .ok_or_else(|| format!("unknown container: {id}"))?;
+        Ok(container.holder.id())
+    }
+}
+
+impl Runtime for Netns {
+    fn image_exists(&self, _tag: &str) -> bool {
+        // the software installed on the host is used instead of images
+        true
+    }
+
+    fn build(&self, _tag: &str, _build_dir: &Path, _offline: bool) -> Command {
+        Command::new("true")
+    }
+
+    fn tag(&self, _image_tag: &str, _alias: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn run(&self, _image_tag: &str, network: &str, name: &str) -> Result<String> {
+        let (ipv4_addr, bridge) = {
+            let mut state = self.state.lock().unwrap();
+            let network = state
+                .networks
+                .get_mut(network)
+                .ok_or_else(|| format!("unknown network: {network}"))?;
+            let host = network.next_host;
+            network.next_host = host
+                .checked_add(1)
+                .ok_or("no IPv4 addresses left in the network")?;
+            let [a, b, c] = network.prefix;
+            (Ipv4Addr::new(a, b, c, host), network.bridge.clone())
+        };
+
+        // written first so that `gc` can find whatever a forcefully terminated process leaves
+        // behind
+        let owner = serde_json::to_string(Owner::current())?;
+        let state_dir = container_dir(name);
+        fs::create_dir_all(&state_dir)?;
+        let holder = fs::write(state_dir.join(OWNER_FILE), owner)
+            .map_err(Into::into)
+            .and_then(|()| setup_container(name, ipv4_addr, &bridge, &state_dir));
+        let holder = match holder {
+            Ok(holder) => holder,
+            Err(e) => {
+                // undo the partial setup
+                self.remove(name, None);
+                return Err(e);
+            }
+        };
+
+        self.state
+            .lock()
+            .unwrap()
+            .containers
+            .insert(name.to_string(), ContainerState { holder, ipv4_addr });
+
+        Ok(name.to_string())
+    }
+
+    fn ipv4_addr(&self, id: &str) -> Result<Ipv4Addr> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .containers
+            .get(id)
+            .ok_or_else(|| format!("unknown container: {id}"))?
+            .ipv4_addr)
+    }
+
+    fn exec(&self, id: &str, command_and_args: &[&str]) -> Result<Command> {
+        let pid = self.holder_pid(id)?;
+
+        let mut command = Command::new("nsenter");
+        command
+            .args(["--target", &pid.to_string(), "--net", "--mount", "--"])
+            // merge stderr into stdout, like `docker exec -t` does
+            .args(["sh", "-c", "exec \"$@\" 2>&1", "sh"])
+            .args(command_and_args);
+        Ok(command)
+    }
+
+    fn cp(&self, src_path: &Path, id: &str, path_in_container: &str) -> Result<()> {
+        let mut command = self.exec(id, &["sh", "-c", "cat > \"$0\"", path_in_container])?;
+        command.stdin(File::open(src_path)?);
+        checked_output(&mut command)?;
+
+        Ok(())
+    }
+
+    fn remove(&self, id: &str, _network: Option<&str>) {
+        if let Some(mut container) = self.state.lock().unwrap().containers.remove(id) {
+            let _ = container.holder.kill();
+            let _ = container.holder.wait();
+        }
+
+        // kill processes that were `exec`-ed into the container
+        let _ = Command::new("sh")
+            .args(["-c", "ip netns pids \"$0\" | xargs -r kill -9", id])
+            .status();
+        let _ = ip(&["netns", "del", id]);
+        let _ = fs::remove_dir_all(container_dir(id));
+    }
+
+    fn create_network(&self, name: &str) -> Result<String> {
+        let prefix = claim_subnet(&subnets_dir(), name)?;
+
+        let network_dir = network_dir(name);
+        fs::create_dir_all(&network_dir)?;
+        fs::write(
+            network_dir.join(OWNER_FILE),
+            serde_json::to_string(Owner::current())?,
+        )?;
+        fs::write(network_dir.join(SUBNET_FILE), subnet_name(prefix))?;
+
+        let bridge = interface_name("db", name);
+        let created = ip(&["link", "add", &bridge, "type", "bridge"])
+            .and_then(|()| ip(&["link", "set", &bridge, "up"]));
+        if let Err(e) = created {
+            // releases the subnet
+            self.remove_network(name);
+            return Err(e);
+        }
+
+        self.state.lock().unwrap().networks.insert(
+            name.to_string(),
+            NetworkState {
+                bridge,
+                prefix,
+                // `.1` is conventionally the gateway; leave it unused
+                next_host: 2,
+            },
+        );
+
+        Ok(format!("{}/24", subnet_name(prefix)))
+    }
+
+    fn remove_network(&self, name: &str) {
+        // the bridge name is derived from the network name so this also works for networks
+        // created by other processes
+        self.state.lock().unwrap().networks.remove(name);
+        let _ = ip(&["link", "del", &interface_name("db", name)]);
+
+        let network_dir = network_dir(name);
+        if let Ok(subnet) = fs::read_to_string(network_dir.join(SUBNET_FILE)) {
+            let _ = fs::remove_dir(subnets_dir().join(subnet.trim()));
+        }
+        let _ = fs::remove_dir_all(network_dir);
+    }
+
+    fn list(&self) -> Result<Vec<Resource>> {
+        let mut resources = vec![];
+        for (kind, dir) in [
+            (ResourceKind::Container, container_dir("")),
+            (ResourceKind::Network, network_dir("")),
+        ] {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+
+            for entry in entries {
+                let entry = entry?;
+                let Ok(owner) = fs::read_to_string(entry.path().join(OWNER_FILE)) else {
+                    continue;
+                };
+
+                if let Ok(owner) = serde_json::from_str(&owner) {
+                    resources.push(Resource {
+                        kind,
+                        id: entry.file_name().to_string_lossy().into_owned(),
+                        owner,
+                    });
+                }
+            }
+        }
+
+        Ok(resources)
+    }
+
+    #[cfg(test)]
+    fn network_exists(&self, name: &str) -> bool {
+        let bridge = interface_name("db", name);
+        Path::new("/sys/class/net").join(bridge).exists()
+    }
+}
+
+/// Creates the namespaces of container `name`, connects it to `bridge` and returns the process
+/// that holds the namespaces
+fn setup_container(
+    name: &str,
+    ipv4_addr: Ipv4Addr,
+    bridge: &str,
+    state_dir: &Path,
+) -> Result<process::Child> {
+    ip(&["netns", "add", name])?;
+
+    let veth = interface_name("dv", name);
+    ip(&[
+        "link", "add", &veth, "type", "veth", "peer", "name", "eth0", "netns", name,
+    ])?;
+    ip(&["link", "set", &veth, "master", bridge, "up"])?;
+    ip(&[
+        "-n",
+        name,
+        "addr",
+        "add",
+        &format!("{ipv4_addr}/24"),
+        "dev",
+        "eth0",
+    ])?;
+    ip(&["-n", name, "link", "set", "eth0", "up"])?;
+    ip(&["-n", name, "link", "set", "lo", "up"])?;
+
+    let ready_file = state_dir.join("ready");
+
+    // `unshare` without `--fork` execs `sh` in a new mount namespace so `holder`'s PID is the
+    // PID of a process that lives in both namespaces
+    let setup = format!(
+        "set -e
+mount --make-rprivate /
+mount -t tmpfs tmpfs /tmp
+for dir in /etc /var; do
+    mkdir -p {state}/upper$dir {state}/work$dir
+    mount -t overlay overlay -o lowerdir=$dir,upperdir={state}/upper$dir,workdir={state}/work$dir $dir
+done
+touch {ready}
+exec sleep infinity",
+        state = state_dir.display(),
+        ready = ready_file.display(),
+    );
+    let mut holder = Command::new("ip")
+        .args([
+            "netns", "exec", name, "unshare", "--mount", "sh", "-c", &setup,
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let start = Instant::now();
+    while !ready_file.exists() {
+        if start.elapsed() > Duration::from_secs(5) {
+            let _ = holder.kill();
+            let _ = holder.wait();
+            return Err(format!("container {name} did not start").into());
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    Ok(holder)
+}
+
+fn ip(args: &[&str]) -> Result<()> {
+    let mut command = Command::new("ip");
+    command.args(args);
+    checked_output(&mut command)?;
+
+    Ok(())
+}
+
+/// Claims a /24 subnet of `10.0.0.0/8` that no other network, of this or any other process, uses
+/// and returns its first three octets
+///
+/// Claims are directories in `subnets_dir`; creating a directory is atomic so two networks never
+/// claim the same subnet. The search starts at a subnet derived from `name`
+fn claim_subnet(subnets_dir: &Path, name: &str) -> Result<[u8; 3]> {
+    fs::create_dir_all(subnets_dir)?;
+
+    let mut hasher = ContentHasher::default();
+    hasher.write(name.as_bytes());
+    let [a, b, ..] = hasher.finish().to_be_bytes();
+    let start = u16::from_be_bytes([a, b]);
+
+    for offset in 0..=u16::MAX {
+        let [a, b] = start.wrapping_add(offset).to_be_bytes();
+        let prefix = [10, a, b];
+        match fs::create_dir(subnets_dir.join(subnet_name(prefix))) {
+            Ok(()) => return Ok(prefix),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Err("all the /24 subnets of 10.0.0.0/8 are in use".into())
+}
+
+fn subnet_name([a, b, c]: [u8; 3]) -> String {
+    format!("{a}.{b}.{c}.0")
+}
+
+fn subnets_dir() -> PathBuf {
+    Path::new(STATE_DIR).join("subnets")
+}
+
+fn container_dir(name: &str) -> PathBuf {
+    Path::new(STATE_DIR).join("containers").join(name)
+}
+
+fn network_dir(name: &str) -> PathBuf {
+    Path::new(STATE_DIR).join("networks").join(name)
+}
+
+/// Linux interface names are limited to 15 characters so derive a short, unique-enough name from
+/// the (longer) resource name
+fn interface_name(prefix: &str, name: &str) -> String {
+    let mut hasher = ContentHasher::default();
+    hasher.write(name.as_bytes());
+    let hash = format!("{:016x}", hasher.finish());
+    format!("{prefix}{}", &hash[..12])
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn subnets_are_never_claimed_twice() -> Result<()> {
+        let subnets_dir = TempDir::new()?;
+
+        // same name so both searches start at the same subnet
+        let first = claim_subnet(subnets_dir.path(), "dns-test-1-0")?;
+        let second = claim_subnet(subnets_dir.path(), "dns-test-1-0")?;
+        assert_ne!(first, second);
+
+        fs::remove_dir(subnets_dir.path().join(subnet_name(first)))?;
+        assert_eq!(first, claim_subnet(subnets_dir.path(), "dns-test-1-0")?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+//! Identity of the process that created a container or network
+//!
+//! A PID alone does not identify a process: PIDs are reused, and the same PID may belong to
+//! unrelated processes on other hosts or in other PID namespaces that share the container engine.
+//! An `Owner` records enough to tell whether the process that created a resource has exited
+
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fs, io, process};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+lazy_static! {
+    static ref CURRENT: Owner = Owner::of_current_process();
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct Owner {
+    pub(crate) pid: u32,
+    /// Start time of the process, in clock ticks since boot
+    start_time: Option<u64>,
+    host: Option<String>,
+    /// e.g. `pid:[4026531836]`
+    pid_namespace: Option<String>,
+    /// Unique to the process
+    run_id: String,
+}
+
+impl Owner {
+    /// Returns the identity of this process
+    pub(crate) fn current() -> &'static Self {
+        &CURRENT
+    }
+
+    /// Returns `true` only if the owner is positively known to have exited
+    ///
+    /// That is only the case when the owner ran on this host, in this PID namespace, and either
+    /// its PID is no longer in use or it's in use by a process that started at a different time
+    pub(crate) fn is_dead(&self) -> bool {
+        let current = Self::current();
+        if self.run_id == current.run_id {
+            return false;
+        }
+
+        let is_local = self.host.is_some()
+            && self.host == current.host
+            && self.pid_namespace.is_some()
+            && self.pid_namespace == current.pid_namespace;
+        if !is_local {
+            return false;
+        }
+
+        match process_start_time(self.pid) {
+            Ok(start_time) => self
+                .start_time
+                .is_some_and(|recorded| recorded != start_time),
+            Err(e) => e.kind() == io::ErrorKind::NotFound,
+        }
+    }
+
+    fn of_current_process() -> Self {
+        let pid = process::id();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos())
+            .unwrap_or_default();
+
+        Self {
+            pid,
+            start_time: process_start_time(pid).ok(),
+            host: fs::read_to_string("/proc/sys/kernel/hostname")
+                .ok()
+                .map(|host| host.trim().to_string()),
+            pid_namespace: fs::read_link("/proc/self/ns/pid")
+                .ok()
+                .map(|link| link.to_string_lossy().into_owned()),
+            run_id: format!("{pid}-{nanos}"),
+        }
+    }
+}
+
+/// Reads the start time of process `pid` from procfs
+///
+/// Fails with `NotFound` if there's no such process
+fn process_start_time(pid: u32) -> io::Result<u64> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat"))?;
+    parse_start_time(&stat)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed /proc/<pid>/stat"))
+}
+
+fn parse_start_time(stat: &str) -> Option<u64> {
+    // the second field, the command name, is in parentheses and may contain spaces
+    let (_, fields) = stat.rsplit_once(')')?;
+    // `starttime` is the 22nd field; the fields after the command name start at the 3rd one
+    fields.split_whitespace().nth(22 - 3)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner(pid: u32) -> Owner {
+        Owner {
+            pid,
+            run_id: format!("{pid}-0"),
+            ..Owner::current().clone()
+        }
+    }
+
+    #[test]
+    fn start_time_is_parsed() {
+        let stat = "1234 (a (weird) name) S 1 1234 1234 0 -1 4194560 100 0 0 0 1 2 0 0 20 0 1 0 \
+                    987654 12345678 200 18446744073709551615";
+        assert_eq!(Some(987654), parse_start_time(stat));
+    }
+
+    #[test]
+    fn current_process_is_alive() {
+        assert!(!Owner::current().is_dead());
+        // a different run ID, e.g. a copy read back from a label, is checked against procfs
+        assert!(!owner(process::id()).is_dead());
+    }
+
+    #[test]
+    fn reused_pid_is_dead() {
+        let mut owner = owner(process::id());
+        owner.start_time = owner.start_time.map(|start_time| start_time + 1);
+        assert!(owner.is_dead());
+    }
+
+    #[test]
+    fn exited_process_is_dead() -> io::Result<()> {
+        let mut child = process::Command::new("true").spawn()?;
+        let pid = child.id();
+        child.wait()?;
+
+        assert!(owner(pid).is_dead());
+
+        Ok(())
+    }
+
+    #[test]
+    fn owners_elsewhere_are_never_dead() {
+        let mut other_host = owner(u32::MAX);
+        other_host.host = Some("elsewhere".to_string());
+        assert!(!other_host.is_dead());
+
+        let mut unknown_namespace = owner(u32::MAX);
+        unknown_namespace.pid_namespace = None;
+        assert!(!unknown_namespace.is_dead());
+    }
+}
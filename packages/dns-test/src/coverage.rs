@@ -0,0 +1,633 @@
+//! Traceability between RFC requirements and the tests of the `conformance-tests` suite
+//!
+//! The requirements live in per-RFC catalogs: the TOML files in the `requirements` directory of
+//! the suite. A test claims to check a requirement with a tag: a line comment in the block above
+//! its `#[test]` attribute, optionally followed by a quote of the RFC
+//!
+//! ``` text
+//! // RFC 4035 section 3.2.2 MUST [copy-cd-bit]: "The name server side of a security-aware
+//! // recursive name server MUST copy the setting of the CD bit from a query to the
+//! // corresponding response"
+//! #[test]
+//! fn copies_cd_bit_from_query_to_response() -> Result<()> {
+//! ```
+//!
+//! A test may carry several tags. Tags may reference RFCs that have no catalog; their
+//! identifiers are then not checked. `Report` checks the tags against the catalogs and renders
+//! which requirements have tests and which do not as Markdown
+
+use core::fmt;
+use core::str::FromStr;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::Result;
+
+const CATALOG_DIR: &str = "requirements";
+const SOURCE_DIR: &str = "src";
+
+/// Requirement level, as defined in RFC 2119
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Keyword {
+    #[serde(rename = "MUST")]
+    Must,
+    #[serde(rename = "MUST NOT")]
+    MustNot,
+    #[serde(rename = "SHOULD")]
+    Should,
+    #[serde(rename = "SHOULD NOT")]
+    ShouldNot,
+    #[serde(rename = "MAY")]
+    May,
+}
+
+impl Keyword {
+    const ALL: [Self; 5] = [
+        Self::Must,
+        Self::MustNot,
+        Self::Should,
+        Self::ShouldNot,
+        Self::May,
+    ];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Must => "MUST",
+            Self::MustNot => "MUST NOT",
+            Self::Should => "SHOULD",
+            Self::ShouldNot => "SHOULD NOT",
+            Self::May => "MAY",
+        }
+    }
+}
+
+impl FromStr for Keyword {
+    type Err = crate::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|keyword| keyword.as_str() == input)
+            .ok_or_else(|| format!("unknown requirement keyword `{input}`").into())
+    }
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The requirements of an RFC that the suite aims to cover
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Catalog {
+    pub rfc: u16,
+    pub title: String,
+    #[serde(rename = "requirement")]
+    pub requirements: Vec<Requirement>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Requirement {
+    /// Unique within the catalog, e.g. `copy-cd-bit`
+    pub id: String,
+    /// e.g. `3.2.2`
+    pub section: String,
+    pub keyword: Keyword,
+    /// A paraphrase of the requirement
+    pub summary: String,
+}
+
+impl Catalog {
+    /// Loads all the `*.toml` catalogs in `dir`, sorted by RFC number
+    pub fn load_dir(dir: &Path) -> Result<Vec<Self>> {
+        let mut catalogs = vec![];
+        for path in files(dir, "toml")? {
+            let toml = fs::read_to_string(&path)?;
+            let catalog = toml
+                .parse::<Self>()
+                .map_err(|e| format!("{}: {e}", path.display()))?;
+            catalogs.push(catalog);
+        }
+
+        catalogs.sort_by_key(|catalog| catalog.rfc);
+        if let Some(pair) = catalogs.windows(2).find(|pair| pair[0].rfc == pair[1].rfc) {
+            return Err(format!("RFC {} has more than one catalog", pair[0].rfc).into());
+        }
+
+        Ok(catalogs)
+    }
+
+    pub fn requirement(&self, id: &str) -> Option<&Requirement> {
+        self.requirements
+            .iter()
+            .find(|requirement| requirement.id == id)
+    }
+}
+
+impl FromStr for Catalog {
+    type Err = crate::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let catalog: Self = toml::from_str(input)?;
+
+        let mut ids = BTreeSet::new();
+        for requirement in &catalog.requirements {
+            if !ids.insert(requirement.id.as_str()) {
+                return Err(format!("duplicate requirement `{}`", requirement.id).into());
+            }
+        }
+
+        Ok(catalog)
+    }
+}
+
+/// A claim that a test checks a requirement, e.g. `RFC 4035 section 3.2.2 MUST [copy-cd-bit]`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tag {
+    pub rfc: u16,
+    pub section: String,
+    pub keyword: Keyword,
+    pub id: String,
+}
+
+impl FromStr for Tag {
+    type Err = crate::Error;
+
+    /// Parses a tag, ignoring the quote that may follow it
+    fn from_str(input: &str) -> Result<Self> {
+        let error = || {
+            format!("expected `RFC <number> section <section> <KEYWORD> [<id>]`, found `{input}`")
+        };
+
+        let rest = input.strip_prefix("RFC ").ok_or_else(error)?;
+        let (rfc, rest) = rest.split_once(" section ").ok_or_else(error)?;
+        let (section, rest) = rest.split_once(' ').ok_or_else(error)?;
+        let (keyword, rest) = rest.split_once(" [").ok_or_else(error)?;
+        let (id, rest) = rest.split_once(']').ok_or_else(error)?;
+
+        if !(rest.is_empty() || rest.starts_with(':')) || id.is_empty() || id.contains(' ') {
+            return Err(error().into());
+        }
+
+        Ok(Self {
+            rfc: rfc.parse().map_err(|_| error())?,
+            section: section.to_string(),
+            keyword: keyword.parse()?,
+            id: id.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            rfc,
+            section,
+            keyword,
+            id,
+        } = self;
+
+        write!(f, "RFC {rfc} section {section} {keyword} [{id}]")
+    }
+}
+
+/// A test function and the tags above it
+#[derive(Debug, PartialEq)]
+pub struct Test {
+    /// The full path of the test, e.g. `resolver::dnssec::rfc4035::section_4::section_4_1::foo`
+    pub name: String,
+    pub ignored: bool,
+    pub tags: Vec<Tag>,
+}
+
+/// Collects the tests of the crate whose sources are in `src_dir`, sorted by name
+pub fn scan(src_dir: &Path) -> Result<Vec<Test>> {
+    let mut tests = vec![];
+    for path in files(src_dir, "rs")? {
+        let module = module_path(path.strip_prefix(src_dir)?);
+        let source = fs::read_to_string(&path)?;
+        let found =
+            scan_source(&module, &source).map_err(|e| format!("{}: {e}", path.display()))?;
+        tests.extend(found);
+    }
+
+    tests.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(tests)
+}
+
+fn scan_source(module: &str, source: &str) -> Result<Vec<Test>> {
+    let mut tests = vec![];
+    let mut tags = vec![];
+    let mut is_test = false;
+    let mut ignored = false;
+
+    for (index, line) in source.lines().enumerate() {
+        let line = line.trim();
+
+        if let Some(comment) = line.strip_prefix("//") {
+            let comment = comment.trim();
+            // plain citations, e.g. `// RFC 6891 section 6.1.1: "..."`, are not tags
+            if comment.starts_with("RFC ") && comment.contains('[') {
+                let tag = comment
+                    .parse()
+                    .map_err(|e| format!("line {}: {e}", index + 1))?;
+                tags.push(tag);
+            }
+        } else if line.starts_with("#[") {
+            is_test |= line == "#[test]";
+            ignored |= line.starts_with("#[ignore");
+        } else {
+            let signature = line.strip_prefix("pub ").unwrap_or(line);
+            if let Some(name) = signature.strip_prefix("fn ") {
+                if is_test {
+                    let name = name.split(['(', '<']).next().unwrap_or_default();
+                    tests.push(Test {
+                        name: if module.is_empty() {
+                            name.to_string()
+                        } else {
+                            format!("{module}::{name}")
+                        },
+                        ignored,
+                        tags: std::mem::take(&mut tags),
+                    });
+                }
+            }
+
+            if !tags.is_empty() {
+                return Err(format!(
+                    "line {}: tags must be above a `#[test]` function",
+                    index + 1
+                )
+                .into());
+            }
+            is_test = false;
+            ignored = false;
+        }
+    }
+
+    Ok(tests)
+}
+
+/// The requirements of the catalogs and the tests that cover them
+pub struct Report {
+    catalogs: Vec<Catalog>,
+    tests: Vec<Test>,
+}
+
+impl Report {
+    /// Loads the catalogs and scans the tests of the crate in `crate_dir`, e.g. the
+    /// `conformance-tests` package
+    pub fn load(crate_dir: &Path) -> Result<Self> {
+        let catalogs = Catalog::load_dir(&crate_dir.join(CATALOG_DIR))?;
+        let tests = scan(&crate_dir.join(SOURCE_DIR))?;
+        Self::new(catalogs, tests)
+    }
+
+    /// Fails if a tag references a requirement that is not in the catalog of its RFC or
+    /// disagrees with the catalog about the section or keyword of the requirement
+    pub fn new(catalogs: Vec<Catalog>, tests: Vec<Test>) -> Result<Self> {
+        let mut errors = vec![];
+        for test in &tests {
+            for tag in &test.tags {
+                let Some(catalog) = catalogs.iter().find(|catalog| catalog.rfc == tag.rfc) else {
+                    continue;
+                };
+
+                match catalog.requirement(&tag.id) {
+                    None => errors.push(format!(
+                        "{}: RFC {} has no requirement `{}`",
+                        test.name, tag.rfc, tag.id
+                    )),
+                    Some(requirement)
+                        if requirement.section != tag.section
+                            || requirement.keyword != tag.keyword =>
+                    {
+                        errors.push(format!(
+                            "{}: `{tag}` does not match the catalog: section {} {}",
+                            test.name, requirement.section, requirement.keyword
+                        ))
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors.join("\n").into());
+        }
+
+        Ok(Self { catalogs, tests })
+    }
+
+    /// Returns the tests that are tagged with the requirement `id` of `rfc`
+    pub fn tests(&self, rfc: u16, id: &str) -> Vec<&Test> {
+        self.tests
+            .iter()
+            .filter(|test| test.tags.iter().any(|tag| tag.rfc == rfc && tag.id == id))
+            .collect()
+    }
+
+    /// Returns the catalogued requirements that have no tests
+    pub fn uncovered(&self) -> Vec<(&Catalog, &Requirement)> {
+        self.catalogs
+            .iter()
+            .flat_map(|catalog| {
+                catalog
+                    .requirements
+                    .iter()
+                    .map(move |requirement| (catalog, requirement))
+            })
+            .filter(|(catalog, requirement)| self.tests(catalog.rfc, &requirement.id).is_empty())
+            .collect()
+    }
+
+    /// Renders the report as Markdown
+    pub fn to_markdown(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# RFC requirement coverage")?;
+
+        for catalog in &self.catalogs {
+            writeln!(f, "\n## RFC {}: {}\n", catalog.rfc, catalog.title)?;
+
+            let mut summary = vec![];
+            for keyword in Keyword::ALL {
+                let requirements = catalog
+                    .requirements
+                    .iter()
+                    .filter(|requirement| requirement.keyword == keyword)
+                    .collect::<Vec<_>>();
+                if requirements.is_empty() {
+                    continue;
+                }
+
+                let covered = requirements
+                    .iter()
+                    .filter(|requirement| !self.tests(catalog.rfc, &requirement.id).is_empty())
+                    .count();
+                summary.push(format!("{keyword} {covered}/{}", requirements.len()));
+            }
+            writeln!(f, "covered: {}\n", summary.join(", "))?;
+
+            writeln!(f, "| section | keyword | requirement | tests |")?;
+            writeln!(f, "|---------|---------|-------------|-------|")?;
+            for requirement in &catalog.requirements {
+                let tests = self.tests(catalog.rfc, &requirement.id);
+                let tests = if tests.is_empty() {
+                    "**none**".to_string()
+                } else {
+                    tests
+                        .iter()
+                        .map(|test| test_cell(test))
+                        .collect::<Vec<_>>()
+                        .join("<br>")
+                };
+
+                writeln!(
+                    f,
+                    "| {} | {} | `{}`: {} | {tests} |",
+                    requirement.section, requirement.keyword, requirement.id, requirement.summary
+                )?;
+            }
+        }
+
+        let uncatalogued = self
+            .tests
+            .iter()
+            .flat_map(|test| test.tags.iter().map(move |tag| (tag, test)))
+            .filter(|(tag, _)| !self.catalogs.iter().any(|catalog| catalog.rfc == tag.rfc))
+            .collect::<Vec<_>>();
+        if !uncatalogued.is_empty() {
+            writeln!(f, "\n## Requirements of other RFCs\n")?;
+            for (tag, test) in uncatalogued {
+                writeln!(f, "- {tag}: {}", test_cell(test))?;
+            }
+        }
+
+        let untagged = self
+            .tests
+            .iter()
+            .filter(|test| test.tags.is_empty())
+            .collect::<Vec<_>>();
+        if !untagged.is_empty() {
+            writeln!(f, "\n## Untagged tests\n")?;
+            for test in untagged {
+                writeln!(f, "- {}", test_cell(test))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn test_cell(test: &Test) -> String {
+    if test.ignored {
+        format!("`{}` (ignored)", test.name)
+    } else {
+        format!("`{}`", test.name)
+    }
+}
+
+/// Returns the files in `dir` and its subdirectories that have the given extension, sorted
+fn files(dir: &Path, extension: &str) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("could not read {}: {e}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(self::files(&path, extension)?);
+        } else if path.extension().is_some_and(|ext| ext == extension) {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Maps the path of a source file, relative to `src`, to its module path, e.g.
+/// `resolver/dnssec.rs` to `resolver::dnssec`
+fn module_path(path: &Path) -> String {
+    let mut segments = path
+        .with_extension("")
+        .iter()
+        .map(|segment| segment.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    if segments
+        .last()
+        .is_some_and(|last| ["lib", "main", "mod"].contains(&last.as_str()))
+    {
+        segments.pop();
+    }
+
+    segments.join("::")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CATALOG: &str = r#"
+rfc = 4035
+title = "Protocol Modifications for the DNS Security Extensions"
+
+[[requirement]]
+id = "copy-cd-bit"
+section = "3.2.2"
+keyword = "MUST"
+summary = "copies the CD bit"
+
+[[requirement]]
+id = "keep-requested-dnssec-rrs"
+section = "3.2.1"
+keyword = "MUST NOT"
+summary = "does not strip requested DNSSEC RRs"
+"#;
+
+    const SOURCE: &str = r#"
+use dns_test::Result;
+
+// RFC 4035 section 3.2.2 MUST [copy-cd-bit]: "The name server side of a security-aware
+// recursive name server MUST copy the setting of the CD bit"
+// RFC 6891 section 6.1.1 MUST [multiple-opt-formerr]
+#[test]
+#[ignore]
+fn copies_cd_bit() -> Result<()> {
+    Ok(())
+}
+
+// RFC 6891 section 6.1.1: "a FORMERR MUST be returned"
+#[test]
+fn untagged() {}
+
+fn helper() {}
+"#;
+
+    #[test]
+    fn parses_tags() -> Result<()> {
+        let tag: Tag =
+            "RFC 4035 section 3.2.1 MUST NOT [keep-requested-dnssec-rrs]: \"quote\"".parse()?;
+        assert_eq!(
+            Tag {
+                rfc: 4035,
+                section: "3.2.1".to_string(),
+                keyword: Keyword::MustNot,
+                id: "keep-requested-dnssec-rrs".to_string(),
+            },
+            tag
+        );
+        assert_eq!(
+            "RFC 4035 section 3.2.1 MUST NOT [keep-requested-dnssec-rrs]",
+            tag.to_string()
+        );
+
+        for malformed in [
+            "RFC 4035 3.2.1 MUST [id]",
+            "RFC 4035 section 3.2.1 MUSTN'T [id]",
+            "RFC 4035 section 3.2.1 MUST []",
+            "RFC 4035 section 3.2.1 MUST [id] trailing",
+            "RFC four section 3.2.1 MUST [id]",
+        ] {
+            assert!(malformed.parse::<Tag>().is_err(), "{malformed}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn scans_tests_and_their_tags() -> Result<()> {
+        let tests = scan_source("resolver::dnssec", SOURCE)?;
+
+        assert_eq!(2, tests.len());
+        assert_eq!("resolver::dnssec::copies_cd_bit", tests[0].name);
+        assert!(tests[0].ignored);
+        assert_eq!(2, tests[0].tags.len());
+        assert_eq!("copy-cd-bit", tests[0].tags[0].id);
+        assert_eq!(6891, tests[0].tags[1].rfc);
+
+        assert_eq!("resolver::dnssec::untagged", tests[1].name);
+        assert!(!tests[1].ignored);
+        assert!(tests[1].tags.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn tags_must_be_above_a_test() {
+        let source = "// RFC 4035 section 3.2.2 MUST [copy-cd-bit]\nfn helper() {}\n";
+        assert!(scan_source("", source).is_err());
+    }
+
+    #[test]
+    fn module_paths() {
+        assert_eq!("", module_path(Path::new("lib.rs")));
+        assert_eq!("resolver", module_path(Path::new("resolver.rs")));
+        assert_eq!(
+            "resolver::dnssec::fixtures",
+            module_path(Path::new("resolver/dnssec/fixtures.rs"))
+        );
+        assert_eq!(
+            "resolver::dns",
+            module_path(Path::new("resolver/dns/mod.rs"))
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_requirements() {
+        let duplicate = format!(
+            "{CATALOG}\n[[requirement]]\nid = \"copy-cd-bit\"\nsection = \"3.2.2\"\n\
+             keyword = \"MUST\"\nsummary = \"again\"\n"
+        );
+        assert!(duplicate.parse::<Catalog>().is_err());
+    }
+
+    #[test]
+    fn validates_tags_against_the_catalog() -> Result<()> {
+        let tests = || scan_source("resolver", SOURCE);
+
+        assert!(Report::new(vec![CATALOG.parse()?], tests()?).is_ok());
+
+        let wrong_keyword = SOURCE.replace("MUST [copy-cd-bit]", "SHOULD [copy-cd-bit]");
+        let tests = scan_source("resolver", &wrong_keyword)?;
+        assert!(Report::new(vec![CATALOG.parse()?], tests).is_err());
+
+        let unknown_id = SOURCE.replace("[copy-cd-bit]", "[copy-ad-bit]");
+        let tests = scan_source("resolver", &unknown_id)?;
+        assert!(Report::new(vec![CATALOG.parse()?], tests).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn markdown_report() -> Result<()> {
+        let report = Report::new(vec![CATALOG.parse()?], scan_source("resolver", SOURCE)?)?;
+        let markdown = report.to_markdown();
+
+        assert!(markdown.contains("covered: MUST 1/1, MUST NOT 0/1"));
+        assert!(markdown.contains(
+            "| 3.2.2 | MUST | `copy-cd-bit`: copies the CD bit | `resolver::copies_cd_bit` (ignored) |"
+        ));
+        assert!(markdown.contains(
+            "| `keep-requested-dnssec-rrs`: does not strip requested DNSSEC RRs | **none** |"
+        ));
+        assert!(markdown.contains(
+            "- RFC 6891 section 6.1.1 MUST [multiple-opt-formerr]: `resolver::copies_cd_bit` (ignored)"
+        ));
+        assert!(markdown.contains("## Untagged tests\n\n- `resolver::untagged`\n"));
+
+        assert_eq!(1, report.uncovered().len());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,495 @@
+//! Differential testing of resolvers
+//!
+//! A `Differential` starts one resolver per implementation, all with the same
+//! `ResolverSettings` and therefore resolving through the same `Graph`, and sends each of them
+//! the same queries. The responses are normalised -- TTLs are zeroed and records are sorted --
+//! and compared against those of the first implementation, the reference, so divergences are
+//! reported without hand-written expectations
+
+use core::fmt;
+
+use crate::client::{Client, DigFlags, DigOutput, DigSettings, DigStatus, ExtendedDnsError};
+use crate::message::Section;
+use crate::record::RecordType;
+use crate::resolver::ResolverSettings;
+use crate::{Implementation, Resolver, Result, FQDN};
+
+/// A query sent to every resolver
+#[derive(Clone)]
+pub struct Query {
+    pub settings: DigSettings,
+    pub record_type: RecordType,
+    pub fqdn: FQDN,
+}
+
+impl Query {
+    /// A recursive query for `record_type` records of `fqdn` with the DO bit set
+    pub fn new(record_type: RecordType, fqdn: FQDN) -> Self {
+        Self {
+            settings: *DigSettings::default().recurse().dnssec(),
+            record_type,
+            fqdn,
+        }
+    }
+
+    pub fn settings(mut self, settings: DigSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.fqdn, self.record_type)
+    }
+}
+
+/// Resolvers of different implementations that share their settings
+pub struct Differential {
+    client: Client,
+    implementations: Vec<Implementation>,
+    resolvers: Vec<Resolver>,
+}
+
+impl Differential {
+    /// Starts one resolver per implementation; the first implementation is the reference the
+    /// others are compared against
+    pub fn start(settings: &ResolverSettings, implementations: &[Implementation]) -> Result<Self> {
+        if implementations.len() < 2 {
+            return Err("differential testing needs at least two implementations".into());
+        }
+
+        let resolvers = implementations
+            .iter()
+            .map(|implementation| settings.start(implementation))
+            .collect::<Result<Vec<_>>>()?;
+        let client = Client::new(resolvers[0].network())?;
+
+        Ok(Self {
+            client,
+            implementations: implementations.to_vec(),
+            resolvers,
+        })
+    }
+
+    /// The resolvers, in the order their implementations were given
+    pub fn resolvers(&self) -> &[Resolver] {
+        &self.resolvers
+    }
+
+    /// Sends `query` to every resolver
+    pub fn query(&self, query: &Query) -> Comparison {
+        let outputs = self
+            .resolvers
+            .iter()
+            .map(|resolver| {
+                self.client
+                    .dig(
+                        query.settings,
+                        resolver.ipv4_addr(),
+                        query.record_type,
+                        &query.fqdn,
+                    )
+                    .map(Normalized::from)
+                    .map_err(|e| e.to_string())
+            })
+            .collect();
+
+        Comparison {
+            query: query.clone(),
+            outputs,
+        }
+    }
+
+    /// Sends every query, in order, to every resolver
+    pub fn run(&self, queries: &[Query]) -> Report {
+        Report {
+            implementations: self.implementations.clone(),
+            comparisons: queries.iter().map(|query| self.query(query)).collect(),
+        }
+    }
+}
+
+/// A `DigOutput` stripped of what legitimately varies between resolvers and runs
+///
+/// TTLs are set to zero and the records of each section are rendered, with whitespace
+/// collapsed, and sorted
+#[derive(Clone, Debug, PartialEq)]
+pub struct Normalized {
+    pub status: DigStatus,
+    pub flags: DigFlags,
+    pub ede: Option<ExtendedDnsError>,
+    pub answer: Vec<String>,
+    pub authority: Vec<String>,
+}
+
+impl From<DigOutput> for Normalized {
+    fn from(output: DigOutput) -> Self {
+        let DigOutput {
+            ede,
+            flags,
+            status,
+            answer,
+            authority,
+        } = output;
+
+        let normalize = |records: Vec<crate::record::Record>| {
+            let mut records = records
+                .into_iter()
+                .map(|mut record| {
+                    *record.ttl_mut() = 0;
+                    record
+                        .to_string()
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect::<Vec<_>>();
+            records.sort();
+            records
+        };
+
+        Self {
+            status,
+            flags,
+            ede,
+            answer: normalize(answer),
+            authority: normalize(authority),
+        }
+    }
+}
+
+/// A single way in which a response differs from the reference's
+#[derive(Clone, Debug, PartialEq)]
+pub enum Difference {
+    /// Exactly one of the two `dig` invocations failed, e.g. because it timed out
+    Failure {
+        reference: Option<String>,
+        actual: Option<String>,
+    },
+    Status {
+        reference: DigStatus,
+        actual: DigStatus,
+    },
+    Flag {
+        flag: &'static str,
+        reference: bool,
+        actual: bool,
+    },
+    Ede {
+        reference: Option<ExtendedDnsError>,
+        actual: Option<ExtendedDnsError>,
+    },
+    /// The records of `section` differ; `missing` are only in the reference's response and
+    /// `unexpected` only in the other one
+    Records {
+        section: Section,
+        missing: Vec<String>,
+        unexpected: Vec<String>,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Failure { reference, actual } => write!(
+                f,
+                "dig failed: {} (reference) vs {}",
+                reference.as_deref().unwrap_or("no"),
+                actual.as_deref().unwrap_or("no"),
+            ),
+            Self::Status { reference, actual } => {
+                write!(f, "status: {reference:?} (reference) vs {actual:?}")
+            }
+            Self::Flag {
+                flag,
+                reference,
+                actual,
+            } => write!(f, "{flag} flag: {reference} (reference) vs {actual}"),
+            Self::Ede { reference, actual } => {
+                write!(f, "EDE: {reference:?} (reference) vs {actual:?}")
+            }
+            Self::Records {
+                section,
+                missing,
+                unexpected,
+            } => {
+                write!(f, "{section:?} section:")?;
+                for record in missing {
+                    write!(f, "\n  - {record}")?;
+                }
+                for record in unexpected {
+                    write!(f, "\n  + {record}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The responses of every resolver to a query
+pub struct Comparison {
+    pub query: Query,
+    /// One output per implementation, in order; the error of a failed `dig` is kept as text
+    pub outputs: Vec<core::result::Result<Normalized, String>>,
+}
+
+impl Comparison {
+    /// The differences between the response of the implementation at `index` and the
+    /// reference's
+    pub fn diff(&self, index: usize) -> Vec<Difference> {
+        diff(&self.outputs[0], &self.outputs[index])
+    }
+
+    /// Whether every response matches the reference's
+    pub fn is_consistent(&self) -> bool {
+        (1..self.outputs.len()).all(|index| self.diff(index).is_empty())
+    }
+}
+
+/// The comparisons of a query set
+pub struct Report {
+    pub implementations: Vec<Implementation>,
+    pub comparisons: Vec<Comparison>,
+}
+
+impl Report {
+    /// Whether every implementation responded like the reference to every query
+    pub fn is_consistent(&self) -> bool {
+        self.comparisons.iter().all(Comparison::is_consistent)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reference = &self.implementations[0];
+        for comparison in &self.comparisons {
+            for (index, implementation) in self.implementations.iter().enumerate().skip(1) {
+                let differences = comparison.diff(index);
+                if differences.is_empty() {
+                    continue;
+                }
+
+                writeln!(
+                    f,
+                    "{}: {implementation} diverges from {reference}",
+                    comparison.query
+                )?;
+                for difference in differences {
+                    writeln!(f, "- {difference}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn diff(
+    reference: &core::result::Result<Normalized, String>,
+    actual: &core::result::Result<Normalized, String>,
+) -> Vec<Difference> {
+    let (reference, actual) = match (reference, actual) {
+        (Ok(reference), Ok(actual)) => (reference, actual),
+        (Err(_), Err(_)) => return vec![],
+        (reference, actual) => {
+            return vec![Difference::Failure {
+                reference: reference.as_ref().err().cloned(),
+                actual: actual.as_ref().err().cloned(),
+            }]
+        }
+    };
+
+    let mut differences = vec![];
+    if reference.status != actual.status {
+        differences.push(Difference::Status {
+            reference: reference.status,
+            actual: actual.status,
+        });
+    }
+
+    for (flag, reference, actual) in flags(&reference.flags)
+        .into_iter()
+        .zip(flags(&actual.flags))
+        .map(|((flag, reference), (_, actual))| (flag, reference, actual))
+    {
+        if reference != actual {
+            differences.push(Difference::Flag {
+                flag,
+                reference,
+                actual,
+            });
+        }
+    }
+
+    if reference.ede != actual.ede {
+        differences.push(Difference::Ede {
+            reference: reference.ede,
+            actual: actual.ede,
+        });
+    }
+
+    for (section, reference, actual) in [
+        (Section::Answer, &reference.answer, &actual.answer),
+        (Section::Authority, &reference.authority, &actual.authority),
+    ] {
+        let missing = difference(reference, actual);
+        let unexpected = difference(actual, reference);
+        if !missing.is_empty() || !unexpected.is_empty() {
+            differences.push(Difference::Records {
+                section,
+                missing,
+                unexpected,
+            });
+        }
+    }
+
+    differences
+}
+
+fn flags(flags: &DigFlags) -> [(&'static str, bool); 6] {
+    [
+        ("qr", flags.qr),
+        ("aa", flags.authoritative_answer),
+        ("rd", flags.recursion_desired),
+        ("ra", flags.recursion_available),
+        ("ad", flags.authenticated_data),
+        ("cd", flags.checking_disabled),
+    ]
+}
+
+/// The records of `left` that are not in `right`, accounting for duplicates
+fn difference(left: &[String], right: &[String]) -> Vec<String> {
+    let mut right = right.to_vec();
+    left.iter()
+        .filter(
+            |record| match right.iter().position(|other| other == *record) {
+                Some(index) => {
+                    right.swap_remove(index);
+                    false
+                }
+                None => true,
+            },
+        )
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::name_server::{Graph, NameServer, Sign};
+    use crate::record::Record;
+    use crate::Network;
+
+    use super::*;
+
+    fn normalized(input: &str) -> Result<Normalized> {
+        Ok(input.parse::<DigOutput>()?.into())
+    }
+
+    const REFERENCE: &str = "
+;; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 1
+;; flags: qr rd ra ad; QUERY: 1, ANSWER: 2, AUTHORITY: 0, ADDITIONAL: 1
+
+;; ANSWER SECTION:
+example.nameservers.com. 3600	IN	A	1.2.3.4
+example.nameservers.com. 3600	IN	A	5.6.7.8
+";
+
+    #[test]
+    fn ignores_ttl_and_record_order() -> Result<()> {
+        let other = "
+;; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 2
+;; flags: qr rd ra ad; QUERY: 1, ANSWER: 2, AUTHORITY: 0, ADDITIONAL: 1
+
+;; ANSWER SECTION:
+example.nameservers.com. 3599	IN	A	5.6.7.8
+example.nameservers.com. 42	IN	A	1.2.3.4
+";
+
+        assert_eq!(normalized(REFERENCE)?, normalized(other)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_status_flags_and_records() -> Result<()> {
+        let other = "
+;; ->>HEADER<<- opcode: QUERY, status: SERVFAIL, id: 2
+;; flags: qr rd ra; QUERY: 1, ANSWER: 1, AUTHORITY: 0, ADDITIONAL: 1
+
+;; OPT PSEUDOSECTION:
+; EDE: 6 (DNSSEC Bogus)
+;; ANSWER SECTION:
+example.nameservers.com. 3600	IN	A	9.9.9.9
+";
+
+        let differences = diff(&Ok(normalized(REFERENCE)?), &Ok(normalized(other)?));
+        assert_eq!(
+            vec![
+                Difference::Status {
+                    reference: DigStatus::NOERROR,
+                    actual: DigStatus::SERVFAIL,
+                },
+                Difference::Flag {
+                    flag: "ad",
+                    reference: true,
+                    actual: false,
+                },
+                Difference::Ede {
+                    reference: None,
+                    actual: Some(ExtendedDnsError::DnssecBogus),
+                },
+                Difference::Records {
+                    section: Section::Answer,
+                    missing: vec![
+                        "example.nameservers.com. 0 IN A 1.2.3.4".to_string(),
+                        "example.nameservers.com. 0 IN A 5.6.7.8".to_string(),
+                    ],
+                    unexpected: vec!["example.nameservers.com. 0 IN A 9.9.9.9".to_string()],
+                },
+            ],
+            differences
+        );
+
+        let failure = diff(&Ok(normalized(REFERENCE)?), &Err("timed out".to_string()));
+        assert_eq!(
+            vec![Difference::Failure {
+                reference: None,
+                actual: Some("timed out".to_string()),
+            }],
+            failure
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn implementations_agree_on_secure_answer() -> Result<()> {
+        let network = Network::new()?;
+        let needle_fqdn = FQDN("example.nameservers.com.")?;
+
+        let mut leaf_ns = NameServer::new(&crate::PEER, FQDN::NAMESERVERS, &network)?;
+        leaf_ns.add(Record::a(needle_fqdn.clone(), [1, 2, 3, 4].into()));
+
+        let Graph {
+            nameservers: _nameservers,
+            root,
+            trust_anchor,
+        } = Graph::build(leaf_ns, Sign::Yes)?;
+
+        let mut settings = Resolver::new(&network, root);
+        settings.trust_anchor(&trust_anchor.unwrap());
+
+        let differential =
+            Differential::start(&settings, &[Implementation::Unbound, Implementation::Bind])?;
+        let report = differential.run(&[
+            Query::new(RecordType::A, needle_fqdn),
+            Query::new(RecordType::SOA, FQDN::NAMESERVERS),
+        ]);
+
+        assert!(report.is_consistent(), "{report}");
+
+        Ok(())
+    }
+}
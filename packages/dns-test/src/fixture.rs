@@ -0,0 +1,255 @@
+//! Fixtures that are shared by the tests of a test binary
+//!
+//! Building and signing a `Graph` takes several containers and `ldns` invocations; tests that
+//! only query a graph can share one instead. A `SharedGraph` is meant to be a `static`: it builds
+//! its graph, on its own network, the first time a test asks for it and hands out resolvers that
+//! use it as their root and trust anchor
+//!
+//! ``` text
+//! static SECURE: SharedGraph = SharedGraph::new(|network| {
+//!     let leaf_ns = NameServer::new(&dns_test::PEER, FQDN::NAMESERVERS, network)?;
+//!     Graph::build(leaf_ns, Sign::Yes)
+//! });
+//!
+//! #[test]
+//! fn can_validate() -> Result<()> {
+//!     let resolver = SECURE.resolver(&dns_test::SUBJECT)?;
+//!     let client = Client::new(resolver.network())?;
+//!     // ..
+//! }
+//! ```
+//!
+//! Resolvers are pooled: when a `PooledResolver` is dropped its container goes back to the pool
+//! and the next test that asks for a resolver of the same implementation gets it restarted, with
+//! an empty cache and without the faults injected by the previous test
+//!
+//! Tests run in parallel so the name servers of a shared graph see the traffic of several tests
+//! at once; tests that eavesdrop on, impair or restart name servers should build their own graph.
+//! `static`s are never dropped so the containers and the network of a shared graph, and the
+//! pooled resolvers, outlive the test binary; the automatic clean-up of the next `dns-test`
+//! process removes them
+
+use core::ops::Deref;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+
+use crate::name_server::Graph;
+use crate::resolver::ResolverSettings;
+use crate::{Faults, Implementation, Network, PersistentState, Resolver, Result};
+
+/// A `Graph` that is built at most once per process and is only ever accessed through shared
+/// references
+pub struct SharedGraph {
+    build: fn(&Network) -> Result<Graph>,
+    state: OnceLock<core::result::Result<State, String>>,
+}
+
+struct State {
+    graph: Graph,
+    network: Network,
+    settings: ResolverSettings,
+    /// Resolvers that have been returned to the pool; they must be reset before they are reused
+    idle: Mutex<Vec<(Implementation, Resolver)>>,
+}
+
+impl SharedGraph {
+    /// `build` creates the graph on the network it's given; it must sign the graph so that the
+    /// graph has a trust anchor
+    pub const fn new(build: fn(&Network) -> Result<Graph>) -> Self {
+        Self {
+            build,
+            state: OnceLock::new(),
+        }
+    }
+
+    /// Returns the graph, building it if this is the first call
+    ///
+    /// If building the graph fails, this and every later call returns the same error
+    pub fn graph(&self) -> Result<&Graph> {
+        Ok(&self.state()?.graph)
+    }
+
+    /// Returns the network of the graph
+    pub fn network(&self) -> Result<&Network> {
+        Ok(&self.state()?.network)
+    }
+
+    /// Returns the settings of the resolvers handed out by `resolver`, i.e. the root and the
+    /// trust anchor of the graph, e.g. to start a resolver with additional settings
+    ///
+    /// Resolvers started from these settings are not pooled
+    pub fn resolver_settings(&self) -> Result<ResolverSettings> {
+        let State { graph, network, .. } = self.state()?;
+        Ok(settings(graph, network))
+    }
+
+    /// Takes a resolver of `implementation` out of the pool, or starts one if there's none,
+    /// that uses the graph
+    pub fn resolver(&self, implementation: &Implementation) -> Result<PooledResolver<'_>> {
+        let state = self.state()?;
+
+        let pooled = {
+            let mut idle = state.idle.lock().unwrap();
+            idle.iter()
+                .position(|(pooled, _)| pooled == implementation)
+                .map(|index| idle.swap_remove(index).1)
+        };
+
+        let resolver = match pooled {
+            Some(mut resolver) => {
+                resolver.clear_faults()?;
+                state
+                    .settings
+                    .restart(&mut resolver, PersistentState::Drop)?;
+                resolver
+            }
+            None => state.settings.start(implementation)?,
+        };
+
+        Ok(PooledResolver {
+            implementation: implementation.clone(),
+            resolver: Some(resolver),
+            idle: &state.idle,
+        })
+    }
+
+    fn state(&self) -> Result<&State> {
+        let state = self.state.get_or_init(|| {
+            let network = Network::new().map_err(|e| e.to_string())?;
+            let graph = (self.build)(&network).map_err(|e| e.to_string())?;
+            if graph.trust_anchor.is_none() {
+                return Err("the graph of a `SharedGraph` must be signed".to_string());
+            }
+
+            Ok(State {
+                settings: settings(&graph, &network),
+                graph,
+                network,
+                idle: Mutex::new(vec![]),
+            })
+        });
+
+        state
+            .as_ref()
+            .map_err(|e| format!("could not build the shared graph: {e}").into())
+    }
+}
+
+fn settings(graph: &Graph, network: &Network) -> ResolverSettings {
+    let mut settings = Resolver::new(network, graph.root.clone());
+    if let Some(trust_anchor) = &graph.trust_anchor {
+        settings.trust_anchor(trust_anchor);
+    }
+    settings
+}
+
+/// A resolver that goes back to the pool of its `SharedGraph` when dropped
+///
+/// If the test panicked, the resolver is discarded instead
+pub struct PooledResolver<'a> {
+    implementation: Implementation,
+    resolver: Option<Resolver>,
+    idle: &'a Mutex<Vec<(Implementation, Resolver)>>,
+}
+
+impl Deref for PooledResolver<'_> {
+    type Target = Resolver;
+
+    fn deref(&self) -> &Resolver {
+        self.resolver.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledResolver<'_> {
+    fn drop(&mut self) {
+        let Some(resolver) = self.resolver.take() else {
+            return;
+        };
+
+        // a resolver that has exited panics when dropped, like any other resolver
+        if thread::panicking() || !resolver.is_alive() {
+            return;
+        }
+
+        if let Ok(mut idle) = self.idle.lock() {
+            idle.push((self.implementation.clone(), resolver));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use crate::client::{Client, DigSettings};
+    use crate::name_server::{NameServer, Sign};
+    use crate::record::{Record, RecordType};
+    use crate::FQDN;
+
+    use super::*;
+
+    const NEEDLE: Ipv4Addr = Ipv4Addr::new(1, 2, 3, 4);
+
+    fn secure_graph(network: &Network) -> Result<Graph> {
+        let mut leaf_ns = NameServer::new(&Implementation::Unbound, FQDN::NAMESERVERS, network)?;
+        leaf_ns.add(Record::a(FQDN("example.nameservers.com.")?, NEEDLE));
+        Graph::build(leaf_ns, Sign::Yes)
+    }
+
+    #[test]
+    fn pooled_resolvers_are_reused_with_an_empty_cache() -> Result<()> {
+        // not shared with other tests so that no other test takes the pooled resolver
+        static SECURE: SharedGraph = SharedGraph::new(secure_graph);
+
+        let needle = FQDN("example.nameservers.com.")?;
+        let is_needle = |record: &Record| matches!(record, Record::A(a) if a.fqdn == needle);
+        let settings = *DigSettings::default().recurse().authentic_data();
+
+        let first = SECURE.resolver(&Implementation::Unbound)?;
+        let client = Client::new(first.network())?;
+        let output = client.dig(settings, first.ipv4_addr(), RecordType::A, &needle)?;
+        assert!(output.status.is_noerror());
+        assert!(output.flags.authenticated_data);
+        assert!(first.dump_cache()?.iter().any(is_needle));
+
+        let container_id = first.container_id().to_string();
+        drop(first);
+
+        let second = SECURE.resolver(&Implementation::Unbound)?;
+        assert_eq!(container_id, second.container_id());
+        assert!(!second.dump_cache()?.iter().any(is_needle));
+
+        let output = client.dig(settings, second.ipv4_addr(), RecordType::A, &needle)?;
+        assert!(output.flags.authenticated_data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_tests_get_distinct_resolvers() -> Result<()> {
+        static SECURE: SharedGraph = SharedGraph::new(secure_graph);
+
+        let first = SECURE.resolver(&Implementation::Unbound)?;
+        let second = SECURE.resolver(&Implementation::Unbound)?;
+
+        assert_ne!(first.container_id(), second.container_id());
+        assert_eq!(
+            SECURE.network()?.name(),
+            first.network().name(),
+            "sanity check"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn build_errors_are_reported_by_every_call() {
+        static UNSIGNED: SharedGraph = SharedGraph::new(|network| {
+            let leaf_ns = NameServer::new(&Implementation::Unbound, FQDN::NAMESERVERS, network)?;
+            Graph::build(leaf_ns, Sign::No)
+        });
+
+        assert!(UNSIGNED.graph().is_err());
+        assert!(UNSIGNED.resolver(&Implementation::Unbound).is_err());
+    }
+}
@@ -0,0 +1,717 @@
+//! Response-mutation fuzzing of resolvers
+//!
+//! A `Scenario` is a list of `Mutation`s applied to a minimal signed graph whose leaf zone,
+//! `nameservers.com.`, contains the A record of `example.nameservers.com.`. Zone mutations are
+//! applied to the signed zone file, through `Sign::AndAmend`; wire mutations are applied to the
+//! responses of the leaf zone's name server by a `RogueServer::proxy` the resolver is redirected
+//! to. The resolver is then asked for the A record and its response is checked against these
+//! invariants
+//!
+//! - the resolver does not crash
+//! - the AD flag is never set on bogus data
+//! - data that is still valid is not answered with SERVFAIL
+//!
+//! A `Campaign` generates random scenarios from a seed, minimises the ones that violate an
+//! invariant and saves them, in the format of `Scenario`'s `Display` implementation, so they can
+//! be replayed with `Scenario::load`
+
+use core::fmt;
+use core::str::FromStr;
+use std::fs;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+
+use base64::prelude::*;
+
+use crate::client::{Client, DigSettings};
+use crate::message::Section;
+use crate::name_server::{Graph, NameServer, Sign};
+use crate::record::{Record, RecordType};
+use crate::rogue_server::{Action, RogueServer, Rule};
+use crate::{Error, Implementation, Network, Resolver, Result, FQDN};
+
+const NEEDLE_IPV4_ADDR: Ipv4Addr = Ipv4Addr::new(1, 2, 3, 4);
+
+/// The record types that zone mutations target
+const ZONE_TYPES: [RecordType; 5] = [
+    RecordType::A,
+    RecordType::DNSKEY,
+    RecordType::NS,
+    RecordType::NSEC3,
+    RecordType::SOA,
+];
+const TTLS: [u32; 6] = [0, 1, 60, 3600, 7 * 24 * 60 * 60, i32::MAX as u32];
+const SECTIONS: [Section; 3] = [Section::Answer, Section::Authority, Section::Additional];
+
+/// A change made to the signed leaf zone or to the responses of its name server
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mutation {
+    /// Removes the RRSIGs that cover the records of this type
+    ZoneDropRrsigs(RecordType),
+    /// Flips a bit, modulo the signature length, of the RRSIGs that cover the records of this
+    /// type
+    ZoneCorruptSignature { covered: RecordType, bit: usize },
+    /// Changes the TTL of the records of this type, but not the original TTL of their RRSIGs
+    ZoneTtl { record_type: RecordType, ttl: u32 },
+    /// Prepends a label to the owner of the records of this type, except for the ones at the
+    /// zone apex; their RRSIGs are not changed
+    ZoneRelabel(RecordType),
+    /// Flips a bit of each response, see `Action::FlipBit`
+    WireFlipBit(usize),
+    /// Removes the RRSIGs from each response
+    WireDropRrsigs,
+    /// Reverses the order of the records in each section
+    WireReverse,
+    /// Moves the records of a section to another one
+    WireMove(Section, Section),
+    /// Sets the TTL of all the records in each response
+    WireTtl(u32),
+    /// Writes the owner names in uppercase
+    WireUppercase,
+}
+
+impl Mutation {
+    /// Returns a random mutation
+    pub fn random(rng: &mut Rng) -> Self {
+        match rng.below(10) {
+            0 => Self::ZoneDropRrsigs(*rng.choose(&ZONE_TYPES)),
+            1 => Self::ZoneCorruptSignature {
+                covered: *rng.choose(&ZONE_TYPES),
+                bit: rng.below(2048),
+            },
+            2 => Self::ZoneTtl {
+                record_type: *rng.choose(&ZONE_TYPES),
+                ttl: *rng.choose(&TTLS),
+            },
+            3 => Self::ZoneRelabel(*rng.choose(&[RecordType::A, RecordType::NSEC3])),
+            4 => Self::WireFlipBit(rng.below(8 * 512)),
+            5 => Self::WireDropRrsigs,
+            6 => Self::WireReverse,
+            7 => {
+                let from = rng.below(SECTIONS.len());
+                let to = (from + 1 + rng.below(SECTIONS.len() - 1)) % SECTIONS.len();
+                Self::WireMove(SECTIONS[from], SECTIONS[to])
+            }
+            8 => Self::WireTtl(*rng.choose(&TTLS)),
+            _ => Self::WireUppercase,
+        }
+    }
+
+    /// What the mutation does to the needle record, as far as DNSSEC validation goes
+    fn expectation(&self) -> Expectation {
+        let breaks = |record_type: &RecordType| {
+            if matches!(record_type, RecordType::A | RecordType::DNSKEY) {
+                Expectation::Bogus
+            } else {
+                Expectation::Unknown
+            }
+        };
+
+        match self {
+            Self::ZoneDropRrsigs(covered) => breaks(covered),
+            Self::ZoneCorruptSignature { covered, .. } => breaks(covered),
+            Self::WireDropRrsigs => Expectation::Bogus,
+            // validators cap the TTL to the original TTL of the RRSIG and canonicalise owner
+            // names before checking signatures
+            Self::ZoneTtl { .. } | Self::WireReverse | Self::WireTtl(_) | Self::WireUppercase => {
+                Expectation::Valid
+            }
+            Self::ZoneRelabel(_) | Self::WireFlipBit(_) | Self::WireMove(..) => {
+                Expectation::Unknown
+            }
+        }
+    }
+
+    /// Applies a zone mutation to the `records` of `zone`; wire mutations are ignored
+    pub(crate) fn amend(&self, zone: &FQDN, records: &mut Vec<Record>) {
+        match self {
+            Self::ZoneDropRrsigs(covered) => records.retain(
+                |record| !matches!(record, Record::RRSIG(rrsig) if rrsig.type_covered == *covered),
+            ),
+
+            Self::ZoneCorruptSignature { covered, bit } => {
+                for record in records {
+                    if let Record::RRSIG(rrsig) = record {
+                        if rrsig.type_covered == *covered {
+                            let mut signature = BASE64_STANDARD
+                                .decode(&rrsig.signature)
+                                .expect("invalid base64 signature");
+                            let bit = bit % (8 * signature.len());
+                            signature[bit / 8] ^= 0x80 >> (bit % 8);
+                            rrsig.signature = BASE64_STANDARD.encode(&signature);
+                        }
+                    }
+                }
+            }
+
+            Self::ZoneTtl { record_type, ttl } => {
+                for record in records {
+                    if record.record_type() == *record_type {
+                        *record.ttl_mut() = *ttl;
+                    }
+                }
+            }
+
+            Self::ZoneRelabel(record_type) => {
+                for record in records {
+                    if record.record_type() == *record_type && record.owner() != zone {
+                        let owner = record.owner_mut();
+                        *owner = FQDN(format!("x.{}", owner.as_str()))
+                            .expect("unreachable: the owner is a FQDN");
+                    }
+                }
+            }
+
+            Self::WireFlipBit(_)
+            | Self::WireDropRrsigs
+            | Self::WireReverse
+            | Self::WireMove(..)
+            | Self::WireTtl(_)
+            | Self::WireUppercase => {}
+        }
+    }
+
+    /// Returns the rogue server action that implements a wire mutation
+    pub(crate) fn action(&self) -> Option<Action> {
+        let action = match self {
+            Self::WireFlipBit(bit) => Action::FlipBit(*bit),
+            Self::WireDropRrsigs => Action::DropRrsigs,
+            Self::WireReverse => Action::Reverse,
+            Self::WireMove(from, to) => Action::Move(*from, *to),
+            Self::WireTtl(ttl) => Action::Ttl(*ttl),
+            Self::WireUppercase => Action::Uppercase,
+            Self::ZoneDropRrsigs(_)
+            | Self::ZoneCorruptSignature { .. }
+            | Self::ZoneTtl { .. }
+            | Self::ZoneRelabel(_) => return None,
+        };
+
+        Some(action)
+    }
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZoneDropRrsigs(covered) => write!(f, "zone drop-rrsigs {covered}"),
+            Self::ZoneCorruptSignature { covered, bit } => {
+                write!(f, "zone corrupt-signature {covered} {bit}")
+            }
+            Self::ZoneTtl { record_type, ttl } => write!(f, "zone ttl {record_type} {ttl}"),
+            Self::ZoneRelabel(record_type) => write!(f, "zone relabel {record_type}"),
+            Self::WireFlipBit(bit) => write!(f, "wire flip-bit {bit}"),
+            Self::WireDropRrsigs => f.write_str("wire drop-rrsigs"),
+            Self::WireReverse => f.write_str("wire reverse"),
+            Self::WireMove(from, to) => {
+                write!(f, "wire move {} {}", section_name(from), section_name(to))
+            }
+            Self::WireTtl(ttl) => write!(f, "wire ttl {ttl}"),
+            Self::WireUppercase => f.write_str("wire uppercase"),
+        }
+    }
+}
+
+impl FromStr for Mutation {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let columns = input.split_whitespace().collect::<Vec<_>>();
+        let number = |column: &str| -> Result<usize> {
+            column
+                .parse()
+                .map_err(|_| format!("invalid number `{column}` in mutation `{input}`").into())
+        };
+        let ttl = |column: &str| -> Result<u32> {
+            column
+                .parse()
+                .map_err(|_| format!("invalid TTL `{column}` in mutation `{input}`").into())
+        };
+
+        let mutation = match columns[..] {
+            ["zone", "drop-rrsigs", covered] => Self::ZoneDropRrsigs(covered.parse()?),
+            ["zone", "corrupt-signature", covered, bit] => Self::ZoneCorruptSignature {
+                covered: covered.parse()?,
+                bit: number(bit)?,
+            },
+            ["zone", "ttl", record_type, value] => Self::ZoneTtl {
+                record_type: record_type.parse()?,
+                ttl: ttl(value)?,
+            },
+            ["zone", "relabel", record_type] => Self::ZoneRelabel(record_type.parse()?),
+            ["wire", "flip-bit", bit] => Self::WireFlipBit(number(bit)?),
+            ["wire", "drop-rrsigs"] => Self::WireDropRrsigs,
+            ["wire", "reverse"] => Self::WireReverse,
+            ["wire", "move", from, to] => Self::WireMove(parse_section(from)?, parse_section(to)?),
+            ["wire", "ttl", value] => Self::WireTtl(ttl(value)?),
+            ["wire", "uppercase"] => Self::WireUppercase,
+            _ => return Err(format!("unknown mutation: {input}").into()),
+        };
+
+        Ok(mutation)
+    }
+}
+
+fn section_name(section: &Section) -> &'static str {
+    match section {
+        Section::Question => "question",
+        Section::Answer => "answer",
+        Section::Authority => "authority",
+        Section::Additional => "additional",
+    }
+}
+
+fn parse_section(input: &str) -> Result<Section> {
+    SECTIONS
+        .into_iter()
+        .find(|section| section_name(section) == input)
+        .ok_or_else(|| format!("unknown section: {input}").into())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Expectation {
+    /// the needle record still validates
+    Valid,
+    /// the needle record cannot be validated
+    Bogus,
+    /// the needle record may or may not validate
+    Unknown,
+}
+
+/// An invariant the resolver did not uphold
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Violation {
+    /// The resolver process exited
+    Crash,
+    /// The response has the AD flag set but the data is bogus, or is not the needle record
+    AdOnBogusData,
+    /// The data is still valid but the response is SERVFAIL, or there is no response
+    ServfailOnValidData,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Crash => "the resolver crashed",
+            Self::AdOnBogusData => "the AD flag was set on bogus data",
+            Self::ServfailOnValidData => "valid data was answered with SERVFAIL",
+        })
+    }
+}
+
+/// A list of mutations, and the test that applies them
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scenario {
+    pub mutations: Vec<Mutation>,
+}
+
+impl Scenario {
+    /// Returns a scenario with between 1 and `max_mutations` random mutations
+    pub fn random(rng: &mut Rng, max_mutations: usize) -> Self {
+        let len = 1 + rng.below(max_mutations.max(1));
+        Self {
+            mutations: (0..len).map(|_| Mutation::random(rng)).collect(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| format!("{}: {e}", path.display()).into())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        Ok(fs::write(path, self.to_string())?)
+    }
+
+    /// Runs the scenario against a fresh `subject` resolver and returns the invariant it
+    /// violated, if any
+    ///
+    /// The name servers use the `PEER` implementation
+    pub fn check(&self, subject: &Implementation) -> Result<Option<Violation>> {
+        let needle_fqdn = FQDN("example.nameservers.com.")?;
+        let network = Network::new()?;
+
+        let mut leaf_ns = NameServer::new(&crate::PEER, FQDN::NAMESERVERS, &network)?;
+        leaf_ns.add(Record::a(needle_fqdn.clone(), NEEDLE_IPV4_ADDR));
+
+        let Graph {
+            nameservers,
+            root,
+            trust_anchor,
+        } = Graph::build(
+            leaf_ns,
+            Sign::AndAmend(&|zone, records| {
+                if zone == &FQDN::NAMESERVERS {
+                    for mutation in &self.mutations {
+                        mutation.amend(zone, records);
+                    }
+                }
+            }),
+        )?;
+
+        let mut settings = Resolver::new(&network, root);
+        settings.trust_anchor(&trust_anchor.unwrap());
+
+        let rules = self
+            .mutations
+            .iter()
+            .filter_map(Mutation::action)
+            .collect::<Vec<_>>();
+        let _proxy = if rules.is_empty() {
+            None
+        } else {
+            let leaf_ns = nameservers
+                .iter()
+                .find(|nameserver| nameserver.zone() == &FQDN::NAMESERVERS)
+                .expect("unreachable: the graph contains the leaf zone");
+
+            let mut proxy = RogueServer::proxy(leaf_ns)?;
+            for action in rules {
+                proxy.rule(Rule::new(action));
+            }
+            let proxy = proxy.start()?;
+
+            settings.redirect(leaf_ns.ipv4_addr(), proxy.ipv4_addr());
+            Some(proxy)
+        };
+
+        let resolver = settings.start(subject)?;
+        let client = Client::new(&network)?;
+        let output = client.dig(
+            *DigSettings::default().recurse().authentic_data(),
+            resolver.ipv4_addr(),
+            RecordType::A,
+            &needle_fqdn,
+        );
+
+        if !resolver.is_alive() {
+            resolver.reap()?;
+            return Ok(Some(Violation::Crash));
+        }
+
+        let expectation = self.expectation();
+        let output = match output {
+            Ok(output) => output,
+            // e.g. a timeout
+            Err(_) if expectation == Expectation::Valid => {
+                return Ok(Some(Violation::ServfailOnValidData))
+            }
+            Err(_) => return Ok(None),
+        };
+
+        let is_needle = matches!(
+            &output.answer[..],
+            [Record::A(a)] if a.fqdn == needle_fqdn && a.ipv4_addr == NEEDLE_IPV4_ADDR
+        );
+        let authenticated = output.flags.authenticated_data;
+
+        let violation = if authenticated && (expectation == Expectation::Bogus || !is_needle) {
+            Some(Violation::AdOnBogusData)
+        } else if expectation == Expectation::Valid && output.status.is_servfail() {
+            Some(Violation::ServfailOnValidData)
+        } else {
+            None
+        };
+
+        Ok(violation)
+    }
+
+    /// Removes the mutations that are not needed to reproduce `violation`
+    ///
+    /// Each mutation is removed in turn, and the smaller scenario kept if it still violates the
+    /// same invariant, until no mutation can be removed
+    pub fn minimise(&self, subject: &Implementation, violation: Violation) -> Scenario {
+        let mut minimal = self.clone();
+        let mut index = 0;
+        while index < minimal.mutations.len() {
+            let mut candidate = minimal.clone();
+            candidate.mutations.remove(index);
+
+            if matches!(candidate.check(subject), Ok(Some(found)) if found == violation) {
+                minimal = candidate;
+            } else {
+                index += 1;
+            }
+        }
+
+        minimal
+    }
+
+    fn expectation(&self) -> Expectation {
+        let expectations = self.mutations.iter().map(Mutation::expectation);
+        if expectations.clone().any(|e| e == Expectation::Bogus) {
+            Expectation::Bogus
+        } else if expectations.clone().all(|e| e == Expectation::Valid) {
+            Expectation::Valid
+        } else {
+            Expectation::Unknown
+        }
+    }
+}
+
+/// One mutation per line; empty lines and lines that start with `#` are comments
+impl fmt::Display for Scenario {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for mutation in &self.mutations {
+            writeln!(f, "{mutation}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let mutations = input
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::parse)
+            .collect::<Result<_>>()?;
+
+        Ok(Self { mutations })
+    }
+}
+
+/// Checks random scenarios against a resolver
+pub struct Campaign {
+    corpus_dir: Option<PathBuf>,
+    iterations: usize,
+    max_mutations: usize,
+    seed: u64,
+}
+
+impl Campaign {
+    /// The scenarios of a campaign only depend on its `seed` and settings
+    pub fn new(seed: u64) -> Self {
+        Self {
+            corpus_dir: None,
+            iterations: 100,
+            max_mutations: 3,
+            seed,
+        }
+    }
+
+    /// Number of scenarios to check; defaults to 100
+    pub fn iterations(&mut self, iterations: usize) -> &mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Maximum number of mutations per scenario; defaults to 3
+    pub fn max_mutations(&mut self, max_mutations: usize) -> &mut Self {
+        self.max_mutations = max_mutations;
+        self
+    }
+
+    /// Saves the minimised scenarios that violate an invariant to `dir`, which must exist
+    pub fn corpus_dir(&mut self, dir: impl Into<PathBuf>) -> &mut Self {
+        self.corpus_dir = Some(dir.into());
+        self
+    }
+
+    /// Returns the scenario checked at `iteration`
+    pub fn scenario(&self, iteration: usize) -> Scenario {
+        let mut rng = Rng::new(self.seed.wrapping_add(iteration as u64));
+        Scenario::random(&mut rng, self.max_mutations)
+    }
+
+    /// Checks the scenarios against `subject` and returns the ones that violated an invariant,
+    /// minimised
+    ///
+    /// `progress` is called after each scenario is checked, with its iteration number and the
+    /// result. Scenarios that could not be checked, e.g. because a name server refused to load
+    /// its mutated zone, are skipped
+    pub fn run(
+        &self,
+        subject: &Implementation,
+        mut progress: impl FnMut(usize, &Scenario, &Result<Option<Violation>>),
+    ) -> Result<Vec<Finding>> {
+        let mut findings = vec![];
+        for iteration in 0..self.iterations {
+            let scenario = self.scenario(iteration);
+            let result = scenario.check(subject);
+            progress(iteration, &scenario, &result);
+
+            let Ok(Some(violation)) = result else {
+                continue;
+            };
+
+            let scenario = scenario.minimise(subject, violation);
+            let path = if let Some(dir) = &self.corpus_dir {
+                let path = dir.join(format!("{:016x}-{iteration}.scenario", self.seed));
+                fs::write(&path, format!("# {violation}\n{scenario}"))?;
+                Some(path)
+            } else {
+                None
+            };
+
+            findings.push(Finding {
+                path,
+                scenario,
+                violation,
+            });
+        }
+
+        Ok(findings)
+    }
+}
+
+/// A minimised scenario that violates an invariant
+#[derive(Debug)]
+pub struct Finding {
+    /// Where the scenario was saved, if a corpus directory was set
+    pub path: Option<PathBuf>,
+    pub scenario: Scenario,
+    pub violation: Violation,
+}
+
+/// A deterministic pseudo-random number generator (xorshift64*), so that campaigns can be
+/// reproduced from their seed
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // scramble the seed (splitmix64) so that consecutive seeds produce unrelated sequences;
+        // the state must not be zero
+        let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+
+        Self { state: z.max(1) }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    /// Returns a number in the range `0..n`
+    ///
+    /// # Panics
+    ///
+    /// this method panics if `n` is zero
+    pub fn below(&mut self, n: usize) -> usize {
+        assert_ne!(0, n, "the range cannot be empty");
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mutations_roundtrip() -> Result<()> {
+        let mut rng = Rng::new(0);
+        for _ in 0..1_000 {
+            let mutation = Mutation::random(&mut rng);
+            assert_eq!(mutation, mutation.to_string().parse()?);
+        }
+
+        assert!("wire move answer".parse::<Mutation>().is_err());
+        assert!("zone drop-rrsigs CNAME".parse::<Mutation>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn campaigns_are_deterministic() {
+        let mut campaign = Campaign::new(42);
+        campaign.max_mutations(5);
+
+        let scenarios = (0..10).map(|i| campaign.scenario(i)).collect::<Vec<_>>();
+        assert_eq!(
+            scenarios,
+            (0..10).map(|i| campaign.scenario(i)).collect::<Vec<_>>()
+        );
+        assert_ne!(scenarios[0], scenarios[1]);
+        assert!(scenarios
+            .iter()
+            .all(|scenario| (1..=5).contains(&scenario.mutations.len())));
+    }
+
+    #[test]
+    fn scenario_file() -> Result<()> {
+        let scenario: Scenario = "# the AD flag was set on bogus data
+zone corrupt-signature A 12
+
+wire move answer additional
+"
+        .parse()?;
+
+        assert_eq!(
+            vec![
+                Mutation::ZoneCorruptSignature {
+                    covered: RecordType::A,
+                    bit: 12
+                },
+                Mutation::WireMove(Section::Answer, Section::Additional)
+            ],
+            scenario.mutations
+        );
+        assert_eq!(Expectation::Bogus, scenario.expectation());
+
+        Ok(())
+    }
+
+    #[test]
+    fn zone_mutations() -> Result<()> {
+        let zone = FQDN::NAMESERVERS;
+        let mut records = vec![
+            Record::a(FQDN("example.nameservers.com.")?, NEEDLE_IPV4_ADDR),
+            "example.nameservers.com. 86400 IN RRSIG A 8 3 86400 20240101000000 20230101000000 12345 nameservers.com. AAAA".parse()?,
+            Record::ns(zone.clone(), FQDN("primary0.nameservers.com.")?),
+        ];
+
+        Mutation::ZoneCorruptSignature {
+            covered: RecordType::A,
+            bit: 7,
+        }
+        .amend(&zone, &mut records);
+        let Record::RRSIG(rrsig) = &records[1] else {
+            panic!("expected an RRSIG");
+        };
+        assert_eq!("AQAA", rrsig.signature);
+
+        Mutation::ZoneRelabel(RecordType::A).amend(&zone, &mut records);
+        Mutation::ZoneRelabel(RecordType::NS).amend(&zone, &mut records);
+        assert_eq!("x.example.nameservers.com.", records[0].owner().as_str());
+        // apex records are left alone
+        assert_eq!(&zone, records[2].owner());
+
+        Mutation::ZoneDropRrsigs(RecordType::A).amend(&zone, &mut records);
+        assert_eq!(2, records.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn unmodified_data_validates() -> Result<()> {
+        assert_eq!(None, Scenario::default().check(&Implementation::Unbound)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn stripped_signatures_are_not_authenticated() -> Result<()> {
+        let scenario = Scenario {
+            mutations: vec![Mutation::WireDropRrsigs, Mutation::WireReverse],
+        };
+
+        assert_eq!(None, scenario.check(&Implementation::Unbound)?);
+
+        Ok(())
+    }
+}
@@ -1,21 +1,39 @@
 use core::fmt;
+use core::str::FromStr;
 use std::borrow::Cow;
-use std::path::Path;
+use std::env;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
 
 use url::Url;
 
-use crate::FQDN;
+use crate::container::Probe;
+use crate::{Error, TsigKey, FQDN};
 
 #[derive(Clone, Copy)]
 pub enum Config<'a> {
     NameServer {
         origin: &'a FQDN,
+        log_level: LogLevel,
+        access: &'a ZoneAccess,
+        /// `Some` if the zone file has been signed; the keys are in the zones directory
+        signed: Option<DnssecPolicy>,
     },
     Resolver {
         use_dnssec: bool,
         netmask: &'a str,
         /// Extended DNS error (RFC8914)
         ede: bool,
+        log_level: LogLevel,
+    },
+    Forwarder {
+        upstreams: &'a [Ipv4Addr],
+        /// validate responses locally, in addition to whatever validation the upstreams do
+        use_dnssec: bool,
+        netmask: &'a str,
+        /// fall back to recursive resolution, using the root hints, if forwarding fails
+        forward_first: bool,
+        log_level: LogLevel,
     },
 }
 
@@ -24,20 +42,236 @@ impl Config<'_> {
         match self {
             Config::NameServer { .. } => Role::NameServer,
             Config::Resolver { .. } => Role::Resolver,
+            Config::Forwarder { .. } => Role::Forwarder,
+        }
+    }
+
+    pub fn log_level(&self) -> LogLevel {
+        match self {
+            Config::NameServer { log_level, .. }
+            | Config::Resolver { log_level, .. }
+            | Config::Forwarder { log_level, .. } => *log_level,
+        }
+    }
+}
+
+/// Changes to the configuration file that is rendered from the built-in templates
+#[derive(Clone, Default)]
+pub(crate) struct ConfigOverrides {
+    /// replaces the built-in template
+    pub(crate) template: Option<String>,
+    /// appended, in order, to the rendered file
+    pub(crate) snippets: Vec<String>,
+}
+
+/// Renders the replacement template of `$overrides`, if there's one, or else the built-in
+/// `$builtin` with the given variables
+///
+/// Errors in the replacement template are returned as `Err`; the built-in templates are known
+/// to render
+macro_rules! render_config {
+    ($overrides:expr, $builtin:expr, $($key:ident => $value:expr),* $(,)?) => {
+        match &$overrides.template {
+            Some(template) => minijinja::Environment::new()
+                .render_str(template, minijinja::context! { $($key => $value,)* })
+                .map_err(|e| format!("could not render the configuration template: {e}")),
+            None => Ok(minijinja::render!($builtin, $($key => $value,)*)),
+        }
+    };
+}
+
+/// How a zone was signed; BIND needs this to re-sign the zone after dynamic updates
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct DnssecPolicy {
+    /// DNSKEY algorithm number of both keys
+    pub(crate) algorithm: u8,
+    pub(crate) zsk_bits: usize,
+    pub(crate) ksk_bits: usize,
+    /// the NSEC3 records have the opt-out flag set
+    pub(crate) opt_out: bool,
+}
+
+impl DnssecPolicy {
+    /// Key sizes are only configurable for the RSA algorithms
+    fn key_bits(&self, bits: usize) -> Option<usize> {
+        // RSAMD5, RSASHA1, RSASHA1-NSEC3-SHA1, RSASHA256, RSASHA512
+        matches!(self.algorithm, 1 | 5 | 7 | 8 | 10).then_some(bits)
+    }
+}
+
+/// Who may transfer (AXFR / IXFR) and update (RFC2136) the zone of a name server
+#[derive(Clone, Default)]
+pub(crate) struct ZoneAccess {
+    /// `Some` if the name server is a secondary that transfers the zone from this primary
+    pub(crate) primary: Option<Ipv4Addr>,
+    /// serve the zone to other servers, e.g. secondaries, over AXFR / IXFR
+    pub(crate) allow_transfer: bool,
+    /// accept dynamic updates
+    pub(crate) allow_update: bool,
+    /// send NOTIFY messages to these servers, in addition to the ones listed in the NS records,
+    /// when the zone changes
+    pub(crate) also_notify: Vec<Ipv4Addr>,
+    /// if set, transfers and updates must be signed with this key
+    pub(crate) tsig_key: Option<TsigKey>,
+}
+
+impl ZoneAccess {
+    /// Returns an error if `implementation` does not support this kind of access
+    pub(crate) fn check_supported(&self, implementation: &Implementation) -> crate::Result<()> {
+        if self.allow_update && !implementation.is_bind() {
+            let server = if implementation.is_unbound() {
+                "NSD"
+            } else {
+                "hickory-dns"
+            };
+            return Err(format!("{server} does not support dynamic updates").into());
+        }
+
+        if implementation.is_hickory() {
+            if self.primary.is_some() {
+                return Err("hickory-dns is not supported as a secondary name server".into());
+            }
+
+            if self.tsig_key.is_some() || !self.also_notify.is_empty() {
+                return Err("hickory-dns does not support TSIG-signed access nor NOTIFY".into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// How verbose the logs of a server are
+///
+/// Each implementation maps these levels to its own setting:
+///
+/// | level   | BIND  | hickory-dns              | NSD            | unbound        |
+/// |---------|-------|--------------------------|----------------|----------------|
+/// | `Error` | `-d0` | `-q`, `RUST_LOG=error`   | `verbosity: 0` | `verbosity: 0` |
+/// | `Warn`  | `-d0` | `-q`, `RUST_LOG=warn`    | `verbosity: 0` | `verbosity: 1` |
+/// | `Info`  | `-d0` | `RUST_LOG=info`          | `verbosity: 1` | `verbosity: 2` |
+/// | `Debug` | `-d5` | `-d`, `RUST_LOG=debug`   | `verbosity: 2` | `verbosity: 4` |
+/// | `Trace` | `-d9` | `-d`, `RUST_LOG=trace`   | `verbosity: 2` | `verbosity: 5` |
+///
+/// Note that BIND always logs messages of severity info and above
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    #[default]
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    /// The level set through the `DNS_TEST_LOG_LEVEL` environment variable or `Debug` if the
+    /// variable is not set
+    ///
+    /// # Panics
+    ///
+    /// this function panics if the variable is set to something that's not a log level
+    pub fn from_env() -> Self {
+        Self::from_env_or(LogLevel::default())
+    }
+
+    /// Like `from_env` but falls back to `default` if the variable is not set
+    ///
+    /// # Panics
+    ///
+    /// this function panics if the variable is set to something that's not a log level
+    pub fn from_env_or(default: Self) -> Self {
+        match env::var("DNS_TEST_LOG_LEVEL") {
+            Ok(level) => level
+                .parse()
+                .unwrap_or_else(|e| panic!("DNS_TEST_LOG_LEVEL: {e}")),
+            Err(_) => default,
+        }
+    }
+
+    fn bind_debug_level(&self) -> u8 {
+        match self {
+            LogLevel::Error | LogLevel::Warn | LogLevel::Info => 0,
+            LogLevel::Debug => 5,
+            LogLevel::Trace => 9,
+        }
+    }
+
+    fn hickory_flag(&self) -> &'static str {
+        match self {
+            LogLevel::Error | LogLevel::Warn => "-q",
+            LogLevel::Info => "",
+            LogLevel::Debug | LogLevel::Trace => "-d",
         }
     }
+
+    fn nsd_verbosity(&self) -> u8 {
+        match self {
+            LogLevel::Error | LogLevel::Warn => 0,
+            LogLevel::Info => 1,
+            LogLevel::Debug | LogLevel::Trace => 2,
+        }
+    }
+
+    fn unbound_verbosity(&self) -> u8 {
+        match self {
+            LogLevel::Error => 0,
+            LogLevel::Warn => 1,
+            LogLevel::Info => 2,
+            LogLevel::Debug => 4,
+            LogLevel::Trace => 5,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        };
+
+        f.write_str(s)
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let level = match input {
+            "error" => LogLevel::Error,
+            "warn" => LogLevel::Warn,
+            "info" => LogLevel::Info,
+            "debug" => LogLevel::Debug,
+            "trace" => LogLevel::Trace,
+            _ => {
+                return Err(format!(
+                    "`{input}` is not one of 'error', 'warn', 'info', 'debug' or 'trace'"
+                )
+                .into())
+            }
+        };
+
+        Ok(level)
+    }
 }
 
 #[derive(Clone, Copy)]
 pub enum Role {
     NameServer,
     Resolver,
+    Forwarder,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub enum Implementation {
     Bind,
     Hickory(Repository<'static>),
+    #[default]
     Unbound,
 }
 
@@ -60,63 +294,182 @@ impl Implementation {
         matches!(self, Self::Hickory(_))
     }
 
-    pub(crate) fn format_config(&self, config: Config) -> String {
-        match config {
+    #[must_use]
+    pub fn is_unbound(&self) -> bool {
+        matches!(self, Self::Unbound)
+    }
+
+    /// Renders the configuration file of the server
+    ///
+    /// Returns an error if the replacement template in `overrides` cannot be rendered
+    pub(crate) fn format_config(
+        &self,
+        config: Config,
+        overrides: &ConfigOverrides,
+    ) -> crate::Result<String> {
+        let mut rendered = match config {
             Config::Resolver {
                 use_dnssec,
                 netmask,
                 ede,
+                log_level,
             } => match self {
                 Self::Bind => {
                     assert!(!ede, "the BIND resolver does not support EDE (RFC8914)");
 
-                    minijinja::render!(
+                    render_config!(
+                        overrides,
                         include_str!("templates/named.resolver.conf.jinja"),
                         use_dnssec => use_dnssec,
                         netmask => netmask,
-                    )
+                    )?
                 }
 
                 Self::Hickory(_) => {
                     // TODO enable EDE in Hickory when supported
-                    minijinja::render!(
+                    render_config!(
+                        overrides,
                         include_str!("templates/hickory.resolver.toml.jinja"),
                         use_dnssec => use_dnssec,
-                    )
+                    )?
                 }
 
-                Self::Unbound => {
-                    minijinja::render!(
-                        include_str!("templates/unbound.conf.jinja"),
-                        use_dnssec => use_dnssec,
-                        netmask => netmask,
-                        ede => ede,
-                    )
-                }
+                Self::Unbound => render_config!(
+                    overrides,
+                    include_str!("templates/unbound.conf.jinja"),
+                    use_dnssec => use_dnssec,
+                    netmask => netmask,
+                    ede => ede,
+                    verbosity => log_level.unbound_verbosity(),
+                )?,
             },
 
-            Config::NameServer { origin } => match self {
-                Self::Bind => {
-                    minijinja::render!(
-                        include_str!("templates/named.name-server.conf.jinja"),
-                        fqdn => origin.as_str()
-                    )
-                }
+            Config::Forwarder {
+                upstreams,
+                use_dnssec,
+                netmask,
+                forward_first,
+                log_level,
+            } => match self {
+                Self::Bind => render_config!(
+                    overrides,
+                    include_str!("templates/named.forwarder.conf.jinja"),
+                    upstreams => upstreams,
+                    use_dnssec => use_dnssec,
+                    forward_first => forward_first,
+                )?,
 
-                Self::Unbound => {
-                    minijinja::render!(
-                        include_str!("templates/nsd.conf.jinja"),
-                        fqdn => origin.as_str()
-                    )
+                Self::Hickory(_) => {
+                    assert!(
+                        !use_dnssec,
+                        "the hickory forwarder does not support local validation"
+                    );
+                    assert!(
+                        !forward_first,
+                        "the hickory forwarder does not support the forward-first policy"
+                    );
+
+                    render_config!(
+                        overrides,
+                        include_str!("templates/hickory.forwarder.toml.jinja"),
+                        upstreams => upstreams,
+                    )?
                 }
 
-                Self::Hickory(_) => {
-                    minijinja::render!(
+                Self::Unbound => render_config!(
+                    overrides,
+                    include_str!("templates/unbound.forwarder.conf.jinja"),
+                    upstreams => upstreams,
+                    use_dnssec => use_dnssec,
+                    netmask => netmask,
+                    forward_first => forward_first,
+                    verbosity => log_level.unbound_verbosity(),
+                )?,
+            },
+
+            Config::NameServer {
+                origin,
+                log_level,
+                access,
+                signed,
+            } => {
+                let ZoneAccess {
+                    primary,
+                    allow_transfer,
+                    allow_update,
+                    also_notify,
+                    tsig_key,
+                } = access;
+                let primary = primary.map(|addr| addr.to_string());
+                let tsig_name = tsig_key.as_ref().map(|key| key.name().as_str());
+                let tsig_secret = tsig_key.as_ref().map(TsigKey::secret);
+
+                match self {
+                    Self::Bind => render_config!(
+                        overrides,
+                        include_str!("templates/named.name-server.conf.jinja"),
+                        fqdn => origin.as_str(),
+                        primary => primary,
+                        allow_transfer => allow_transfer,
+                        allow_update => allow_update,
+                        also_notify => also_notify,
+                        signed => signed.is_some(),
+                        dnssec_algorithm => signed.map(|policy| policy.algorithm),
+                        zsk_bits => signed.and_then(|policy| policy.key_bits(policy.zsk_bits)),
+                        ksk_bits => signed.and_then(|policy| policy.key_bits(policy.ksk_bits)),
+                        opt_out => signed.is_some_and(|policy| policy.opt_out),
+                        tsig_name => tsig_name,
+                        tsig_secret => tsig_secret,
+                        tsig_algorithm => TsigKey::ALGORITHM,
+                    )?,
+
+                    // unsupported access has been rejected by `ZoneAccess::check_supported`
+                    Self::Unbound => render_config!(
+                        overrides,
+                        include_str!("templates/nsd.conf.jinja"),
+                        fqdn => origin.as_str(),
+                        verbosity => log_level.nsd_verbosity(),
+                        primary => primary,
+                        allow_transfer => allow_transfer,
+                        also_notify => also_notify,
+                        tsig_name => tsig_name,
+                        tsig_secret => tsig_secret,
+                        tsig_algorithm => TsigKey::ALGORITHM,
+                    )?,
+
+                    // unsupported access has been rejected by `ZoneAccess::check_supported`
+                    Self::Hickory(_) => render_config!(
+                        overrides,
                         include_str!("templates/hickory.name-server.toml.jinja"),
-                        fqdn => origin.as_str()
-                    )
+                        fqdn => origin.as_str(),
+                        allow_transfer => allow_transfer,
+                    )?,
                 }
-            },
+            }
+        };
+
+        for snippet in &overrides.snippets {
+            if !rendered.ends_with('\n') {
+                rendered.push('\n');
+            }
+            rendered.push_str(snippet);
+        }
+
+        Ok(rendered)
+    }
+
+    pub(crate) fn readiness_probe<'a>(&self, config: &Config<'a>) -> Probe<'a> {
+        match config {
+            // the recursor logs this, at the info level, once it has finished its start sequence
+            Config::Resolver { log_level, .. }
+                if self.is_hickory() && *log_level >= LogLevel::Info =>
+            {
+                Probe::LogMarker("Server starting up")
+            }
+
+            Config::Resolver { .. } | Config::Forwarder { .. } => Probe::Query(&FQDN::ROOT),
+
+            Config::NameServer { origin, .. } => Probe::Query(origin),
         }
     }
 
@@ -128,26 +481,35 @@ impl Implementation {
 
             Self::Unbound => match role {
                 Role::NameServer => "/etc/nsd/nsd.conf",
-                Role::Resolver => "/etc/unbound/unbound.conf",
+                Role::Resolver | Role::Forwarder => "/etc/unbound/unbound.conf",
             },
         }
     }
 
-    pub(crate) fn cmd_args(&self, role: Role) -> &[&'static str] {
+    pub(crate) fn cmd_args(&self, config: &Config) -> Vec<String> {
+        let log_level = config.log_level();
         match self {
-            Implementation::Bind => &["named", "-g", "-d5"],
+            Implementation::Bind => vec![
+                "named".into(),
+                "-g".into(),
+                format!("-d{}", log_level.bind_debug_level()),
+            ],
 
-            Implementation::Hickory(_) => &[
-                "sh",
-                "-c",
-                "echo $$ > /tmp/hickory.pid
-exec hickory-dns -d",
+            Implementation::Hickory(_) => vec![
+                "sh".into(),
+                "-c".into(),
+                format!(
+                    "echo $$ > /tmp/hickory.pid
+export RUST_LOG={log_level}
+exec hickory-dns {}",
+                    log_level.hickory_flag()
+                ),
             ],
 
-            Implementation::Unbound => match role {
-                Role::NameServer => &["nsd", "-d"],
+            Implementation::Unbound => match config.role() {
+                Role::NameServer => vec!["nsd".into(), "-d".into()],
 
-                Role::Resolver => &["unbound", "-d"],
+                Role::Resolver | Role::Forwarder => vec!["unbound".into(), "-d".into()],
             },
         }
     }
@@ -160,7 +522,7 @@ exec hickory-dns -d",
 
             Implementation::Unbound => match role {
                 Role::NameServer => "/tmp/nsd.pid",
-                Role::Resolver => "/tmp/unbound.pid",
+                Role::Resolver | Role::Forwarder => "/tmp/unbound.pid",
             },
         }
     }
@@ -178,14 +540,151 @@ impl fmt::Display for Implementation {
     }
 }
 
-#[derive(Clone, Debug)]
+/// A `hickory-dns` build: either a git repository plus the options used to `cargo install` it or
+/// an already-built `hickory-dns` binary
+#[derive(Clone, Debug, PartialEq)]
 pub struct Repository<'a> {
-    inner: Cow<'a, str>,
+    source: Source<'a>,
+    features: Vec<String>,
+    profile: Profile,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Source<'a> {
+    Git {
+        url: Cow<'a, str>,
+        /// branch, tag or commit to check out
+        revision: Option<String>,
+        /// include uncommitted changes
+        working_tree: bool,
+    },
+    Binary(PathBuf),
+}
+
+/// The cargo profile used to build `hickory-dns`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Profile {
+    #[default]
+    Debug,
+    Release,
 }
 
 impl Repository<'_> {
-    pub(crate) fn as_str(&self) -> &str {
-        &self.inner
+    const DEFAULT_FEATURES: [&'static str; 2] = ["recursor", "dnssec-ring"];
+
+    /// Uses the already-built `hickory-dns` binary at `path` instead of building one from source
+    ///
+    /// The binary is copied into a Debian (bookworm) image so it must be compatible with that
+    /// distribution, e.g. its glibc version
+    ///
+    /// # Panics
+    ///
+    /// this function panics if `path` is not an existing file
+    pub fn binary(path: impl Into<PathBuf>) -> Repository<'static> {
+        let path = path.into();
+        assert!(path.is_file(), "{} is not a file", path.display());
+
+        Repository {
+            source: Source::Binary(path),
+            features: vec![],
+            profile: Profile::default(),
+        }
+    }
+
+    /// Checks out the given branch, tag or commit instead of the default branch / `HEAD`
+    ///
+    /// # Panics
+    ///
+    /// this method panics if `self` is a binary or if `working_tree` has been set
+    #[must_use]
+    pub fn revision(mut self, revision: impl Into<String>) -> Self {
+        let Source::Git {
+            revision: rev,
+            working_tree,
+            ..
+        } = &mut self.source
+        else {
+            panic!("`revision` cannot be used with a `hickory-dns` binary")
+        };
+        assert!(
+            !*working_tree,
+            "`revision` and `working_tree` are mutually exclusive"
+        );
+
+        *rev = Some(revision.into());
+        self
+    }
+
+    /// Builds the working tree of a local repository, including changes that have not been
+    /// committed, instead of a clone of it
+    ///
+    /// # Panics
+    ///
+    /// this method panics if `self` is a binary, a remote repository or if `revision` has been set
+    #[must_use]
+    pub fn working_tree(mut self) -> Self {
+        let Source::Git {
+            url,
+            revision,
+            working_tree,
+        } = &mut self.source
+        else {
+            panic!("`working_tree` cannot be used with a `hickory-dns` binary")
+        };
+        assert!(
+            Path::new(&**url).is_dir(),
+            "`working_tree` can only be used with a local repository"
+        );
+        assert!(
+            revision.is_none(),
+            "`revision` and `working_tree` are mutually exclusive"
+        );
+
+        *working_tree = true;
+        self
+    }
+
+    /// Sets the cargo features `hickory-dns` is built with
+    ///
+    /// Defaults to `recursor` and `dnssec-ring`. An empty list builds the default features of
+    /// `hickory-dns`. Forwarders are additionally built with the `resolver` feature
+    #[must_use]
+    pub fn features<S>(mut self, features: impl IntoIterator<Item = S>) -> Self
+    where
+        S: Into<String>,
+    {
+        self.features = features.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the cargo profile `hickory-dns` is built with
+    ///
+    /// Defaults to `Profile::Debug`
+    #[must_use]
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    pub(crate) fn source(&self) -> &Source<'_> {
+        &self.source
+    }
+
+    /// Adds `feature` to the cargo features, unless the default features of `hickory-dns` are
+    /// used
+    pub(crate) fn with_feature(mut self, feature: &str) -> Self {
+        if !self.features.is_empty() && !self.features.iter().any(|enabled| enabled == feature) {
+            self.features.push(feature.to_string());
+        }
+        self
+    }
+
+    pub(crate) fn cargo_features(&self) -> &[String] {
+        &self.features
+    }
+
+    pub(crate) fn cargo_profile(&self) -> Profile {
+        self.profile
     }
 }
 
@@ -201,11 +700,197 @@ pub fn Repository(input: impl Into<Cow<'static, str>>) -> Repository<'static> {
         Path::new(&*input).exists() || Url::parse(&input).is_ok(),
         "{input} is not a valid repository"
     );
-    Repository { inner: input }
+    Repository {
+        source: Source::Git {
+            url: input,
+            revision: None,
+            working_tree: false,
+        },
+        features: Repository::DEFAULT_FEATURES
+            .iter()
+            .map(|feature| feature.to_string())
+            .collect(),
+        profile: Profile::default(),
+    }
 }
 
-impl Default for Implementation {
-    fn default() -> Self {
-        Self::Unbound
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsupported_zone_access_is_an_error() -> crate::Result<()> {
+        let hickory = Implementation::Hickory(Repository(env!("CARGO_MANIFEST_DIR")));
+        let secondary = ZoneAccess {
+            primary: Some(Ipv4Addr::new(192, 0, 2, 1)),
+            ..ZoneAccess::default()
+        };
+        let signed = ZoneAccess {
+            allow_transfer: true,
+            tsig_key: Some(TsigKey::generate(FQDN("transfer-key.")?)?),
+            ..ZoneAccess::default()
+        };
+
+        let update = ZoneAccess {
+            allow_update: true,
+            ..ZoneAccess::default()
+        };
+
+        assert!(secondary.check_supported(&hickory).is_err());
+        assert!(update.check_supported(&hickory).is_err());
+        assert!(update.check_supported(&Implementation::Unbound).is_err());
+        assert!(update.check_supported(&Implementation::Bind).is_ok());
+        assert!(signed.check_supported(&hickory).is_err());
+        assert!(secondary.check_supported(&Implementation::Unbound).is_ok());
+        assert!(signed.check_supported(&Implementation::Bind).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn with_feature() {
+        let repo = Repository(env!("CARGO_MANIFEST_DIR")).with_feature("resolver");
+        assert_eq!(
+            ["recursor", "dnssec-ring", "resolver"].as_slice(),
+            repo.cargo_features()
+        );
+        assert_eq!(
+            repo.cargo_features(),
+            repo.clone().with_feature("resolver").cargo_features()
+        );
+
+        let default_features = Repository(env!("CARGO_MANIFEST_DIR"))
+            .features::<&str>([])
+            .with_feature("resolver");
+        assert!(default_features.cargo_features().is_empty());
+    }
+
+    #[test]
+    fn config_overrides() -> crate::Result<()> {
+        let access = ZoneAccess::default();
+        let config = Config::NameServer {
+            origin: &FQDN::ROOT,
+            log_level: LogLevel::Info,
+            access: &access,
+            signed: None,
+        };
+
+        let builtin = Implementation::Unbound.format_config(config, &ConfigOverrides::default())?;
+        let overrides = ConfigOverrides {
+            template: None,
+            snippets: vec!["  zonestats: main".to_string()],
+        };
+        let appended = Implementation::Unbound.format_config(config, &overrides)?;
+        assert!(appended.starts_with(&builtin));
+        assert!(appended.ends_with("\n  zonestats: main"));
+
+        let overrides = ConfigOverrides {
+            template: Some("zone: {{ fqdn }} verbosity: {{ verbosity }}".to_string()),
+            snippets: vec![],
+        };
+        let replaced = Implementation::Unbound.format_config(config, &overrides)?;
+        assert_eq!("zone: . verbosity: 1", replaced);
+
+        let overrides = ConfigOverrides {
+            template: Some("zone: {{ fqdn }".to_string()),
+            snippets: vec![],
+        };
+        assert!(Implementation::Unbound
+            .format_config(config, &overrides)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn forwarder_config() -> crate::Result<()> {
+        let upstreams = [Ipv4Addr::new(10, 0, 0, 2), Ipv4Addr::new(10, 0, 0, 3)];
+        let config = Config::Forwarder {
+            upstreams: &upstreams,
+            use_dnssec: false,
+            netmask: "10.0.0.0/24",
+            forward_first: false,
+            log_level: LogLevel::Info,
+        };
+        let overrides = ConfigOverrides::default();
+
+        let unbound = Implementation::Unbound.format_config(config, &overrides)?;
+        assert!(unbound.contains("forward-addr: 10.0.0.2"));
+        assert!(unbound.contains("forward-addr: 10.0.0.3"));
+        assert!(unbound.contains("forward-first: no"));
+        assert!(!unbound.contains("root-hints"));
+
+        let bind = Implementation::Bind.format_config(config, &overrides)?;
+        assert!(bind.contains("10.0.0.2;"));
+        assert!(bind.contains("forward only;"));
+        assert!(!bind.contains("type hint"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn zone_transfers_config() -> crate::Result<()> {
+        let access = ZoneAccess {
+            primary: Some(Ipv4Addr::new(10, 0, 0, 2)),
+            allow_transfer: false,
+            allow_update: false,
+            also_notify: vec![],
+            tsig_key: Some(TsigKey::generate(FQDN("transfer-key.")?)?),
+        };
+        let config = Config::NameServer {
+            origin: &FQDN::COM,
+            log_level: LogLevel::Info,
+            access: &access,
+            signed: None,
+        };
+        let overrides = ConfigOverrides::default();
+
+        let nsd = Implementation::Unbound.format_config(config, &overrides)?;
+        assert!(nsd.contains("request-xfr: 10.0.0.2 transfer-key.\n"));
+        assert!(nsd.contains("allow-notify: 10.0.0.2 NOKEY\n"));
+        assert!(!nsd.contains("provide-xfr: 0.0.0.0/0"));
+
+        let bind = Implementation::Bind.format_config(config, &overrides)?;
+        assert!(bind.contains("type secondary;"));
+        assert!(bind.contains("primaries { 10.0.0.2 key \"transfer-key.\"; };"));
+        assert!(bind.contains("allow-transfer { 127.0.0.1; };"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn signed_dynamic_zone_config() -> crate::Result<()> {
+        let access = ZoneAccess {
+            allow_update: true,
+            ..ZoneAccess::default()
+        };
+        let mut policy = DnssecPolicy {
+            algorithm: 7,
+            zsk_bits: 1024,
+            ksk_bits: 2048,
+            opt_out: true,
+        };
+        let config = |policy| Config::NameServer {
+            origin: &FQDN::COM,
+            log_level: LogLevel::Info,
+            access: &access,
+            signed: Some(policy),
+        };
+        let overrides = ConfigOverrides::default();
+
+        let bind = Implementation::Bind.format_config(config(policy), &overrides)?;
+        assert!(bind.contains("ksk key-directory lifetime unlimited algorithm 7 2048;"));
+        assert!(bind.contains("zsk key-directory lifetime unlimited algorithm 7 1024;"));
+        assert!(bind.contains("nsec3param iterations 0 optout yes salt-length 0;"));
+        assert!(bind.contains("dnssec-policy \"dns-test\";"));
+        assert!(bind.contains("inline-signing yes;"));
+        assert!(!bind.contains("auto-dnssec"));
+
+        // ECDSAP256SHA256 has a fixed key size
+        policy.algorithm = 13;
+        let bind = Implementation::Bind.format_config(config(policy), &overrides)?;
+        assert!(bind.contains("zsk key-directory lifetime unlimited algorithm 13;"));
+
+        Ok(())
     }
 }
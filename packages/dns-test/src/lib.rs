@@ -1,26 +1,48 @@
 //! A test framework for all things DNS
 
 use std::env;
+use std::path::Path;
 
 use lazy_static::lazy_static;
 
-pub use crate::container::Network;
+pub use crate::container::{Faults, Impairment, Network};
 pub use crate::fqdn::FQDN;
-pub use crate::implementation::{Implementation, Repository};
-pub use crate::resolver::Resolver;
+pub use crate::implementation::{Implementation, LogLevel, Profile, Repository};
+pub use crate::resolver::{PersistentState, Resolver};
 pub use crate::trust_anchor::TrustAnchor;
+pub use crate::tsig::TsigKey;
 
 pub mod client;
 mod container;
+pub mod coverage;
+pub mod differential;
+pub mod fixture;
 mod fqdn;
+pub mod fuzz;
 mod implementation;
+pub mod matrix;
+pub mod message;
 pub mod name_server;
 pub mod record;
 mod resolver;
+pub mod rogue_server;
+pub mod session;
+pub mod topology;
 mod trust_anchor;
 pub mod tshark;
+mod tsig;
 pub mod zone_file;
 
+/// Removes the containers and networks left behind by `dns-test` processes that are no longer
+/// running, e.g. because they were terminated with Ctrl+C
+///
+/// This also happens automatically when a process creates its first `Network`
+///
+/// Returns the number of removed containers and networks
+pub fn gc() -> Result<usize> {
+    container::runtime::gc()
+}
+
 pub type Error = Box<dyn std::error::Error>;
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -44,7 +66,7 @@ fn parse_subject() -> Implementation {
 
         if subject.starts_with("hickory") {
             if let Some(url) = subject.strip_prefix("hickory ") {
-                Implementation::Hickory(Repository(url.to_string()))
+                Implementation::Hickory(parse_hickory_repository(url))
             } else {
                 panic!("the syntax of DNS_TEST_SUBJECT is 'hickory $URL', e.g. 'hickory /tmp/hickory' or 'hickory https://github.com/owner/repo'")
             }
@@ -56,6 +78,39 @@ fn parse_subject() -> Implementation {
     }
 }
 
+/// `input` is either a repository or the path to an already-built `hickory-dns` binary; the build
+/// options are read from the `DNS_TEST_HICKORY_*` environment variables
+fn parse_hickory_repository(input: &str) -> Repository<'static> {
+    if Path::new(input).is_file() {
+        return Repository::binary(input);
+    }
+
+    let mut repository = Repository(input.to_string());
+
+    if let Ok(revision) = env::var("DNS_TEST_HICKORY_REVISION") {
+        repository = repository.revision(revision);
+    }
+
+    if env::var("DNS_TEST_HICKORY_WORKING_TREE").is_ok() {
+        repository = repository.working_tree();
+    }
+
+    if let Ok(features) = env::var("DNS_TEST_HICKORY_FEATURES") {
+        repository = repository.features(features.split(',').map(str::trim));
+    }
+
+    if let Ok(profile) = env::var("DNS_TEST_HICKORY_PROFILE") {
+        let profile = match profile.as_str() {
+            "debug" => Profile::Debug,
+            "release" => Profile::Release,
+            _ => panic!("DNS_TEST_HICKORY_PROFILE must be one of 'debug' or 'release'"),
+        };
+        repository = repository.profile(profile);
+    }
+
+    repository
+}
+
 fn parse_peer() -> Implementation {
     if let Ok(peer) = env::var("DNS_TEST_PEER") {
         match peer.as_str() {
@@ -74,15 +129,6 @@ mod tests {
 
     use super::*;
 
-    impl PartialEq for Implementation {
-        fn eq(&self, other: &Self) -> bool {
-            match (self, other) {
-                (Self::Hickory(_), Self::Hickory(_)) => true,
-                _ => core::mem::discriminant(self) == core::mem::discriminant(other),
-            }
-        }
-    }
-
     #[test]
     fn immutable_subject() {
         let before = super::SUBJECT.clone();
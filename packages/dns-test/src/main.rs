@@ -0,0 +1,183 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use dns_test::coverage::Report;
+use dns_test::matrix::Runner;
+use dns_test::session::{self, Inventory, Session};
+use dns_test::Result;
+
+const CONFORMANCE_TESTS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../conformance-tests");
+
+fn main() -> Result<()> {
+    let args: Vec<_> = env::args().skip(1).collect();
+    let args: Vec<_> = args.iter().map(String::as_str).collect();
+
+    match args[..] {
+        ["up", topology] => up(Path::new(topology)),
+
+        ["dig", ref dig_args @ ..] => dig(dig_args),
+
+        ["capture"] => capture("resolver"),
+        ["capture", member] => capture(member),
+
+        ["logs", member] => {
+            let inventory = Inventory::load()?;
+            let path = inventory.log_path(inventory.member(member)?);
+            let logs = fs::read_to_string(&path)
+                .map_err(|e| format!("could not read {}: {e}", path.display()))?;
+            print!("{logs}");
+            Ok(())
+        }
+
+        ["down"] => {
+            session::down()?;
+            println!("the environment is down");
+            Ok(())
+        }
+
+        ["matrix", ref matrix_args @ ..] => matrix(matrix_args),
+
+        ["coverage"] => coverage(Path::new(CONFORMANCE_TESTS_DIR)),
+        ["coverage", dir] => coverage(Path::new(dir)),
+
+        ["gc"] => {
+            let count = dns_test::gc()?;
+            println!("removed {count} containers and networks");
+            Ok(())
+        }
+
+        _ => cli_error(),
+    }
+}
+
+fn up(topology: &Path) -> Result<()> {
+    println!("starting {}...", topology.display());
+    let session = Session::up(topology)?;
+    println!("DONE\n");
+    print!("{}", session.inventory());
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let handler_flag = interrupted.clone();
+    ctrlc::set_handler(move || handler_flag.store(true, Ordering::SeqCst))?;
+
+    println!(
+        "\nqueries: `dns-test dig example.com. A`; logs: `dns-test logs resolver`
+press Ctrl+C or run `dns-test down` to take down the environment"
+    );
+    session.run(|| interrupted.load(Ordering::SeqCst))?;
+
+    println!("taking down the environment...");
+    Ok(())
+}
+
+/// Runs `dig` in the client container; queries go to the resolver unless a server is given
+fn dig(dig_args: &[&str]) -> Result<()> {
+    let inventory = Inventory::load()?;
+    let resolver = format!("@{}", inventory.member("resolver")?.ipv4_addr);
+
+    let mut command = vec!["dig"];
+    if !dig_args.iter().any(|arg| arg.starts_with('@')) {
+        command.push(&resolver);
+    }
+    command.extend_from_slice(dig_args);
+
+    let status = inventory.member("client")?.exec(&command)?;
+    if !status.success() {
+        return Err(format!("dig failed: {status}").into());
+    }
+    Ok(())
+}
+
+/// Prints the DNS traffic that flows through `member` until interrupted
+fn capture(member: &str) -> Result<()> {
+    let inventory = Inventory::load()?;
+    let member = inventory.member(member)?;
+    if member.name == "client" {
+        return Err("`tshark` is not available in the client container".into());
+    }
+
+    member.exec(&["tshark", "-f", "udp port 53 or tcp port 53", "-O", "dns"])?;
+    Ok(())
+}
+
+/// Runs the conformance test suite across subjects and peers and saves the results
+fn matrix(matrix_args: &[&str]) -> Result<()> {
+    let mut subjects = vec![];
+    let mut peers = vec![];
+    let mut output_dir = Path::new(".");
+    let mut filter = None;
+
+    let mut matrix_args = matrix_args.iter();
+    while let Some(arg) = matrix_args.next() {
+        let Some(value) = matrix_args.next() else {
+            return cli_error();
+        };
+
+        match *arg {
+            "--subject" => subjects.push(value.to_string()),
+            "--peer" => peers.push(value.to_string()),
+            "--output" => output_dir = Path::new(value),
+            "--filter" => filter = Some(*value),
+            _ => return cli_error(),
+        }
+    }
+
+    if subjects.is_empty() {
+        subjects = vec!["unbound".to_string(), "bind".to_string()];
+    }
+    if peers.is_empty() {
+        peers = vec!["unbound".to_string()];
+    }
+
+    let mut runner = Runner::new(&subjects, &peers);
+    if let Some(filter) = filter {
+        runner.filter(filter);
+    }
+    let matrix =
+        runner.run(|column| eprintln!("running the conformance tests with {column}..."))?;
+    matrix.save(output_dir)?;
+
+    println!(
+        "wrote matrix.json, matrix.md and matrix.html to {}",
+        output_dir.display()
+    );
+    if !matrix.is_green() {
+        return Err("some tests failed".into());
+    }
+    Ok(())
+}
+
+/// Prints which RFC requirements have tests as Markdown
+fn coverage(crate_dir: &Path) -> Result<()> {
+    let report = Report::load(crate_dir)?;
+    print!("{}", report.to_markdown());
+    Ok(())
+}
+
+fn cli_error<T>() -> Result<T> {
+    eprintln!(
+        "usage: dns-test <COMMAND>
+Commands:
+  up <TOPOLOGY>     start the topology file TOPOLOGY and print its inventory; stays in the
+                    foreground until interrupted or `down` is run
+  dig [ARGS]..      run `dig ARGS` in the client container; queries go to the resolver unless
+                    `@SERVER` is given
+  capture [MEMBER]  print the DNS traffic that flows through MEMBER (default: resolver)
+  logs <MEMBER>     print the logs of MEMBER, e.g. `resolver` or `example.com.`
+  down              take down the environment started with `up`
+  matrix [OPTIONS]  run `conformance-tests` across subjects and peers and write the results to
+                    matrix.json, matrix.md and matrix.html
+      --subject S   a `DNS_TEST_SUBJECT` value; repeatable (default: unbound and bind)
+      --peer P      a `DNS_TEST_PEER` value; repeatable (default: unbound)
+      --filter F    only run the tests whose name contains F
+      --output DIR  where to write the results (default: .)
+  coverage [DIR]    print which RFC requirements the tests of the crate in DIR are tagged with
+                    (default: the conformance-tests package)
+  gc                remove the containers and networks left behind by terminated processes"
+    );
+
+    Err("CLI error".into())
+}
@@ -0,0 +1,477 @@
+//! Compatibility matrix of the `conformance-tests` suite
+//!
+//! `Runner` runs the suite once per combination of subject and peer, i.e. the values of
+//! `DNS_TEST_SUBJECT` and `DNS_TEST_PEER`, and collects the outcome of every test into a
+//! `Matrix`, which renders as JSON, Markdown or HTML. Tests are grouped by the module they live
+//! in, which mirrors the RFC sections they cover
+//!
+//! `#[ignore]`-d tests are run as well: they are the tests that are known to fail with
+//! `hickory-dns` so, when the subject is `hickory-dns`, their failures are reported as expected
+//! failures
+
+use core::fmt::{self, Write as _};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+
+const PACKAGE: &str = "conformance-tests";
+const LEGEND: &str = "expected failure: the test is `#[ignore]`-d because it is known to fail \
+                      with the subject; skip: the test did not run";
+
+/// The outcome of a test in a column of the matrix
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    Pass,
+    Fail,
+    /// The test did not run, e.g. because the test binary crashed before reaching it
+    Skip,
+    /// The test failed, as its `#[ignore]` attribute says it does with this subject
+    ExpectedFailure,
+}
+
+impl Outcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pass => "pass",
+            Self::Fail => "fail",
+            Self::Skip => "skip",
+            Self::ExpectedFailure => "expected failure",
+        }
+    }
+}
+
+/// A combination of subject and peer, in the syntax of `DNS_TEST_SUBJECT` and `DNS_TEST_PEER`
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Column {
+    pub subject: String,
+    pub peer: String,
+}
+
+impl Column {
+    fn expects_ignored_to_fail(&self) -> bool {
+        self.subject.starts_with("hickory")
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (peer: {})", self.subject, self.peer)
+    }
+}
+
+/// The outcomes of all tests, one per column
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct Matrix {
+    pub columns: Vec<Column>,
+    /// Sorted by name
+    pub tests: Vec<Row>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct Row {
+    /// The full path of the test, e.g. `resolver::dnssec::rfc4035::section_4::section_4_1::foo`
+    pub name: String,
+    pub ignored: bool,
+    /// One per column
+    pub outcomes: Vec<Outcome>,
+}
+
+impl Row {
+    /// The module path of the test
+    pub fn module(&self) -> &str {
+        self.name
+            .rsplit_once("::")
+            .map_or("", |(module, _test)| module)
+    }
+
+    fn test(&self) -> &str {
+        self.name
+            .rsplit_once("::")
+            .map_or(&self.name, |(_module, test)| test)
+    }
+}
+
+impl Matrix {
+    /// A matrix where every test is skipped in every column
+    pub fn new(columns: Vec<Column>, tests: &[String], ignored: &[String]) -> Self {
+        let mut names = tests.to_vec();
+        names.sort();
+        names.dedup();
+
+        let tests = names
+            .into_iter()
+            .map(|name| Row {
+                ignored: ignored.contains(&name),
+                outcomes: vec![Outcome::Skip; columns.len()],
+                name,
+            })
+            .collect();
+
+        Self { columns, tests }
+    }
+
+    /// Records the results, as printed by the libtest harness, of the run of `column`
+    pub fn record(&mut self, column: usize, libtest_output: &str) {
+        let expects_ignored_to_fail = self.columns[column].expects_ignored_to_fail();
+
+        for (name, passed) in parse_results(libtest_output) {
+            let Some(row) = self.tests.iter_mut().find(|row| row.name == name) else {
+                continue;
+            };
+
+            row.outcomes[column] = match (passed, row.ignored && expects_ignored_to_fail) {
+                (Some(true), _) => Outcome::Pass,
+                (Some(false), true) => Outcome::ExpectedFailure,
+                (Some(false), false) => Outcome::Fail,
+                (None, _) => Outcome::Skip,
+            };
+        }
+    }
+
+    /// Returns `true` if no test has the `Fail` outcome
+    pub fn is_green(&self) -> bool {
+        self.tests
+            .iter()
+            .all(|row| !row.outcomes.contains(&Outcome::Fail))
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+        let header = self
+            .columns
+            .iter()
+            .map(|column| format!(" {} |", escape_markdown(&column.to_string())))
+            .collect::<String>();
+        let separator = "---|".repeat(self.columns.len());
+
+        for (module, rows) in self.groups() {
+            let _ = writeln!(markdown, "### {}\n", group_title(module));
+            let _ = writeln!(markdown, "| test |{header}");
+            let _ = writeln!(markdown, "|---|{separator}");
+            for row in rows {
+                let outcomes = row
+                    .outcomes
+                    .iter()
+                    .map(|outcome| match outcome {
+                        Outcome::Fail => " **fail** |".to_string(),
+                        _ => format!(" {} |", outcome.as_str()),
+                    })
+                    .collect::<String>();
+                let _ = writeln!(markdown, "| `{}` |{outcomes}", row.test());
+            }
+            markdown.push('\n');
+        }
+
+        markdown.push_str(LEGEND);
+        markdown.push('\n');
+        markdown
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>conformance-tests compatibility matrix</title>
+<style>
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; }
+td.pass { background: #d4f7d4; }
+td.fail { background: #f7d4d4; font-weight: bold; }
+td.skip { background: #eee; }
+td.expected-failure { background: #f7efd4; }
+</style>
+</head>
+<body>
+",
+        );
+
+        let header = self
+            .columns
+            .iter()
+            .map(|column| format!("<th>{}</th>", escape_html(&column.to_string())))
+            .collect::<String>();
+        for (module, rows) in self.groups() {
+            let _ = writeln!(html, "<h3>{}</h3>", escape_html(&group_title(module)));
+            let _ = writeln!(html, "<table>\n<tr><th>test</th>{header}</tr>");
+            for row in rows {
+                let _ = write!(html, "<tr><td><code>{}</code></td>", row.test());
+                for outcome in &row.outcomes {
+                    let class = outcome.as_str().replace(' ', "-");
+                    let _ = write!(html, "<td class=\"{class}\">{}</td>", outcome.as_str());
+                }
+                html.push_str("</tr>\n");
+            }
+            html.push_str("</table>\n");
+        }
+
+        let _ = writeln!(html, "<p>{}</p>", escape_html(LEGEND));
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    /// Writes `matrix.json`, `matrix.md` and `matrix.html` into `dir`
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(dir.join("matrix.json"), self.to_json()?)?;
+        std::fs::write(dir.join("matrix.md"), self.to_markdown())?;
+        std::fs::write(dir.join("matrix.html"), self.to_html())?;
+        Ok(())
+    }
+
+    /// The rows grouped by module, in module order
+    fn groups(&self) -> BTreeMap<&str, Vec<&Row>> {
+        let mut groups = BTreeMap::<_, Vec<_>>::new();
+        for row in &self.tests {
+            groups.entry(row.module()).or_default().push(row);
+        }
+        groups
+    }
+}
+
+/// Runs the conformance test suite across columns
+pub struct Runner {
+    columns: Vec<Column>,
+    filter: Option<String>,
+}
+
+impl Runner {
+    /// Every combination of `subjects` and `peers`
+    pub fn new(subjects: &[String], peers: &[String]) -> Self {
+        let columns = subjects
+            .iter()
+            .flat_map(|subject| {
+                peers.iter().map(|peer| Column {
+                    subject: subject.clone(),
+                    peer: peer.clone(),
+                })
+            })
+            .collect();
+
+        Self {
+            columns,
+            filter: None,
+        }
+    }
+
+    /// Only runs the tests whose name contains `filter`
+    pub fn filter(&mut self, filter: impl Into<String>) -> &mut Self {
+        self.filter = Some(filter.into());
+        self
+    }
+
+    /// Runs the suite once per column, calling `progress` before each run
+    pub fn run(&self, mut progress: impl FnMut(&Column)) -> Result<Matrix> {
+        let tests = parse_list(&self.cargo_test(None, &["--list"])?);
+        let ignored = parse_list(&self.cargo_test(None, &["--list", "--ignored"])?);
+
+        let mut matrix = Matrix::new(self.columns.clone(), &tests, &ignored);
+        for (index, column) in self.columns.iter().enumerate() {
+            progress(column);
+            let output = self.cargo_test(Some(column), &["--include-ignored"])?;
+            matrix.record(index, &output);
+        }
+
+        Ok(matrix)
+    }
+
+    /// Returns the stdout of `cargo test`; failing tests do not make this return an error
+    fn cargo_test(&self, column: Option<&Column>, harness_args: &[&str]) -> Result<String> {
+        let mut command = Command::new("cargo");
+        command
+            .args(["test", "-p", PACKAGE, "--"])
+            .args(harness_args);
+        if let Some(filter) = &self.filter {
+            command.arg(filter);
+        }
+        if let Some(column) = column {
+            command
+                .env("DNS_TEST_SUBJECT", &column.subject)
+                .env("DNS_TEST_PEER", &column.peer);
+        }
+
+        let output = command.output()?;
+        let stdout = String::from_utf8(output.stdout)?;
+        if !output.status.success() && !stdout.contains("test result: ") {
+            return Err(format!(
+                "`cargo test -p {PACKAGE}` failed\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            )
+            .into());
+        }
+
+        Ok(stdout)
+    }
+}
+
+/// Parses the output of `cargo test -- --list`
+fn parse_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_suffix(": test"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parses the `test $name ... $result` lines of libtest's output; `None` means the test was
+/// ignored
+fn parse_results(output: &str) -> Vec<(String, Option<bool>)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, result) = line.strip_prefix("test ")?.split_once(" ... ")?;
+            let passed = match result {
+                "ok" => Some(true),
+                "FAILED" => Some(false),
+                _ if result.starts_with("ignored") => None,
+                _ => return None,
+            };
+            Some((name.to_string(), passed))
+        })
+        .collect()
+}
+
+/// `resolver::dnssec::rfc4035::section_4::section_4_1` -> `resolver / dnssec / RFC 4035 §4.1`
+fn group_title(module: &str) -> String {
+    let mut parts = vec![];
+    let mut section = None;
+    for segment in module.split("::") {
+        if let Some(number) = segment.strip_prefix("section_") {
+            section = Some(number.replace('_', "."));
+        } else if let Some(number) = segment.strip_prefix("rfc") {
+            parts.push(format!("RFC {number}"));
+        } else {
+            parts.push(segment.to_string());
+        }
+    }
+
+    let mut title = parts.join(" / ");
+    if let Some(section) = section {
+        let _ = write!(title, " §{section}");
+    }
+    title
+}
+
+fn escape_html(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn escape_markdown(input: &str) -> String {
+    input.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIST: &str = "\
+resolver::dnssec::rfc4035::section_4::section_4_1::sets_do_bit: test
+resolver::dnssec::scenarios::ede::dnskey_missing: test
+name_server::scenarios::soa: test
+
+3 tests, 0 benchmarks
+";
+
+    const RESULTS: &str = "
+running 3 tests
+test name_server::scenarios::soa ... ok
+test resolver::dnssec::scenarios::ede::dnskey_missing ... FAILED
+test resolver::dnssec::rfc4035::section_4::section_4_1::sets_do_bit ... ignored, flaky
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out
+";
+
+    fn matrix() -> Matrix {
+        let columns = vec![
+            Column {
+                subject: "unbound".to_string(),
+                peer: "bind".to_string(),
+            },
+            Column {
+                subject: "hickory /tmp/hickory".to_string(),
+                peer: "unbound".to_string(),
+            },
+        ];
+        let ignored = vec!["resolver::dnssec::scenarios::ede::dnskey_missing".to_string()];
+
+        let mut matrix = Matrix::new(columns, &parse_list(LIST), &ignored);
+        matrix.record(0, RESULTS);
+        matrix.record(1, RESULTS);
+        matrix
+    }
+
+    #[test]
+    fn classifies_outcomes() {
+        let matrix = matrix();
+
+        let outcomes = matrix
+            .tests
+            .iter()
+            .map(|row| (row.name.as_str(), row.outcomes.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (
+                    "name_server::scenarios::soa",
+                    vec![Outcome::Pass, Outcome::Pass]
+                ),
+                (
+                    "resolver::dnssec::rfc4035::section_4::section_4_1::sets_do_bit",
+                    vec![Outcome::Skip, Outcome::Skip]
+                ),
+                (
+                    "resolver::dnssec::scenarios::ede::dnskey_missing",
+                    vec![Outcome::Fail, Outcome::ExpectedFailure]
+                ),
+            ],
+            outcomes
+        );
+        assert!(!matrix.is_green());
+    }
+
+    #[test]
+    fn json_roundtrip() -> Result<()> {
+        let matrix = matrix();
+        let json = matrix.to_json()?;
+
+        assert!(json.contains("\"expected-failure\""));
+        assert_eq!(matrix, serde_json::from_str(&json)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn markdown_groups_by_module() {
+        let markdown = matrix().to_markdown();
+
+        assert!(markdown.contains("### resolver / dnssec / RFC 4035 §4.1\n"));
+        assert!(markdown.contains("### name_server / scenarios\n"));
+        assert!(markdown
+            .contains("| test | unbound (peer: bind) | hickory /tmp/hickory (peer: unbound) |\n"));
+        assert!(markdown.contains("| `dnskey_missing` | **fail** | expected failure |\n"));
+    }
+
+    #[test]
+    fn html_escapes_columns() {
+        let mut matrix = matrix();
+        matrix.columns[0].subject = "hickory <dev>".to_string();
+
+        let html = matrix.to_html();
+        assert!(html.contains("<th>hickory &lt;dev&gt; (peer: bind)</th>"));
+        assert!(html.contains("<td class=\"expected-failure\">expected failure</td>"));
+    }
+}
@@ -0,0 +1,392 @@
+//! Hand-crafted DNS messages, for the queries `dig` cannot express
+//!
+//! `Message` builds a query field by field; use `Client::send_raw` to send it, or arbitrary
+//! bytes, and `Header` to inspect the reply
+
+use crate::client::DigStatus;
+use crate::record::RecordType;
+use crate::{Result, FQDN};
+
+const HEADER_LEN: usize = 12;
+
+const CLASS_IN: u16 = 1;
+const TYPE_OPT: u16 = 41;
+const DEFAULT_ID: u16 = 0xd0d0;
+
+/// A DNS message whose fields, including the section counts, can be set to arbitrary, possibly
+/// inconsistent, values
+#[derive(Clone, Debug)]
+pub struct Message {
+    id: u16,
+    opcode: u8,
+    flags: Flags,
+    rcode: u8,
+    questions: Vec<u8>,
+    additional: Vec<u8>,
+    trailing: Vec<u8>,
+    counts: [u16; 4],
+    overridden_counts: [Option<u16>; 4],
+}
+
+impl Message {
+    /// A standard query for `record_type` records of `fqdn`, with no flags set
+    pub fn query(fqdn: &FQDN, record_type: RecordType) -> Self {
+        let mut message = Self::empty();
+        message.question(fqdn, record_type);
+        message
+    }
+
+    /// A standard query without questions
+    pub fn empty() -> Self {
+        Self {
+            id: DEFAULT_ID,
+            opcode: 0,
+            flags: Flags::default(),
+            rcode: 0,
+            questions: vec![],
+            additional: vec![],
+            trailing: vec![],
+            counts: [0; 4],
+            overridden_counts: [None; 4],
+        }
+    }
+
+    pub fn id(&mut self, id: u16) -> &mut Self {
+        self.id = id;
+        self
+    }
+
+    /// Sets the OPCODE field; only its 4 least significant bits are used
+    pub fn opcode(&mut self, opcode: u8) -> &mut Self {
+        self.opcode = opcode & 0xf;
+        self
+    }
+
+    /// Sets the RCODE field; only its 4 least significant bits are used
+    pub fn rcode(&mut self, rcode: u8) -> &mut Self {
+        self.rcode = rcode & 0xf;
+        self
+    }
+
+    /// Sets the RD flag
+    pub fn recurse(&mut self) -> &mut Self {
+        self.flags.recursion_desired = true;
+        self
+    }
+
+    /// Sets the AD flag
+    pub fn authentic_data(&mut self) -> &mut Self {
+        self.flags.authenticated_data = true;
+        self
+    }
+
+    /// Sets the CD flag
+    pub fn checking_disabled(&mut self) -> &mut Self {
+        self.flags.checking_disabled = true;
+        self
+    }
+
+    /// Sets the QR flag, turning the message into a response
+    pub fn response(&mut self) -> &mut Self {
+        self.flags.qr = true;
+        self
+    }
+
+    /// Appends a question
+    ///
+    /// The labels of `fqdn` are not validated so names and labels that exceed the limits of
+    /// RFC 1035 can be used
+    pub fn question(&mut self, fqdn: &FQDN, record_type: RecordType) -> &mut Self {
+        self.raw_question(&encode_name(fqdn), record_type.code())
+    }
+
+    /// Appends a question whose QNAME is `qname`, verbatim, e.g. a name that uses label
+    /// compression; the question uses the IN class
+    pub fn raw_question(&mut self, qname: &[u8], qtype: u16) -> &mut Self {
+        self.questions.extend_from_slice(qname);
+        self.questions.extend_from_slice(&qtype.to_be_bytes());
+        self.questions.extend_from_slice(&CLASS_IN.to_be_bytes());
+        self.counts[Section::Question as usize] += 1;
+        self
+    }
+
+    /// Appends an OPT record (EDNS, RFC 6891) to the additional section; calling this more than
+    /// once adds several OPT records
+    pub fn edns(&mut self, udp_payload_size: u16, dnssec_ok: bool) -> &mut Self {
+        let flags: u16 = if dnssec_ok { 0x8000 } else { 0 };
+
+        // owner: root
+        self.additional.push(0);
+        self.additional.extend_from_slice(&TYPE_OPT.to_be_bytes());
+        self.additional
+            .extend_from_slice(&udp_payload_size.to_be_bytes());
+        // extended RCODE and version
+        self.additional.extend_from_slice(&[0, 0]);
+        self.additional.extend_from_slice(&flags.to_be_bytes());
+        // RDLENGTH
+        self.additional.extend_from_slice(&[0, 0]);
+        self.counts[Section::Additional as usize] += 1;
+        self
+    }
+
+    /// Overrides the number of records the header announces for `section`, which by default
+    /// is the number of records added to it
+    pub fn count(&mut self, section: Section, count: u16) -> &mut Self {
+        self.overridden_counts[section as usize] = Some(count);
+        self
+    }
+
+    /// Appends `bytes`, verbatim, after the last section
+    pub fn trailing(&mut self, bytes: &[u8]) -> &mut Self {
+        self.trailing.extend_from_slice(bytes);
+        self
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            HEADER_LEN + self.questions.len() + self.additional.len() + self.trailing.len(),
+        );
+
+        bytes.extend_from_slice(&self.id.to_be_bytes());
+        let flags = self.flags.encode() | u16::from(self.opcode) << 11 | u16::from(self.rcode);
+        bytes.extend_from_slice(&flags.to_be_bytes());
+        for (count, overridden) in self.counts.iter().zip(self.overridden_counts) {
+            bytes.extend_from_slice(&overridden.unwrap_or(*count).to_be_bytes());
+        }
+
+        bytes.extend_from_slice(&self.questions);
+        bytes.extend_from_slice(&self.additional);
+        bytes.extend_from_slice(&self.trailing);
+
+        bytes
+    }
+}
+
+/// The sections of a DNS message
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Section {
+    Question,
+    Answer,
+    Authority,
+    Additional,
+}
+
+/// The header of a DNS message
+#[derive(Debug)]
+pub struct Header {
+    pub id: u16,
+    pub opcode: u8,
+    pub flags: Flags,
+    pub rcode: u8,
+    pub qdcount: u16,
+    pub ancount: u16,
+    pub nscount: u16,
+    pub arcount: u16,
+}
+
+impl Header {
+    /// Parses the first 12 bytes of `message`
+    pub fn parse(message: &[u8]) -> Result<Self> {
+        if message.len() < HEADER_LEN {
+            return Err(format!(
+                "message is {} bytes long, too short to contain a header",
+                message.len()
+            )
+            .into());
+        }
+
+        let u16_at = |index: usize| u16::from_be_bytes([message[index], message[index + 1]]);
+        let flags = u16_at(2);
+
+        Ok(Self {
+            id: u16_at(0),
+            opcode: ((flags >> 11) & 0xf) as u8,
+            flags: Flags::decode(flags),
+            rcode: (flags & 0xf) as u8,
+            qdcount: u16_at(4),
+            ancount: u16_at(6),
+            nscount: u16_at(8),
+            arcount: u16_at(10),
+        })
+    }
+
+    /// The response code; the extended RCODE bits of the OPT record, if any, are not taken
+    /// into account
+    pub fn status(&self) -> Result<DigStatus> {
+        DigStatus::try_from(self.rcode)
+    }
+}
+
+/// The flags of a message header
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Flags {
+    pub qr: bool,
+    pub authoritative_answer: bool,
+    pub truncated: bool,
+    pub recursion_desired: bool,
+    pub recursion_available: bool,
+    pub authenticated_data: bool,
+    pub checking_disabled: bool,
+}
+
+impl Flags {
+    fn encode(&self) -> u16 {
+        let Self {
+            qr,
+            authoritative_answer,
+            truncated,
+            recursion_desired,
+            recursion_available,
+            authenticated_data,
+            checking_disabled,
+        } = *self;
+
+        u16::from(qr) << 15
+            | u16::from(authoritative_answer) << 10
+            | u16::from(truncated) << 9
+            | u16::from(recursion_desired) << 8
+            | u16::from(recursion_available) << 7
+            | u16::from(authenticated_data) << 5
+            | u16::from(checking_disabled) << 4
+    }
+
+    fn decode(flags: u16) -> Self {
+        let bit = |position: u16| flags & (1 << position) != 0;
+
+        Self {
+            qr: bit(15),
+            authoritative_answer: bit(10),
+            truncated: bit(9),
+            recursion_desired: bit(8),
+            recursion_available: bit(7),
+            authenticated_data: bit(5),
+            checking_disabled: bit(4),
+        }
+    }
+}
+
+/// Encodes `fqdn` as a sequence of uncompressed labels
+///
+/// Labels longer than 63 bytes are encoded as is, so their length byte may look like a
+/// compression pointer or an extended label type
+pub fn encode_name(fqdn: &FQDN) -> Vec<u8> {
+    let mut bytes = vec![];
+    for label in fqdn.as_str().split('.').filter(|label| !label.is_empty()) {
+        // truncate labels that do not fit in a length byte
+        let label = &label.as_bytes()[..label.len().min(usize::from(u8::MAX))];
+        bytes.push(label.len() as u8);
+        bytes.extend_from_slice(label);
+    }
+    bytes.push(0);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::{Client, Transport};
+    use crate::name_server::NameServer;
+    use crate::{Implementation, Network};
+
+    use super::*;
+
+    #[test]
+    fn encodes_query() -> Result<()> {
+        let mut message = Message::query(&FQDN("example.com.")?, RecordType::A);
+        message.id(0x1234).recurse().edns(1232, true);
+
+        let expected = [
+            0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 1, // header
+            7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0, 0, 1, 0,
+            1, // question
+            0, 0, 41, 0x04, 0xd0, 0, 0, 0x80, 0, 0, 0, // OPT
+        ];
+        assert_eq!(expected.to_vec(), message.encode());
+
+        Ok(())
+    }
+
+    #[test]
+    fn overridden_counts_and_opcode() -> Result<()> {
+        let mut message = Message::query(&FQDN::ROOT, RecordType::SOA);
+        message
+            .opcode(3)
+            .count(Section::Question, 2)
+            .count(Section::Answer, 7);
+
+        let header = Header::parse(&message.encode())?;
+        assert_eq!(3, header.opcode);
+        assert_eq!(2, header.qdcount);
+        assert_eq!(7, header.ancount);
+        assert_eq!(0, header.arcount);
+        assert_eq!(Flags::default(), header.flags);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parses_header() -> Result<()> {
+        let header = Header::parse(&[0xab, 0xcd, 0x84, 0x83, 0, 1, 0, 0, 0, 1, 0, 0])?;
+
+        assert_eq!(0xabcd, header.id);
+        assert!(header.flags.qr);
+        assert!(header.flags.authoritative_answer);
+        assert!(header.flags.recursion_available);
+        assert!(!header.flags.recursion_desired);
+        assert!(header.status()?.is_nxdomain());
+        assert_eq!(1, header.nscount);
+
+        assert!(Header::parse(&[0; 11]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn oversized_label() -> Result<()> {
+        let label = "a".repeat(64);
+        let name = encode_name(&FQDN(format!("{label}.com."))?);
+
+        assert_eq!(64, name[0]);
+        assert_eq!(1 + 64 + 1 + 3 + 1, name.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_queries_are_rejected() -> Result<()> {
+        let network = Network::new()?;
+        let ns = NameServer::new(&Implementation::Unbound, FQDN::ROOT, &network)?.start()?;
+        let client = Client::new(&network)?;
+        let send = |message: &Message, transport| {
+            let reply = client
+                .send_raw(ns.ipv4_addr(), transport, &message.encode())?
+                .ok_or("no reply")?;
+            Header::parse(&reply)
+        };
+
+        let query = Message::query(&FQDN::ROOT, RecordType::SOA);
+        for transport in [Transport::Udp, Transport::Tcp] {
+            let header = send(&query, transport)?;
+            assert!(header.status()?.is_noerror());
+            assert_eq!(DEFAULT_ID, header.id);
+        }
+
+        let header = send(
+            Message::query(&FQDN::ROOT, RecordType::SOA).opcode(3),
+            Transport::Udp,
+        )?;
+        assert!(header.status()?.is_notimp());
+
+        // a compression pointer that points to itself
+        let header = send(
+            Message::empty().raw_question(&[0xc0, 12], RecordType::A.code()),
+            Transport::Udp,
+        )?;
+        assert!(header.status()?.is_formerr());
+
+        // the client itself does not run a DNS server
+        let reply = client.send_raw(client.ipv4_addr(), Transport::Udp, &query.encode())?;
+        assert!(reply.is_none());
+
+        Ok(())
+    }
+}
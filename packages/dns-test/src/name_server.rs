@@ -1,12 +1,18 @@
 use core::sync::atomic::{self, AtomicUsize};
 use std::net::Ipv4Addr;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::container::{Child, Container, Network};
-use crate::implementation::{Config, Role};
+use crate::container::{readiness_timeout, Child, Container, HasContainer, Logs, Monitor, Network};
+use crate::implementation::{Config, ConfigOverrides, DnssecPolicy, LogLevel, Role, ZoneAccess};
 use crate::record::{self, Record, SoaSettings, DS, SOA};
 use crate::tshark::Tshark;
 use crate::zone_file::{self, Root, ZoneFile};
-use crate::{Implementation, Result, TrustAnchor, DEFAULT_TTL, FQDN};
+use crate::{Implementation, Result, TrustAnchor, TsigKey, DEFAULT_TTL, FQDN};
+
+use self::key_cache::{KeyCache, KeyPair, KeyRole};
+
+mod key_cache;
 
 pub struct Graph {
     pub nameservers: Vec<NameServer<Running>>,
@@ -35,32 +41,79 @@ impl Graph {
     ///
     /// a non-empty `TrustAnchor` is returned only when `Sign::Yes` or `Sign::AndAmend` is used
     pub fn build(leaf: NameServer<Stopped>, sign: Sign) -> Result<Self> {
-        assert_eq!(2, leaf.zone().num_labels(), "not yet implemented");
-        assert_eq!(Some(FQDN::COM), leaf.zone().parent(), "not yet implemented");
-
-        // first pass: create nameservers for parent zones
-        let mut zone = leaf.zone().clone();
+        let zone = leaf.zone().clone();
         let network = leaf.container.network().clone();
         let implementation = leaf.implementation.clone();
+        Self::build_graph(
+            zone,
+            &network,
+            &implementation,
+            Leaf::NameServer(Box::new(leaf)),
+            sign,
+        )
+    }
 
-        let (mut nameservers_ns, leaf) = if leaf.zone() != &FQDN::NAMESERVERS {
-            let nameservers_ns = NameServer::new(&implementation, FQDN::NAMESERVERS, &network)?;
-            (nameservers_ns, Some(leaf))
-        } else {
-            (leaf, None)
+    /// Like `build` but the leaf zone is served by `leaf`, e.g. a `RogueServer`, which is not part
+    /// of the returned name servers
+    ///
+    /// The name servers of the parent zones use `implementation`. `leaf` is not signed so when
+    /// `Sign::Yes` or `Sign::AndAmend` are used the delegation to it is insecure
+    pub fn build_above(
+        leaf: &impl ZoneServer,
+        implementation: &Implementation,
+        sign: Sign,
+    ) -> Result<Self> {
+        assert_ne!(
+            &FQDN::NAMESERVERS,
+            leaf.zone(),
+            "the leaf zone cannot be `{}`",
+            FQDN::NAMESERVERS
+        );
+
+        Self::build_graph(
+            leaf.zone().clone(),
+            leaf.network(),
+            implementation,
+            Leaf::External(leaf),
+            sign,
+        )
+    }
+
+    fn build_graph(
+        mut zone: FQDN,
+        network: &Network,
+        implementation: &Implementation,
+        leaf: Leaf,
+        sign: Sign,
+    ) -> Result<Self> {
+        assert_eq!(2, zone.num_labels(), "not yet implemented");
+        assert_eq!(Some(FQDN::COM), zone.parent(), "not yet implemented");
+
+        // first pass: create nameservers for parent zones
+        let (mut nameservers_ns, leaf, external_leaf) = match leaf {
+            Leaf::NameServer(leaf) if leaf.zone() == &FQDN::NAMESERVERS => (*leaf, None, None),
+            Leaf::NameServer(leaf) => (
+                NameServer::new(implementation, FQDN::NAMESERVERS, network)?,
+                Some(*leaf),
+                None,
+            ),
+            Leaf::External(leaf) => (
+                NameServer::new(implementation, FQDN::NAMESERVERS, network)?,
+                None,
+                Some(leaf),
+            ),
         };
 
         // the nameserver covering `FQDN::NAMESERVERS` needs A records about all the nameservers in the graph
         let mut nameservers = vec![];
         while let Some(parent) = zone.parent() {
-            let nameserver = NameServer::new(&implementation, parent.clone(), &network)?;
+            let nameserver = NameServer::new(implementation, parent.clone(), network)?;
 
             nameservers_ns.add(nameserver.a());
             nameservers.push(nameserver);
 
             zone = parent;
         }
-        drop((network, implementation));
 
         if let Some(leaf) = leaf {
             nameservers.insert(0, leaf);
@@ -82,6 +135,14 @@ impl Graph {
             }
         }
 
+        if let Some(leaf) = external_leaf {
+            let parent = nameservers
+                .iter_mut()
+                .find(|nameserver| Some(nameserver.zone()) == leaf.zone().parent().as_ref())
+                .expect("unreachable: the graph contains the parent zone");
+            parent.referral_nameserver(leaf);
+        }
+
         let root = nameservers.last().unwrap().root_hint();
 
         // start name servers
@@ -145,10 +206,33 @@ impl Graph {
     }
 }
 
+/// The server of the leaf zone of a `Graph`
+enum Leaf<'a> {
+    NameServer(Box<NameServer<Stopped>>),
+    /// served by something other than a `NameServer`
+    External(&'a dyn ZoneServer),
+}
+
+/// A server that is authoritative for a zone and that can be delegated to
+pub trait ZoneServer {
+    fn zone(&self) -> &FQDN;
+
+    /// The name of the server, i.e. the target of the zone's NS record
+    fn fqdn(&self) -> &FQDN;
+
+    fn ipv4_addr(&self) -> Ipv4Addr;
+
+    /// The network the server is attached to
+    fn network(&self) -> &Network;
+}
+
 pub struct NameServer<State> {
+    config_overrides: ConfigOverrides,
     container: Container,
     implementation: Implementation,
+    log_level: LogLevel,
     state: State,
+    access: ZoneAccess,
     zone_file: ZoneFile,
 }
 
@@ -164,10 +248,49 @@ impl NameServer<Stopped> {
     ///
     /// - one SOA record, with the primary name server field set to this name server's FQDN
     /// - one NS record, with this name server's FQDN set as the only available name server for
-    /// the zone
+    ///   the zone
     pub fn new(implementation: &Implementation, zone: FQDN, network: &Network) -> Result<Self> {
+        Self::create(implementation, zone, network, primary_ns)
+    }
+
+    /// Spins up a secondary name server that transfers, over AXFR / IXFR, the zone of `primary`
+    ///
+    /// `primary` must allow zone transfers; see `allow_transfer`. If `primary` has a TSIG key,
+    /// the secondary uses it to sign its transfer requests. The secondary will be put in the same
+    /// network as `primary`
+    ///
+    /// The FQDN of the name server will have the form `secondary{count}.nameservers.com.`. Add
+    /// its `a` record, and a NS record, to the primary's zone file to make the secondary part of
+    /// the zone's NS set. NOTE the secondary's own `zone_file` is NOT what it serves; it serves
+    /// whatever it transferred from `primary`
+    pub fn secondary<S>(implementation: &Implementation, primary: &NameServer<S>) -> Result<Self> {
+        let access = ZoneAccess {
+            primary: Some(primary.ipv4_addr()),
+            tsig_key: primary.access.tsig_key.clone(),
+            ..ZoneAccess::default()
+        };
+        // fail before a container is started
+        access.check_supported(implementation)?;
+
+        let network = primary.container.network();
+        let mut secondary = Self::create(
+            implementation,
+            primary.zone().clone(),
+            network,
+            secondary_ns,
+        )?;
+        secondary.access = access;
+        Ok(secondary)
+    }
+
+    fn create(
+        implementation: &Implementation,
+        zone: FQDN,
+        network: &Network,
+        nameserver: fn(usize, &FQDN) -> FQDN,
+    ) -> Result<Self> {
         let ns_count = ns_count();
-        let nameserver = primary_ns(ns_count, &zone);
+        let nameserver = nameserver(ns_count, &zone);
         let image = implementation.clone().into();
         let container = Container::run(&image, network)?;
 
@@ -185,8 +308,11 @@ impl NameServer<Stopped> {
         zone_file.add(Record::a(nameserver.clone(), container.ipv4_addr()));
 
         Ok(Self {
+            config_overrides: ConfigOverrides::default(),
             container,
             implementation: implementation.clone(),
+            log_level: default_log_level(implementation),
+            access: ZoneAccess::default(),
             zone_file,
             state: Stopped,
         })
@@ -198,8 +324,8 @@ impl NameServer<Stopped> {
         self
     }
 
-    /// Adds a NS + A record pair to the zone file from another NameServer
-    pub fn referral_nameserver<T>(&mut self, nameserver: &NameServer<T>) -> &mut Self {
+    /// Adds a NS + A record pair to the zone file from another zone server
+    pub fn referral_nameserver(&mut self, nameserver: &(impl ZoneServer + ?Sized)) -> &mut Self {
         self.referral(
             nameserver.zone().clone(),
             nameserver.fqdn().clone(),
@@ -213,18 +339,100 @@ impl NameServer<Stopped> {
         self
     }
 
-    /// Freezes and signs the name server's zone file
+    /// Appends `snippet` verbatim to the configuration file rendered from the built-in template
+    ///
+    /// The snippet must use the syntax of the implementation the name server runs, e.g. NSD's
+    /// for `Implementation::Unbound`; it can be used to try out options that are not modelled by
+    /// this crate. Can be called several times; snippets are appended in order
+    pub fn additional_config(&mut self, snippet: impl Into<String>) -> &mut Self {
+        self.config_overrides.snippets.push(snippet.into());
+        self
+    }
+
+    /// Replaces the built-in configuration template with `template`
+    ///
+    /// `template` is a minijinja template that is rendered with the same variables as the
+    /// built-in one (see `src/templates`), e.g. `fqdn`. Useful for changes that cannot be
+    /// appended, like options in BIND's single `options` block. `start` returns an error if
+    /// `template` cannot be rendered
+    pub fn config_template(&mut self, template: impl Into<String>) -> &mut Self {
+        self.config_overrides.template = Some(template.into());
+        self
+    }
+
+    /// Sets how verbose the name server's logs are
+    ///
+    /// Defaults to the level set through the `DNS_TEST_LOG_LEVEL` environment variable or, if
+    /// that's not set, `LogLevel::Debug`; `LogLevel::Warn` for NSD
+    pub fn log_level(&mut self, log_level: LogLevel) -> &mut Self {
+        self.log_level = log_level;
+        self
+    }
+
+    /// Allows other servers, e.g. secondaries, to transfer the zone over AXFR / IXFR
+    ///
+    /// If a TSIG key has been set with `tsig_key`, transfer requests must be signed with it
+    pub fn allow_transfer(&mut self) -> &mut Self {
+        self.access.allow_transfer = true;
+        self
+    }
+
+    /// Sets the TSIG key used to authenticate zone transfers and dynamic updates
+    ///
+    /// On a primary, this restricts transfers and updates to requests signed with `key`.
+    /// Secondaries created with `NameServer::secondary` inherit the key of their primary
+    ///
+    /// `start` returns an error if the implementation does not support TSIG, i.e. `hickory-dns`
+    pub fn tsig_key(&mut self, key: TsigKey) -> &mut Self {
+        self.access.tsig_key = Some(key);
+        self
+    }
+
+    /// Accepts dynamic updates (RFC2136) of the zone
+    ///
+    /// If a TSIG key has been set with `tsig_key`, updates must be signed with it. If the zone is
+    /// signed, the server re-signs the updated records using the keys generated by `sign`
+    ///
+    /// `start` returns an error if the implementation does not support dynamic updates; only BIND
+    /// does
+    pub fn allow_update(&mut self) -> &mut Self {
+        self.access.allow_update = true;
+        self
+    }
+
+    /// Sends NOTIFY messages to `ipv4_addr` whenever the zone changes, in addition to the servers
+    /// listed in the zone's NS records
+    ///
+    /// `start` returns an error if the implementation does not support NOTIFY, i.e. `hickory-dns`
+    pub fn also_notify(&mut self, ipv4_addr: Ipv4Addr) -> &mut Self {
+        self.access.also_notify.push(ipv4_addr);
+        self
+    }
+
+    /// Freezes and signs the name server's zone file with the default `SignSettings`
+    ///
+    /// # Panics
+    ///
+    /// this method panics if the name server is a secondary; sign its primary instead
     pub fn sign(self) -> Result<NameServer<Signed>> {
-        // TODO do we want to make these settings configurable?
-        const ZSK_BITS: usize = 1024;
-        const KSK_BITS: usize = 2048;
-        const ALGORITHM: &str = "RSASHA1-NSEC3-SHA1";
+        self.sign_with(&SignSettings::default())
+    }
+
+    /// Like `sign` but with the given algorithm, key sizes and denial of existence
+    pub fn sign_with(self, settings: &SignSettings) -> Result<NameServer<Signed>> {
+        assert!(
+            self.access.primary.is_none(),
+            "a secondary name server cannot be signed; sign its primary instead"
+        );
 
         let Self {
+            config_overrides,
             container,
             zone_file,
             implementation,
+            log_level,
             state: _,
+            access,
         } = self;
 
         container.status_ok(&["mkdir", "-p", ZONES_DIR])?;
@@ -233,24 +441,35 @@ impl NameServer<Stopped> {
 
         let zone = zone_file.origin();
 
-        let zsk_keygen =
-            format!("cd {ZONES_DIR} && ldns-keygen -a {ALGORITHM} -b {ZSK_BITS} {zone}");
-        let zsk_filename = container.stdout(&["sh", "-c", &zsk_keygen])?;
+        let key_cache = if settings.fresh_keys {
+            None
+        } else {
+            KeyCache::from_env()
+        };
+
+        let zsk_filename = keygen(&container, zone, settings, KeyRole::Zsk, key_cache.as_ref())?;
         let zsk_path = format!("{ZONES_DIR}/{zsk_filename}.key");
         let zsk: zone_file::DNSKEY = container.stdout(&["cat", &zsk_path])?.parse()?;
+        // inherit SOA's TTL value
+        let zsk = zsk.with_ttl(zone_file.soa.ttl);
 
-        let ksk_keygen =
-            format!("cd {ZONES_DIR} && ldns-keygen -k -a {ALGORITHM} -b {KSK_BITS} {zone}");
-        let ksk_filename = container.stdout(&["sh", "-c", &ksk_keygen])?;
+        let ksk_filename = keygen(&container, zone, settings, KeyRole::Ksk, key_cache.as_ref())?;
         let ksk_path = format!("{ZONES_DIR}/{ksk_filename}.key");
         let ksk: zone_file::DNSKEY = container.stdout(&["cat", &ksk_path])?.parse()?;
-
-        // -n = use NSEC3 instead of NSEC
-        // -p = set the opt-out flag on all nsec3 rrs
-        let signzone = format!(
-            "cd {ZONES_DIR} && ldns-signzone -n -p {ZONE_FILENAME} {zsk_filename} {ksk_filename}"
-        );
-        container.status_ok(&["sh", "-c", &signzone])?;
+        let ksk = ksk.with_ttl(zone_file.soa.ttl);
+
+        let key_files = KeyFiles {
+            zsk: zsk_filename,
+            ksk: ksk_filename,
+            signzone_flags: settings.signzone_flags(access.allow_update),
+            policy: DnssecPolicy {
+                algorithm: zsk.algorithm,
+                zsk_bits: settings.zsk_bits,
+                ksk_bits: settings.ksk_bits,
+                opt_out: settings.opt_out,
+            },
+        };
+        key_files.sign_zone(&container)?;
 
         // TODO do we want to make the hashing algorithm configurable?
         // -2 = use SHA256 for the DS hash
@@ -261,18 +480,19 @@ impl NameServer<Stopped> {
             .stdout(&["cat", &format!("{zone_file_path}.signed")])?
             .parse()?;
 
-        let ttl = zone_file.soa.ttl;
-
         Ok(NameServer {
+            config_overrides,
             container,
             implementation,
+            log_level,
+            access,
             zone_file,
             state: Signed {
                 ds,
                 signed,
-                // inherit SOA's TTL value
-                ksk: ksk.with_ttl(ttl),
-                zsk: zsk.with_ttl(ttl),
+                ksk,
+                zsk,
+                key_files,
             },
         })
     }
@@ -280,31 +500,58 @@ impl NameServer<Stopped> {
     /// Moves the server to the "Start" state where it can answer client queries
     pub fn start(self) -> Result<NameServer<Running>> {
         let Self {
+            config_overrides,
             container,
             zone_file,
             implementation,
+            log_level,
             state: _,
+            access,
         } = self;
 
+        access.check_supported(&implementation)?;
+
         let config = Config::NameServer {
             origin: zone_file.origin(),
+            log_level,
+            access: &access,
+            signed: None,
         };
 
         container.cp(
             implementation.conf_file_path(config.role()),
-            &implementation.format_config(config),
+            &implementation.format_config(config, &config_overrides)?,
         )?;
 
         container.status_ok(&["mkdir", "-p", ZONES_DIR])?;
-        container.cp(&zone_file_path(), &zone_file.to_string())?;
+        // secondaries get the zone from their primary
+        if access.primary.is_none() {
+            container.cp(&zone_file_path(), &zone_file.to_string())?;
+        }
 
-        let child = container.spawn(implementation.cmd_args(config.role()))?;
+        let mut child = container.spawn(&implementation.cmd_args(&config))?;
+        let logs = child.collect_logs()?;
+        container.wait_until_ready(
+            &mut child,
+            &logs,
+            implementation.readiness_probe(&config),
+            &implementation,
+        )?;
+        let monitor = container.monitor(&mut child, &implementation);
 
         Ok(NameServer {
+            config_overrides,
             container,
             implementation,
+            log_level,
+            access,
             zone_file,
-            state: Running { child },
+            state: Running {
+                child,
+                logs,
+                monitor,
+                key_files: None,
+            },
         })
     }
 }
@@ -316,6 +563,28 @@ fn zone_file_path() -> String {
     format!("{ZONES_DIR}/{ZONE_FILENAME}")
 }
 
+/// `a > b` in RFC1982 serial number arithmetic
+fn serial_gt(a: u32, b: u32) -> bool {
+    const HALF: u32 = 1 << 31;
+
+    (a > b && a - b < HALF) || (a < b && b - a > HALF)
+}
+
+/// The serial number that follows `serial`; 0 is skipped as some tools treat it as "unset"
+fn next_serial(serial: u32) -> u32 {
+    serial.wrapping_add(1).max(1)
+}
+
+/// NSD (`Implementation::Unbound`) keeps its own default verbosity, 0, unless a level is set
+/// through `DNS_TEST_LOG_LEVEL`
+fn default_log_level(implementation: &Implementation) -> LogLevel {
+    if implementation.is_unbound() {
+        LogLevel::from_env_or(LogLevel::Warn)
+    } else {
+        LogLevel::from_env()
+    }
+}
+
 fn ns_count() -> usize {
     static COUNT: AtomicUsize = AtomicUsize::new(0);
     COUNT.fetch_add(1, atomic::Ordering::Relaxed)
@@ -325,28 +594,55 @@ impl NameServer<Signed> {
     /// Moves the server to the "Start" state where it can answer client queries
     pub fn start(self) -> Result<NameServer<Running>> {
         let Self {
+            config_overrides,
             container,
             zone_file,
             implementation,
+            log_level,
             state,
+            access,
         } = self;
 
+        access.check_supported(&implementation)?;
+
         let config = Config::NameServer {
             origin: zone_file.origin(),
+            log_level,
+            access: &access,
+            signed: Some(state.key_files.policy),
         };
         container.cp(
             implementation.conf_file_path(config.role()),
-            &implementation.format_config(config),
+            &implementation.format_config(config, &config_overrides)?,
         )?;
         container.cp(&zone_file_path(), &state.signed.to_string())?;
-
-        let child = container.spawn(implementation.cmd_args(config.role()))?;
+        // BIND's inline signing, used by signed zones that accept updates, keeps its own signed
+        // copy of the zone at this path
+        container.status_ok(&["rm", "-f", &format!("{}.signed", zone_file_path())])?;
+
+        let mut child = container.spawn(&implementation.cmd_args(&config))?;
+        let logs = child.collect_logs()?;
+        container.wait_until_ready(
+            &mut child,
+            &logs,
+            implementation.readiness_probe(&config),
+            &implementation,
+        )?;
+        let monitor = container.monitor(&mut child, &implementation);
 
         Ok(NameServer {
+            config_overrides,
             container,
             implementation,
+            log_level,
+            access,
             zone_file,
-            state: Running { child },
+            state: Running {
+                child,
+                logs,
+                monitor,
+                key_files: Some(state.key_files),
+            },
         })
     }
 
@@ -377,6 +673,260 @@ impl NameServer<Running> {
         self.container.eavesdrop()
     }
 
+    /// Returns the serial number in the SOA record of the zone this server is currently serving
+    ///
+    /// The SOA record is queried from within the server's container
+    pub fn soa_serial(&self) -> Result<u32> {
+        let output = self.container.stdout(&[
+            "dig",
+            "@127.0.0.1",
+            "+norecurse",
+            "+short",
+            "SOA",
+            self.zone().as_str(),
+        ])?;
+
+        // +short format: MNAME RNAME SERIAL REFRESH RETRY EXPIRE MINIMUM
+        let serial = output.split_whitespace().nth(2).ok_or_else(|| {
+            format!(
+                "`{}` did not return a SOA record: {output}",
+                self.implementation
+            )
+        })?;
+        Ok(serial.parse()?)
+    }
+
+    /// Returns the zone this server is currently serving, which includes e.g. the changes made by
+    /// dynamic updates or, for a secondary, the data transferred from the primary
+    ///
+    /// The zone is transferred over AXFR from within the server's container
+    pub fn current_zone(&self) -> Result<ZoneFile> {
+        let output = self.container.stdout(&[
+            "dig",
+            "@127.0.0.1",
+            "+noall",
+            "+answer",
+            "AXFR",
+            self.zone().as_str(),
+        ])?;
+
+        ZoneFile::parse_axfr(&output).map_err(|e| {
+            format!(
+                "could not transfer the zone from `{}`: {e}",
+                self.implementation
+            )
+            .into()
+        })
+    }
+
+    /// Blocks until the server serves the zone with a SOA serial number of at least `serial`,
+    /// where serial numbers are compared using RFC1982 serial number arithmetic
+    ///
+    /// Useful to wait for a secondary to complete a zone transfer. Returns an error if that does
+    /// not happen within `timeout`
+    pub fn wait_for_serial(&self, serial: u32, timeout: Duration) -> Result<()> {
+        const POLL_PERIOD: Duration = Duration::from_millis(100);
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let current = self.soa_serial().ok();
+            if current.is_some_and(|current| current == serial || serial_gt(current, serial)) {
+                return Ok(());
+            }
+
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "`{}` did not serve serial {serial} within {timeout:?}; current serial: {current:?}",
+                    self.implementation
+                )
+                .into());
+            }
+
+            thread::sleep(POLL_PERIOD);
+        }
+    }
+
+    /// Sends a NOTIFY message for this server's zone to the name server at `ipv4_addr`, e.g. a
+    /// secondary, which should then check whether it needs to transfer the zone again
+    ///
+    /// The message is sent from this server's container so it has this server's address as its
+    /// source address
+    pub fn notify(&self, ipv4_addr: Ipv4Addr) -> Result<()> {
+        let serial = self.soa_serial()?.to_string();
+        self.container.status_ok(&[
+            "ldns-notify",
+            "-z",
+            self.zone().as_str(),
+            "-s",
+            &serial,
+            &ipv4_addr.to_string(),
+        ])
+    }
+
+    /// Replaces the zone this server serves with `zone_file` and reloads the server
+    ///
+    /// The zone file must have the same origin as the current zone. If its SOA serial number is
+    /// not greater than the current one, in RFC1982 serial number arithmetic, it is set to the
+    /// current serial number plus one so that secondaries and caches notice the change. If the
+    /// server was started from a signed zone, the new zone is re-signed with the same keys;
+    /// amendments made through `Sign::AndAmend` are NOT re-applied
+    ///
+    /// This method blocks until the server serves the new serial number. Do not combine it with
+    /// dynamic updates: changes made through those are lost
+    ///
+    /// BIND and NSD reload their zone files in place. `hickory-dns` cannot reload its zone files so
+    /// it is restarted instead: it gets a new process, and new logs, and it does not answer
+    /// queries while it restarts
+    ///
+    /// # Panics
+    ///
+    /// this method panics if the server is a secondary; change its primary instead
+    pub fn replace_zone(&mut self, zone_file: ZoneFile) -> Result<()> {
+        assert_eq!(
+            self.zone(),
+            zone_file.origin(),
+            "the replacement zone must have the same origin as the current zone"
+        );
+        self.assert_not_secondary();
+
+        let current_serial = self.zone_file.soa.settings.serial;
+        self.zone_file = zone_file;
+        let serial = &mut self.zone_file.soa.settings.serial;
+        if !serial_gt(*serial, current_serial) {
+            *serial = next_serial(current_serial);
+        }
+
+        self.reload()
+    }
+
+    /// Adds `record` to the zone this server serves, bumps the SOA serial number and reloads the
+    /// server
+    ///
+    /// See `replace_zone` for details
+    pub fn add_record(&mut self, record: impl Into<Record>) -> Result<()> {
+        self.assert_not_secondary();
+        self.zone_file.add(record);
+        self.bump_serial();
+        self.reload()
+    }
+
+    /// Removes all the records that are equal to `record` from the zone this server serves, bumps
+    /// the SOA serial number and reloads the server
+    ///
+    /// Returns an error if the zone contains no such record. See `replace_zone` for details
+    pub fn remove_record(&mut self, record: impl Into<Record>) -> Result<()> {
+        self.assert_not_secondary();
+        let record = record.into().to_string();
+        let records = &mut self.zone_file.records;
+        let count = records.len();
+        records.retain(|candidate| candidate.to_string() != record);
+
+        if records.len() == count {
+            return Err(format!("record `{record}` is not part of the zone").into());
+        }
+
+        self.bump_serial();
+        self.reload()
+    }
+
+    fn assert_not_secondary(&self) {
+        assert!(
+            self.access.primary.is_none(),
+            "a secondary name server gets its zone from its primary; change the primary instead"
+        );
+    }
+
+    fn bump_serial(&mut self) {
+        let serial = &mut self.zone_file.soa.settings.serial;
+        *serial = next_serial(*serial);
+    }
+
+    /// Rewrites the zone file in the container, re-signing it if needed, and makes the server
+    /// load it
+    fn reload(&mut self) -> Result<()> {
+        let container = &self.container;
+        let zone_file_path = zone_file_path();
+        container.cp(&zone_file_path, &self.zone_file.to_string())?;
+        if let Some(key_files) = &self.state.key_files {
+            key_files.sign_zone(container)?;
+            container.status_ok(&["mv", &format!("{zone_file_path}.signed"), &zone_file_path])?;
+        }
+
+        let pidfile = self.implementation.pidfile(Role::NameServer);
+        match self.implementation {
+            // both reload their zone files on SIGHUP
+            Implementation::Bind | Implementation::Unbound => {
+                container.status_ok(&["sh", "-c", &format!("kill -HUP $(cat {pidfile})")])?;
+            }
+
+            // the hickory-dns binary does not do signal handling so restart it instead
+            Implementation::Hickory(_) => {
+                let kill = format!(
+                    "pid=$(cat {pidfile})
+kill -TERM $pid
+while kill -0 $pid 2>/dev/null; do sleep 0.1; done"
+                );
+                container.status_ok(&["sh", "-c", &kill])?;
+                // reap the old process right away, it was terminated on purpose, so that a
+                // failed relaunch is not reported as a crash
+                self.state.child.reap()?;
+
+                let config = Config::NameServer {
+                    origin: self.zone_file.origin(),
+                    log_level: self.log_level,
+                    access: &self.access,
+                    signed: self
+                        .state
+                        .key_files
+                        .as_ref()
+                        .map(|key_files| key_files.policy),
+                };
+                let mut child = container.spawn(&self.implementation.cmd_args(&config))?;
+                let logs = child.collect_logs()?;
+                container.wait_until_ready(
+                    &mut child,
+                    &logs,
+                    self.implementation.readiness_probe(&config),
+                    &self.implementation,
+                )?;
+                let monitor = container.monitor(&mut child, &self.implementation);
+
+                self.state.child = child;
+                self.state.logs = logs;
+                self.state.monitor = monitor;
+            }
+        }
+
+        self.wait_for_serial(self.zone_file.soa.settings.serial, readiness_timeout())
+    }
+
+    /// Returns the logs the name server has produced so far
+    ///
+    /// Unlike `terminate`, this method does not stop the name server
+    pub fn logs(&self) -> String {
+        self.state.logs.contents()
+    }
+
+    /// Returns the last `n` lines the name server has logged so far
+    pub fn tail_logs(&self, n: usize) -> String {
+        self.state.logs.tail(n)
+    }
+
+    /// Blocks until the name server logs a line that contains `pattern` and returns that line
+    ///
+    /// Lines logged before this method was called are also considered. Returns an error, which
+    /// includes all the logs, if no such line is logged within `timeout`
+    pub fn wait_for_log(&self, pattern: &str, timeout: Duration) -> Result<String> {
+        self.state
+            .logs
+            .expect(pattern, timeout, &self.implementation)
+    }
+
+    /// Returns `false` if the name server process has exited, e.g. because it crashed
+    pub fn is_alive(&self) -> bool {
+        self.state.monitor.is_alive()
+    }
+
     /// gracefully terminates the name server collecting all logs
     pub fn terminate(self) -> Result<String> {
         let pidfile = self.implementation.pidfile(Role::NameServer);
@@ -440,6 +990,30 @@ impl<S> NameServer<S> {
     }
 }
 
+impl<S> ZoneServer for NameServer<S> {
+    fn zone(&self) -> &FQDN {
+        self.zone()
+    }
+
+    fn fqdn(&self) -> &FQDN {
+        self.fqdn()
+    }
+
+    fn ipv4_addr(&self) -> Ipv4Addr {
+        self.ipv4_addr()
+    }
+
+    fn network(&self) -> &Network {
+        self.container.network()
+    }
+}
+
+impl<S> HasContainer for NameServer<S> {
+    fn container(&self) -> &Container {
+        &self.container
+    }
+}
+
 pub struct Stopped;
 
 pub struct Signed {
@@ -447,16 +1021,173 @@ pub struct Signed {
     zsk: record::DNSKEY,
     ksk: record::DNSKEY,
     signed: ZoneFile,
+    key_files: KeyFiles,
 }
 
 pub struct Running {
     child: Child,
+    logs: Logs,
+    monitor: Monitor,
+    /// `Some` if the zone is signed
+    key_files: Option<KeyFiles>,
+}
+
+/// Names of the key files, in `ZONES_DIR`, that were used to sign the zone
+struct KeyFiles {
+    zsk: String,
+    ksk: String,
+    signzone_flags: &'static str,
+    policy: DnssecPolicy,
+}
+
+impl KeyFiles {
+    /// Signs the zone file at `zone_file_path` producing `{zone_file_path}.signed`
+    fn sign_zone(&self, container: &Container) -> Result<()> {
+        let Self {
+            zsk,
+            ksk,
+            signzone_flags,
+            policy: _,
+        } = self;
+        let signzone =
+            format!("cd {ZONES_DIR} && ldns-signzone {signzone_flags} {ZONE_FILENAME} {zsk} {ksk}");
+        container.status_ok(&["sh", "-c", &signzone])
+    }
+}
+
+/// Generates a key pair for `zone` in `ZONES_DIR` and returns the file name of its files,
+/// without extension
+///
+/// The key pair is taken from, or added to, `key_cache` if there's one
+fn keygen(
+    container: &Container,
+    zone: &FQDN,
+    settings: &SignSettings,
+    role: KeyRole,
+    key_cache: Option<&KeyCache>,
+) -> Result<String> {
+    let algorithm = &settings.algorithm;
+    let bits = match role {
+        KeyRole::Zsk => settings.zsk_bits,
+        KeyRole::Ksk => settings.ksk_bits,
+    };
+
+    if let Some(key_cache) = key_cache {
+        if let Some(key_pair) = key_cache.get(zone, algorithm, bits, role)? {
+            let path = format!("{ZONES_DIR}/{}", key_pair.basename);
+            container.cp(&format!("{path}.key"), &key_pair.public)?;
+            container.cp(&format!("{path}.private"), &key_pair.private)?;
+            return Ok(key_pair.basename);
+        }
+    }
+
+    let ksk_flag = match role {
+        KeyRole::Zsk => "",
+        KeyRole::Ksk => "-k ",
+    };
+    let keygen = format!("cd {ZONES_DIR} && ldns-keygen {ksk_flag}-a {algorithm} -b {bits} {zone}");
+    let basename = container.stdout(&["sh", "-c", &keygen])?;
+
+    if let Some(key_cache) = key_cache {
+        let path = format!("{ZONES_DIR}/{basename}");
+        let key_pair = KeyPair {
+            public: container.stdout(&["cat", &format!("{path}.key")])?,
+            private: container.stdout(&["cat", &format!("{path}.private")])?,
+            basename: basename.clone(),
+        };
+        key_cache.insert(zone, algorithm, bits, role, &key_pair)?;
+    }
+
+    Ok(basename)
+}
+
+/// How `NameServer::sign_with` signs a zone
+///
+/// Denial of existence always uses NSEC3. The default is what `NameServer::sign` uses:
+/// RSASHA1-NSEC3-SHA1 with a 1024-bit ZSK and a 2048-bit KSK, and the opt-out flag set on all
+/// NSEC3 records. NSEC3 records are hashed with 1 additional iteration, or none if the name
+/// server `allow_update`s, to match the policy BIND re-signs updated zones with
+///
+/// When the `DNS_TEST_KEY_CACHE` environment variable is set, key pairs are reused across
+/// name servers, tests and runs: there's one key pair per zone, algorithm, key size and role
+/// (ZSK or KSK). Use `fresh_keys` to always generate new ones
+#[derive(Clone, Debug)]
+pub struct SignSettings {
+    algorithm: String,
+    zsk_bits: usize,
+    ksk_bits: usize,
+    opt_out: bool,
+    fresh_keys: bool,
+}
+
+impl Default for SignSettings {
+    fn default() -> Self {
+        Self {
+            algorithm: "RSASHA1-NSEC3-SHA1".to_string(),
+            zsk_bits: 1024,
+            ksk_bits: 2048,
+            opt_out: true,
+            fresh_keys: false,
+        }
+    }
+}
+
+impl SignSettings {
+    /// Sets the algorithm of both keys, as named by `ldns-keygen -a list`, e.g. `RSASHA256` or
+    /// `ECDSAP256SHA256`
+    pub fn algorithm(&mut self, algorithm: impl Into<String>) -> &mut Self {
+        self.algorithm = algorithm.into();
+        self
+    }
+
+    /// Sets the size of the ZSK; ignored by algorithms with a fixed key size
+    pub fn zsk_bits(&mut self, bits: usize) -> &mut Self {
+        self.zsk_bits = bits;
+        self
+    }
+
+    /// Sets the size of the KSK; ignored by algorithms with a fixed key size
+    pub fn ksk_bits(&mut self, bits: usize) -> &mut Self {
+        self.ksk_bits = bits;
+        self
+    }
+
+    /// Whether the NSEC3 records have the opt-out flag set
+    pub fn opt_out(&mut self, opt_out: bool) -> &mut Self {
+        self.opt_out = opt_out;
+        self
+    }
+
+    /// Whether to generate new key pairs even if the key cache is enabled; needed by tests that
+    /// sign the same zone more than once and expect different keys
+    pub fn fresh_keys(&mut self, fresh_keys: bool) -> &mut Self {
+        self.fresh_keys = fresh_keys;
+        self
+    }
+
+    fn signzone_flags(&self, allow_update: bool) -> &'static str {
+        // -n = use NSEC3 instead of NSEC
+        // -t 0 = no additional NSEC3 hash iterations, as in the `dnssec-policy` BIND re-signs
+        //   updated zones with (see `named.name-server.conf.jinja`); other zones keep the ldns
+        //   default of 1 iteration
+        // -p = set the opt-out flag on all nsec3 rrs
+        match (self.opt_out, allow_update) {
+            (true, true) => "-n -t 0 -p",
+            (true, false) => "-n -p",
+            (false, true) => "-n -t 0",
+            (false, false) => "-n",
+        }
+    }
 }
 
 fn primary_ns(ns_count: usize, zone: &FQDN) -> FQDN {
     FQDN(format!("primary{ns_count}.{}", expand_zone(zone))).unwrap()
 }
 
+fn secondary_ns(ns_count: usize, zone: &FQDN) -> FQDN {
+    FQDN(format!("secondary{ns_count}.{}", expand_zone(zone))).unwrap()
+}
+
 fn admin_ns(ns_count: usize, zone: &FQDN) -> FQDN {
     FQDN(format!("admin{ns_count}.{}", expand_zone(zone))).unwrap()
 }
@@ -473,15 +1204,26 @@ fn expand_zone(zone: &FQDN) -> String {
 
 #[cfg(test)]
 mod tests {
-    use std::thread;
-    use std::time::Duration;
-
-    use crate::client::{Client, DigSettings};
+    use crate::client::{Client, DigSettings, Update};
     use crate::record::RecordType;
     use crate::Repository;
 
     use super::*;
 
+    #[test]
+    fn serial_number_arithmetic() {
+        assert!(serial_gt(2, 1));
+        assert!(!serial_gt(1, 1));
+        assert!(!serial_gt(1, 2));
+        // wraps around
+        assert!(serial_gt(1, u32::MAX));
+        assert!(!serial_gt(u32::MAX, 1));
+
+        assert_eq!(2, next_serial(1));
+        assert_eq!(1, next_serial(u32::MAX));
+        assert!(serial_gt(next_serial(u32::MAX), u32::MAX));
+    }
+
     #[test]
     fn simplest() -> Result<()> {
         let network = Network::new()?;
@@ -589,10 +1331,6 @@ mod tests {
         )?
         .start()?;
 
-        // hickory-dns does not do signal handling so we need to wait until it prints something to
-        // the console
-        thread::sleep(Duration::from_millis(500));
-
         let logs = ns.terminate()?;
 
         eprintln!("{logs}");
@@ -606,4 +1344,176 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn secondary_transfers_zone() -> Result<()> {
+        let network = Network::new()?;
+        let key = TsigKey::generate(FQDN("transfer-key.")?)?;
+
+        let mut primary = NameServer::new(&Implementation::Unbound, FQDN::COM, &network)?;
+        primary.allow_transfer().tsig_key(key.clone());
+        let secondary = NameServer::secondary(&Implementation::Bind, &primary)?;
+        let primary = primary.start()?;
+        let secondary = secondary.start()?;
+
+        let serial = primary.soa_serial()?;
+        secondary.wait_for_serial(serial, Duration::from_secs(5))?;
+
+        let client = Client::new(&network)?;
+        let zone = client.axfr(primary.ipv4_addr(), &FQDN::COM, Some(&key))?;
+        assert_eq!(serial, zone.soa.settings.serial);
+        assert_eq!(primary.zone_file().records.len(), zone.records.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn dynamic_update_works() -> Result<()> {
+        let network = Network::new()?;
+        let key = TsigKey::generate(FQDN("update-key.")?)?;
+        let needle_fqdn = FQDN("example.com.")?;
+
+        let mut ns = NameServer::new(&Implementation::Bind, FQDN::COM, &network)?;
+        ns.allow_update().tsig_key(key.clone());
+        let ns = ns.start()?;
+        let serial = ns.soa_serial()?;
+
+        let client = Client::new(&network)?;
+        let mut update = Update::new(FQDN::COM);
+        update
+            .require_no_rrset(&needle_fqdn, RecordType::A)
+            .add(Record::a(needle_fqdn.clone(), Ipv4Addr::new(1, 2, 3, 4)));
+
+        // unsigned updates are refused
+        let status = client.update(ns.ipv4_addr(), &update, None)?;
+        assert!(status.is_refused());
+
+        let status = client.update(ns.ipv4_addr(), &update, Some(&key))?;
+        assert!(status.is_noerror());
+
+        // the prerequisite no longer holds
+        let status = client.update(ns.ipv4_addr(), &update, Some(&key))?;
+        assert!(status.is_yxrrset());
+
+        assert!(serial_gt(ns.soa_serial()?, serial));
+        let zone = ns.current_zone()?;
+        assert!(zone
+            .records
+            .iter()
+            .any(|record| matches!(record, Record::A(a) if a.fqdn == needle_fqdn)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn signed_dynamic_update_works() -> Result<()> {
+        let network = Network::new()?;
+        let key = TsigKey::generate(FQDN("update-key.")?)?;
+        let needle_fqdn = FQDN("example.com.")?;
+
+        let mut ns = NameServer::new(&Implementation::Bind, FQDN::COM, &network)?;
+        ns.allow_update().tsig_key(key.clone());
+        let ns = ns.sign()?.start()?;
+        let serial = ns.soa_serial()?;
+
+        let client = Client::new(&network)?;
+        let mut update = Update::new(FQDN::COM);
+        update.add(Record::a(needle_fqdn.clone(), Ipv4Addr::new(1, 2, 3, 4)));
+        let status = client.update(ns.ipv4_addr(), &update, Some(&key))?;
+        assert!(status.is_noerror());
+
+        // BIND signs the updated zone in the background
+        ns.wait_for_serial(next_serial(serial), Duration::from_secs(10))?;
+        assert!(serial_gt(ns.soa_serial()?, serial));
+
+        let zone = ns.current_zone()?;
+        assert!(zone
+            .records
+            .iter()
+            .any(|record| matches!(record, Record::A(a) if a.fqdn == needle_fqdn)));
+        assert!(zone.records.iter().any(|record| matches!(
+            record,
+            Record::RRSIG(rrsig) if rrsig.fqdn == needle_fqdn && rrsig.type_covered == RecordType::A
+        )));
+
+        Ok(())
+    }
+
+    #[test]
+    fn updatable_signed_zone_keeps_its_nsec3_parameters() -> Result<()> {
+        let network = Network::new()?;
+        let key = TsigKey::generate(FQDN("update-key.")?)?;
+
+        let mut ns = NameServer::new(&Implementation::Bind, FQDN::COM, &network)?;
+        ns.allow_update().tsig_key(key.clone());
+        let ns = ns.sign()?;
+        let expected = ns
+            .signed_zone_file()
+            .records
+            .iter()
+            .find_map(|record| match record {
+                Record::NSEC3PARAM(nsec3param) => Some(nsec3param.clone()),
+                _ => None,
+            })
+            .expect("NSEC3PARAM not found in the signed zone file");
+        let ns = ns.start()?;
+
+        // once an update has been applied BIND has applied its `dnssec-policy` to the zone
+        let mut update = Update::new(FQDN::COM);
+        update.add(Record::a(FQDN("example.com.")?, Ipv4Addr::new(1, 2, 3, 4)));
+        let client = Client::new(&network)?;
+        let status = client.update(ns.ipv4_addr(), &update, Some(&key))?;
+        assert!(status.is_noerror());
+
+        let output = client.dig(
+            DigSettings::default(),
+            ns.ipv4_addr(),
+            RecordType::NSEC3PARAM,
+            &FQDN::COM,
+        )?;
+        let [Record::NSEC3PARAM(served)] = output.answer.as_slice() else {
+            panic!(
+                "expected a single NSEC3PARAM record; got {:?}",
+                output.answer
+            )
+        };
+        assert_eq!(expected.hash_alg, served.hash_alg);
+        assert_eq!(expected.flags, served.flags);
+        assert_eq!(expected.iterations, served.iterations);
+
+        Ok(())
+    }
+
+    #[test]
+    fn hot_zone_changes_are_served() -> Result<()> {
+        let network = Network::new()?;
+        let needle_fqdn = FQDN("example.nameservers.com.")?;
+        let record = Record::a(needle_fqdn.clone(), Ipv4Addr::new(1, 2, 3, 4));
+
+        let mut ns = NameServer::new(&Implementation::Unbound, FQDN::ROOT, &network)?.start()?;
+        let serial = ns.soa_serial()?;
+
+        let client = Client::new(&network)?;
+        let dig = |ns: &NameServer<Running>| {
+            client.dig(
+                DigSettings::default(),
+                ns.ipv4_addr(),
+                RecordType::A,
+                &needle_fqdn,
+            )
+        };
+
+        assert!(dig(&ns)?.status.is_nxdomain());
+
+        ns.add_record(record)?;
+        assert!(ns.soa_serial()? > serial);
+        let output = dig(&ns)?;
+        assert!(output.status.is_noerror());
+        assert_eq!(1, output.answer.len());
+
+        ns.remove_record(Record::a(needle_fqdn.clone(), Ipv4Addr::new(1, 2, 3, 4)))?;
+        assert!(dig(&ns)?.status.is_nxdomain());
+
+        Ok(())
+    }
 }
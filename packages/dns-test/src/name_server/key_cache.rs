@@ -0,0 +1,233 @@
+//! On-disk cache of the DNSSEC key pairs generated by `ldns-keygen`
+//!
+//! Each entry holds the `.key` and `.private` files of one key pair and is keyed by zone,
+//! algorithm, key size and role (ZSK or KSK). Entries are published with a rename so concurrent
+//! test threads and processes never observe a partially written entry; when two of them generate
+//! the same key pair at the same time the first one to publish it wins
+
+use core::sync::atomic::{self, AtomicUsize};
+use std::path::PathBuf;
+use std::{env, fs, io, process};
+
+use crate::{Result, FQDN};
+
+const ENV_VAR: &str = "DNS_TEST_KEY_CACHE";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum KeyRole {
+    Zsk,
+    Ksk,
+}
+
+impl KeyRole {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Zsk => "zsk",
+            Self::Ksk => "ksk",
+        }
+    }
+}
+
+/// The `ldns-keygen` output for a key
+#[derive(Debug, PartialEq)]
+pub(super) struct KeyPair {
+    /// The file name of the key, without extension, e.g. `Kexample.com.+007+12345`
+    pub(super) basename: String,
+    /// Contents of the `.key` file
+    pub(super) public: String,
+    /// Contents of the `.private` file
+    pub(super) private: String,
+}
+
+pub(super) struct KeyCache {
+    dir: PathBuf,
+}
+
+impl KeyCache {
+    /// Returns the cache if it has been enabled through the `DNS_TEST_KEY_CACHE` environment
+    /// variable
+    ///
+    /// The variable holds the cache directory; if it's empty, `dns-test-keys` in the temporary
+    /// directory is used
+    pub(super) fn from_env() -> Option<Self> {
+        let dir = env::var_os(ENV_VAR)?;
+        let dir = if dir.is_empty() {
+            env::temp_dir().join("dns-test-keys")
+        } else {
+            PathBuf::from(dir)
+        };
+
+        Some(Self::new(dir))
+    }
+
+    pub(super) fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub(super) fn get(
+        &self,
+        zone: &FQDN,
+        algorithm: &str,
+        bits: usize,
+        role: KeyRole,
+    ) -> Result<Option<KeyPair>> {
+        let entry = self.entry(zone, algorithm, bits, role);
+        let entries = match fs::read_dir(&entry) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        for dir_entry in entries {
+            let path = dir_entry?.path();
+            if path.extension().is_some_and(|extension| extension == "key") {
+                let basename = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .ok_or_else(|| format!("invalid key file name: {}", path.display()))?
+                    .to_string();
+
+                return Ok(Some(KeyPair {
+                    public: fs::read_to_string(&path)?,
+                    private: fs::read_to_string(path.with_extension("private"))?,
+                    basename,
+                }));
+            }
+        }
+
+        Err(format!("{} contains no `.key` file", entry.display()).into())
+    }
+
+    /// Stores `key_pair` unless the cache already holds a key pair for the same zone, algorithm,
+    /// key size and role
+    pub(super) fn insert(
+        &self,
+        zone: &FQDN,
+        algorithm: &str,
+        bits: usize,
+        role: KeyRole,
+        key_pair: &KeyPair,
+    ) -> Result<()> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        fs::create_dir_all(&self.dir)?;
+
+        let count = COUNT.fetch_add(1, atomic::Ordering::Relaxed);
+        let staging = self.dir.join(format!(".staging-{}-{count}", process::id()));
+        fs::create_dir_all(&staging)?;
+
+        let KeyPair {
+            basename,
+            public,
+            private,
+        } = key_pair;
+        fs::write(staging.join(format!("{basename}.key")), public)?;
+        fs::write(staging.join(format!("{basename}.private")), private)?;
+
+        let entry = self.entry(zone, algorithm, bits, role);
+        if fs::rename(&staging, &entry).is_err() {
+            // another thread or process published the entry first
+            fs::remove_dir_all(&staging)?;
+            if !entry.exists() {
+                return Err(format!("could not store key pair in {}", entry.display()).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn entry(&self, zone: &FQDN, algorithm: &str, bits: usize, role: KeyRole) -> PathBuf {
+        self.dir.join(entry_name(zone, algorithm, bits, role))
+    }
+}
+
+fn entry_name(zone: &FQDN, algorithm: &str, bits: usize, role: KeyRole) -> String {
+    let zone = if zone == &FQDN::ROOT {
+        "root"
+    } else {
+        zone.as_str().trim_end_matches('.')
+    };
+
+    format!("{zone}_{algorithm}_{bits}_{}", role.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn key_pair(basename: &str) -> KeyPair {
+        KeyPair {
+            basename: basename.to_string(),
+            public: format!("{basename} public"),
+            private: format!("{basename} private"),
+        }
+    }
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let dir = TempDir::new()?;
+        let cache = KeyCache::new(dir.path());
+        let zone = FQDN::COM;
+
+        assert_eq!(None, cache.get(&zone, "RSASHA256", 1024, KeyRole::Zsk)?);
+
+        let zsk = key_pair("Kcom.+008+12345");
+        cache.insert(&zone, "RSASHA256", 1024, KeyRole::Zsk, &zsk)?;
+
+        assert_eq!(
+            Some(zsk),
+            cache.get(&zone, "RSASHA256", 1024, KeyRole::Zsk)?
+        );
+        assert_eq!(None, cache.get(&zone, "RSASHA256", 1024, KeyRole::Ksk)?);
+        assert_eq!(None, cache.get(&zone, "RSASHA256", 2048, KeyRole::Zsk)?);
+        assert_eq!(
+            None,
+            cache.get(&zone, "ECDSAP256SHA256", 1024, KeyRole::Zsk)?
+        );
+        assert_eq!(
+            None,
+            cache.get(&FQDN::ROOT, "RSASHA256", 1024, KeyRole::Zsk)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn first_insert_wins() -> Result<()> {
+        let dir = TempDir::new()?;
+        let cache = KeyCache::new(dir.path());
+
+        let first = key_pair("K.+008+1");
+        cache.insert(&FQDN::ROOT, "RSASHA256", 2048, KeyRole::Ksk, &first)?;
+        cache.insert(
+            &FQDN::ROOT,
+            "RSASHA256",
+            2048,
+            KeyRole::Ksk,
+            &key_pair("K.+008+2"),
+        )?;
+
+        assert_eq!(
+            Some(first),
+            cache.get(&FQDN::ROOT, "RSASHA256", 2048, KeyRole::Ksk)?
+        );
+        // no staging directories are left behind
+        assert_eq!(1, fs::read_dir(&cache.dir)?.count());
+
+        Ok(())
+    }
+
+    #[test]
+    fn entry_names() {
+        assert_eq!(
+            "root_RSASHA1-NSEC3-SHA1_2048_ksk",
+            entry_name(&FQDN::ROOT, "RSASHA1-NSEC3-SHA1", 2048, KeyRole::Ksk)
+        );
+        assert_eq!(
+            "nameservers.com_RSASHA256_1024_zsk",
+            entry_name(&FQDN::NAMESERVERS, "RSASHA256", 1024, KeyRole::Zsk)
+        );
+    }
+}
@@ -14,7 +14,7 @@ const CLASS: &str = "IN"; // "internet"
 macro_rules! record_types {
     ($($variant:ident),*) => {
         #[allow(clippy::upper_case_acronyms)]
-        #[derive(Debug, PartialEq)]
+        #[derive(Clone, Copy, Debug, PartialEq)]
         pub enum RecordType {
             $($variant),*
         }
@@ -49,7 +49,27 @@ macro_rules! record_types {
 
 record_types!(A, AAAA, DNSKEY, DS, MX, NS, NSEC3, NSEC3PARAM, RRSIG, SOA, TXT);
 
-#[derive(Debug)]
+impl RecordType {
+    /// Returns the TYPE value used on the wire
+    #[must_use]
+    pub fn code(&self) -> u16 {
+        match self {
+            Self::A => 1,
+            Self::AAAA => 28,
+            Self::DNSKEY => 48,
+            Self::DS => 43,
+            Self::MX => 15,
+            Self::NS => 2,
+            Self::NSEC3 => 50,
+            Self::NSEC3PARAM => 51,
+            Self::RRSIG => 46,
+            Self::SOA => 6,
+            Self::TXT => 16,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 #[allow(clippy::upper_case_acronyms)]
 pub enum Record {
     A(A),
@@ -150,6 +170,107 @@ impl Record {
             Err(self)
         }
     }
+
+    pub fn record_type(&self) -> RecordType {
+        match self {
+            Self::A(_) => RecordType::A,
+            Self::DNSKEY(_) => RecordType::DNSKEY,
+            Self::DS(_) => RecordType::DS,
+            Self::NS(_) => RecordType::NS,
+            Self::NSEC3(_) => RecordType::NSEC3,
+            Self::NSEC3PARAM(_) => RecordType::NSEC3PARAM,
+            Self::RRSIG(_) => RecordType::RRSIG,
+            Self::SOA(_) => RecordType::SOA,
+        }
+    }
+
+    /// The name that owns the record
+    pub fn owner(&self) -> &FQDN {
+        match self {
+            Self::A(a) => &a.fqdn,
+            Self::DNSKEY(dnskey) => &dnskey.zone,
+            Self::DS(ds) => &ds.zone,
+            Self::NS(ns) => &ns.zone,
+            Self::NSEC3(nsec3) => &nsec3.fqdn,
+            Self::NSEC3PARAM(nsec3param) => &nsec3param.zone,
+            Self::RRSIG(rrsig) => &rrsig.fqdn,
+            Self::SOA(soa) => &soa.zone,
+        }
+    }
+
+    pub fn owner_mut(&mut self) -> &mut FQDN {
+        match self {
+            Self::A(a) => &mut a.fqdn,
+            Self::DNSKEY(dnskey) => &mut dnskey.zone,
+            Self::DS(ds) => &mut ds.zone,
+            Self::NS(ns) => &mut ns.zone,
+            Self::NSEC3(nsec3) => &mut nsec3.fqdn,
+            Self::NSEC3PARAM(nsec3param) => &mut nsec3param.zone,
+            Self::RRSIG(rrsig) => &mut rrsig.fqdn,
+            Self::SOA(soa) => &mut soa.zone,
+        }
+    }
+
+    pub fn ttl(&self) -> u32 {
+        match self {
+            Self::A(a) => a.ttl,
+            Self::DNSKEY(dnskey) => dnskey.ttl,
+            Self::DS(ds) => ds.ttl,
+            Self::NS(ns) => ns.ttl,
+            Self::NSEC3(nsec3) => nsec3.ttl,
+            Self::NSEC3PARAM(nsec3param) => nsec3param.ttl,
+            Self::RRSIG(rrsig) => rrsig.ttl,
+            Self::SOA(soa) => soa.ttl,
+        }
+    }
+
+    pub fn ttl_mut(&mut self) -> &mut u32 {
+        match self {
+            Self::A(a) => &mut a.ttl,
+            Self::DNSKEY(dnskey) => &mut dnskey.ttl,
+            Self::DS(ds) => &mut ds.ttl,
+            Self::NS(ns) => &mut ns.ttl,
+            Self::NSEC3(nsec3) => &mut nsec3.ttl,
+            Self::NSEC3PARAM(nsec3param) => &mut nsec3param.ttl,
+            Self::RRSIG(rrsig) => &mut rrsig.ttl,
+            Self::SOA(soa) => &mut soa.ttl,
+        }
+    }
+}
+
+/// Record types that `Record` can represent
+const MODELLED_TYPES: &[&str] = &[
+    "A",
+    "DNSKEY",
+    "DS",
+    "NS",
+    "NSEC3",
+    "NSEC3PARAM",
+    "RRSIG",
+    "SOA",
+];
+
+impl Record {
+    /// Returns `false` if `Record` cannot represent `input`, a record in presentation format,
+    /// because of its type or, for RRSIG and NSEC3 records, the types they refer to, e.g. the
+    /// TYPE65534 records BIND uses to track the signing state of a zone
+    pub(crate) fn is_modelled(input: &str) -> bool {
+        let columns = input.split_whitespace().collect::<Vec<_>>();
+        let Some(record_type) = columns.get(3) else {
+            return false;
+        };
+
+        let referred_types = match *record_type {
+            "RRSIG" => columns.get(4..5).unwrap_or_default(),
+            "NSEC3" => columns.get(9..).unwrap_or_default(),
+            _ => &[],
+        };
+
+        MODELLED_TYPES.contains(record_type)
+            && referred_types
+                .iter()
+                .all(|referred| referred.parse::<RecordType>().is_ok())
+    }
 }
 
 impl FromStr for Record {
@@ -192,7 +313,7 @@ impl fmt::Display for Record {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct A {
     pub fqdn: FQDN,
     pub ttl: u32,
@@ -395,7 +516,7 @@ impl fmt::Display for DS {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct NS {
     pub zone: FQDN,
     pub ttl: u32,
@@ -439,7 +560,7 @@ impl FromStr for NS {
 }
 
 // integer types chosen based on bit sizes in section 3.2 of RFC5155
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct NSEC3 {
     pub fqdn: FQDN,
     pub ttl: u32,
@@ -509,7 +630,7 @@ impl fmt::Display for NSEC3 {
 }
 
 // integer types chosen based on bit sizes in section 4.2 of RFC5155
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct NSEC3PARAM {
     pub zone: FQDN,
     pub ttl: u32,
@@ -567,7 +688,7 @@ impl fmt::Display for NSEC3PARAM {
 
 // integer types chosen based on bit sizes in section 3.1 of RFC4034
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct RRSIG {
     pub fqdn: FQDN,
     pub ttl: u32,
@@ -646,7 +767,7 @@ impl fmt::Display for RRSIG {
 }
 
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct SOA {
     pub zone: FQDN,
     pub ttl: u32,
@@ -704,7 +825,7 @@ impl fmt::Display for SOA {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct SoaSettings {
     pub serial: u32,
     pub refresh: u32,
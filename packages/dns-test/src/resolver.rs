@@ -1,32 +1,55 @@
 use core::fmt::Write;
-use std::io::{BufRead, BufReader};
 use std::net::Ipv4Addr;
+use std::time::Duration;
 
-use crate::container::{Child, Container, Network};
-use crate::implementation::{Config, Role};
-use crate::record::DNSKEY;
+use crate::container::{Child, Container, HasContainer, Image, Logs, Monitor, Network};
+use crate::implementation::{Config, ConfigOverrides, LogLevel, Role};
+use crate::record::{Record, DNSKEY};
 use crate::trust_anchor::TrustAnchor;
 use crate::tshark::Tshark;
 use crate::zone_file::Root;
-use crate::{Implementation, Result};
+use crate::{Implementation, Result, FQDN};
+
+mod cache;
 
 pub struct Resolver {
     container: Container,
     child: Child,
     implementation: Implementation,
+    logs: Logs,
+    monitor: Monitor,
+    /// the settings the resolver was launched with; used to bring it back if a restart fails
+    settings: Settings,
 }
 
 impl Resolver {
     #[allow(clippy::new_ret_no_self)]
     pub fn new(network: &Network, root: Root) -> ResolverSettings {
         ResolverSettings {
+            config_overrides: ConfigOverrides::default(),
             ede: false,
+            log_level: LogLevel::from_env(),
             network: network.clone(),
+            redirects: vec![],
             roots: vec![root],
             trust_anchor: TrustAnchor::empty(),
         }
     }
 
+    /// Returns the settings of a forwarding resolver that forwards queries to `upstream`
+    ///
+    /// Use `ForwarderSettings::upstream` to add more upstream servers
+    pub fn forwarder(network: &Network, upstream: Ipv4Addr) -> ForwarderSettings {
+        ForwarderSettings {
+            config_overrides: ConfigOverrides::default(),
+            log_level: LogLevel::from_env(),
+            network: network.clone(),
+            roots: None,
+            trust_anchor: TrustAnchor::empty(),
+            upstreams: vec![upstream],
+        }
+    }
+
     pub fn eavesdrop(&self) -> Result<Tshark> {
         self.container.eavesdrop()
     }
@@ -43,12 +66,197 @@ impl Resolver {
         self.container.ipv4_addr()
     }
 
+    /// Returns the logs the resolver has produced so far
+    ///
+    /// Unlike `terminate`, this method does not stop the resolver
+    pub fn logs(&self) -> String {
+        self.logs.contents()
+    }
+
+    /// Returns the last `n` lines the resolver has logged so far
+    pub fn tail_logs(&self, n: usize) -> String {
+        self.logs.tail(n)
+    }
+
+    /// Blocks until the resolver logs a line that contains `pattern` and returns that line
+    ///
+    /// Lines logged before this method was called are also considered. Returns an error, which
+    /// includes all the logs, if no such line is logged within `timeout`
+    pub fn wait_for_log(&self, pattern: &str, timeout: Duration) -> Result<String> {
+        self.logs.expect(pattern, timeout, &self.implementation)
+    }
+
+    /// Returns `false` if the resolver process has exited, e.g. because it crashed
+    pub fn is_alive(&self) -> bool {
+        self.monitor.is_alive()
+    }
+
+    /// Removes all the records, including negative answers, from the resolver's cache
+    ///
+    /// Not supported by `hickory-dns`
+    pub fn flush_cache(&self) -> Result<()> {
+        match self.implementation {
+            Implementation::Bind => self.container.status_ok(&["rndc", "flush"]),
+            Implementation::Unbound => {
+                self.container
+                    .status_ok(&["unbound-control", "flush_zone", "."])?;
+                self.container
+                    .status_ok(&["unbound-control", "flush_infra", "all"])
+            }
+            Implementation::Hickory(_) => Err(self.unsupported("flushing the cache")),
+        }
+    }
+
+    /// Removes the records of `fqdn` from the resolver's cache
+    ///
+    /// Unbound only removes the records of common types, e.g. A, AAAA, NS and SOA, and not e.g.
+    /// DS or DNSKEY records. Not supported by `hickory-dns`
+    pub fn flush_name(&self, fqdn: &FQDN) -> Result<()> {
+        match self.implementation {
+            Implementation::Bind => self
+                .container
+                .status_ok(&["rndc", "flushname", fqdn.as_str()]),
+            Implementation::Unbound => {
+                self.container
+                    .status_ok(&["unbound-control", "flush", fqdn.as_str()])
+            }
+            Implementation::Hickory(_) => Err(self.unsupported("flushing a name")),
+        }
+    }
+
+    /// Returns the records in the resolver's cache; their TTL field contains their remaining TTL
+    ///
+    /// Negative cache entries and records of types that `Record` does not model are not included.
+    /// Not supported by `hickory-dns`
+    pub fn dump_cache(&self) -> Result<Vec<Record>> {
+        match self.implementation {
+            Implementation::Bind => {
+                // `rndc` may return before `named` has finished writing the dump
+                const DUMP_FILE: &str = "/var/cache/bind/named_dump.db";
+                let dump = format!(
+                    "rm -f {DUMP_FILE}
+rndc dumpdb -cache
+for _ in $(seq 100); do
+    grep -q '^; Dump complete' {DUMP_FILE} 2>/dev/null && break
+    sleep 0.1
+done
+cat {DUMP_FILE}"
+                );
+                let output = self.container.stdout(&["sh", "-c", &dump])?;
+                cache::parse_bind(&output)
+            }
+            Implementation::Unbound => {
+                let output = self.container.stdout(&["unbound-control", "dump_cache"])?;
+                cache::parse_unbound(&output)
+            }
+            Implementation::Hickory(_) => Err(self.unsupported("dumping the cache")),
+        }
+    }
+
+    /// Stops the resolver process and starts a new one in the same container, and thus with the
+    /// same IP address, with `settings`
+    ///
+    /// If the new process cannot be started, the resolver is relaunched with its previous
+    /// settings and the error includes the logs of the stopped process
+    fn restart(
+        &mut self,
+        network: &Network,
+        state: PersistentState,
+        settings: Settings,
+    ) -> Result<()> {
+        // unbound can reload a cache dump but BIND and hickory-dns cannot
+        const UNBOUND_CACHE: &str = "/tmp/unbound.cache";
+
+        assert_eq!(
+            self.network().name(),
+            network.name(),
+            "the settings must use the resolver's network"
+        );
+
+        let implementation = self.implementation.clone();
+        let keep = state == PersistentState::Keep;
+        let container = self.container.clone();
+        if keep && implementation.is_unbound() {
+            let dump = format!("unbound-control dump_cache > {UNBOUND_CACHE}");
+            container.status_ok(&["sh", "-c", &dump])?;
+        }
+
+        let pidfile = implementation.pidfile(Role::Resolver);
+        container.status_ok(&["sh", "-c", &format!("kill -TERM $(cat {pidfile})")])?;
+        let previous_logs = self.child.reap()?.stdout;
+
+        if !keep {
+            // `/var/cache/bind` is BIND's working directory, where it e.g. tracks the state of
+            // RFC 5011 managed trust anchors
+            container.status_ok(&[
+                "sh",
+                "-c",
+                &format!("rm -rf {UNBOUND_CACHE} /var/cache/bind/*"),
+            ])?;
+        }
+
+        // `launch` sets up the redirections of the settings it's given so the current ones
+        // must go, in both the success and the fallback path
+        container.clear_redirects()?;
+        let resolver = match settings.launch(container.clone(), &implementation) {
+            Ok(resolver) => resolver,
+            Err(e) => {
+                // leave `self` with a running process rather than the one that was just reaped.
+                // The failed process may still be running, holding the port
+                let stop = format!(
+                    "pid=$(cat {pidfile} 2>/dev/null) && kill -TERM $pid && while kill -0 $pid 2>/dev/null; do sleep 0.1; done"
+                );
+                let _ = container.status(&["sh", "-c", &stop]);
+                let restored = container
+                    .clear_redirects()
+                    .and_then(|()| self.settings.launch(container, &implementation));
+                let restored = match restored {
+                    Ok(resolver) => {
+                        *self = resolver;
+                        "it was relaunched with its previous settings".to_string()
+                    }
+                    Err(e) => format!("relaunching it with its previous settings failed too: {e}"),
+                };
+                return Err(format!(
+                    "could not restart `{implementation}`; {restored}\n{e}\n--- LOGS BEFORE THE RESTART ---\n{previous_logs}"
+                )
+                .into());
+            }
+        };
+
+        if keep && implementation.is_unbound() {
+            let load = format!("unbound-control load_cache < {UNBOUND_CACHE}");
+            resolver.container.status_ok(&["sh", "-c", &load])?;
+        }
+
+        *self = resolver;
+        Ok(())
+    }
+
+    fn unsupported(&self, operation: &str) -> crate::Error {
+        format!("{operation} is not supported by `{}`", self.implementation).into()
+    }
+
+    /// Collects the logs of a resolver that has exited on its own, e.g. because it crashed,
+    /// without the panic that dropping it would cause
+    ///
+    /// # Panics
+    ///
+    /// this method panics if the resolver is still running
+    pub(crate) fn reap(mut self) -> Result<String> {
+        assert!(!self.is_alive(), "the resolver is still running");
+        Ok(self.child.reap()?.stdout)
+    }
+
     /// Gracefully terminates the name server collecting all logs
     pub fn terminate(self) -> Result<String> {
         let Resolver {
             implementation,
             container,
             child,
+            logs: _,
+            monitor: _,
+            settings: _,
         } = self;
 
         let pidfile = implementation.pidfile(Role::Resolver);
@@ -73,10 +281,47 @@ kill -TERM $(cat {pidfile})"
     }
 }
 
+/// What happens to the state a resolver persists when it is restarted
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PersistentState {
+    /// Keep the state: BIND keeps the files in its working directory, e.g. the state of RFC 5011
+    /// managed trust anchors, and unbound's cache is dumped before and reloaded after the
+    /// restart. `hickory-dns` does not persist any state
+    Keep,
+    /// Start from scratch, with an empty cache
+    Drop,
+}
+
+impl HasContainer for Resolver {
+    fn container(&self) -> &Container {
+        &self.container
+    }
+}
+
+/// Settings of either role, kept by a `Resolver` to relaunch itself
+#[derive(Clone)]
+enum Settings {
+    Resolver(ResolverSettings),
+    Forwarder(ForwarderSettings),
+}
+
+impl Settings {
+    fn launch(&self, container: Container, implementation: &Implementation) -> Result<Resolver> {
+        match self {
+            Settings::Resolver(settings) => settings.launch(container, implementation),
+            Settings::Forwarder(settings) => settings.launch(container, implementation),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct ResolverSettings {
+    config_overrides: ConfigOverrides,
     /// Extended DNS Errors (RFC8914)
     ede: bool,
+    log_level: LogLevel,
     network: Network,
+    redirects: Vec<(Ipv4Addr, Ipv4Addr)>,
     roots: Vec<Root>,
     trust_anchor: TrustAnchor,
 }
@@ -87,63 +332,84 @@ impl ResolverSettings {
     /// This server is not an authoritative name server; it does not serve a zone file to clients
     pub fn start(&self, implementation: &Implementation) -> Result<Resolver> {
         let image = implementation.clone().into();
-        let container = Container::run(&image, &self.network)?;
+        let container = run_container(&image, implementation, &self.network)?;
+        self.launch(container, implementation)
+    }
 
-        let mut hints = String::new();
-        for root in &self.roots {
-            writeln!(hints, "{root}").unwrap();
-        }
+    /// Restarts `resolver` with these settings, e.g. after changing its trust anchor or roots
+    ///
+    /// The resolver keeps its container and thus its IP address; the rest of the topology is not
+    /// touched. `state` indicates whether the state the resolver persists is kept or dropped
+    ///
+    /// # Panics
+    ///
+    /// these settings must use the same network as `resolver`
+    pub fn restart(&self, resolver: &mut Resolver, state: PersistentState) -> Result<()> {
+        resolver.restart(&self.network, state, Settings::Resolver(self.clone()))
+    }
 
-        container.cp("/etc/root.hints", &hints)?;
+    fn launch(&self, container: Container, implementation: &Implementation) -> Result<Resolver> {
+        install_root_hints(&container, &self.roots)?;
+        for (from, to) in &self.redirects {
+            container.redirect_dns(*from, *to)?;
+        }
 
-        let use_dnssec = !self.trust_anchor.is_empty();
         let config = Config::Resolver {
-            use_dnssec,
+            use_dnssec: !self.trust_anchor.is_empty(),
             netmask: self.network.netmask(),
             ede: self.ede,
+            log_level: self.log_level,
         };
-        container.cp(
-            implementation.conf_file_path(config.role()),
-            &implementation.format_config(config),
-        )?;
 
-        if use_dnssec {
-            let path = if implementation.is_bind() {
-                "/etc/bind/bind.keys"
-            } else {
-                "/etc/trusted-key.key"
-            };
-
-            let contents = if implementation.is_bind() {
-                self.trust_anchor.delv()
-            } else {
-                self.trust_anchor.to_string()
-            };
-
-            container.cp(path, &contents)?;
-        }
+        start(
+            container,
+            implementation,
+            config,
+            &self.config_overrides,
+            &self.trust_anchor,
+            Settings::Resolver(self.clone()),
+        )
+    }
 
-        let mut child = container.spawn(implementation.cmd_args(config.role()))?;
+    /// Appends `snippet` verbatim to the configuration file rendered from the built-in template
+    ///
+    /// The snippet must use the syntax of the implementation the resolver is started with, e.g.
+    /// `server:` clauses for unbound; it can be used to try out options that are not modelled by
+    /// this crate. Can be called several times; snippets are appended in order
+    pub fn additional_config(&mut self, snippet: impl Into<String>) -> &mut Self {
+        self.config_overrides.snippets.push(snippet.into());
+        self
+    }
 
-        // For HickoryDNS we need to wait until its start sequence finished. Only then the server is able
-        // to accept connections. The start sequence logs are consumed here.
-        if implementation.is_hickory() {
-            let stdout = child.stdout()?;
-            let lines = BufReader::new(stdout).lines();
+    /// Replaces the built-in configuration template with `template`
+    ///
+    /// `template` is a minijinja template that is rendered with the same variables as the
+    /// built-in one (see `src/templates`), e.g. `use_dnssec`. Useful for changes that cannot be
+    /// appended, like options in BIND's single `options` block. `start` returns an error if
+    /// `template` cannot be rendered
+    pub fn config_template(&mut self, template: impl Into<String>) -> &mut Self {
+        self.config_overrides.template = Some(template.into());
+        self
+    }
 
-            for line in lines {
-                let line = line?;
-                if line.contains("Server starting up") {
-                    break;
-                }
-            }
-        }
+    /// Sets how verbose the resolver's logs are
+    ///
+    /// Defaults to the level set through the `DNS_TEST_LOG_LEVEL` environment variable or
+    /// `LogLevel::Debug` if that's not set
+    pub fn log_level(&mut self, log_level: LogLevel) -> &mut Self {
+        self.log_level = log_level;
+        self
+    }
 
-        Ok(Resolver {
-            child,
-            container,
-            implementation: implementation.clone(),
-        })
+    /// Sends the queries addressed to the server at `from` to the server at `to` instead, e.g. a
+    /// `RogueServer::proxy` of `from`
+    ///
+    /// The redirection is done at the network level, before the resolver starts, so the
+    /// resolver is not aware of it. `clear_faults` removes it, as does restarting the resolver
+    /// with settings that do not include it
+    pub fn redirect(&mut self, from: Ipv4Addr, to: Ipv4Addr) -> &mut Self {
+        self.redirects.push((from, to));
+        self
     }
 
     /// Enables the Extended DNS Errors (RFC8914) feature
@@ -173,8 +439,211 @@ impl ResolverSettings {
     }
 }
 
+#[derive(Clone)]
+pub struct ForwarderSettings {
+    config_overrides: ConfigOverrides,
+    log_level: LogLevel,
+    network: Network,
+    /// root hints used when falling back to recursive resolution; `None` means forward-only
+    roots: Option<Vec<Root>>,
+    trust_anchor: TrustAnchor,
+    upstreams: Vec<Ipv4Addr>,
+}
+
+impl ForwarderSettings {
+    /// Starts a DNS server in the forwarding resolver role
+    ///
+    /// This server forwards all the queries it can't answer from its cache to its upstreams
+    ///
+    /// # Panics
+    ///
+    /// `hickory-dns` does not support local validation nor the forward-first policy
+    pub fn start(&self, implementation: &Implementation) -> Result<Resolver> {
+        let image = match implementation {
+            // hickory's forwarder needs the `resolver` feature; it's only enabled here so that
+            // the other roles keep sharing the image built with the default features
+            Implementation::Hickory(repo) => Image::Hickory(repo.clone().with_feature("resolver")),
+            _ => implementation.clone().into(),
+        };
+        let container = run_container(&image, implementation, &self.network)?;
+        self.launch(container, implementation)
+    }
+
+    /// Restarts `forwarder` with these settings, e.g. after changing its upstreams
+    ///
+    /// See `ResolverSettings::restart` for details
+    pub fn restart(&self, forwarder: &mut Resolver, state: PersistentState) -> Result<()> {
+        forwarder.restart(&self.network, state, Settings::Forwarder(self.clone()))
+    }
+
+    fn launch(&self, container: Container, implementation: &Implementation) -> Result<Resolver> {
+        if let Some(roots) = &self.roots {
+            install_root_hints(&container, roots)?;
+        }
+
+        let config = Config::Forwarder {
+            upstreams: &self.upstreams,
+            use_dnssec: !self.trust_anchor.is_empty(),
+            netmask: self.network.netmask(),
+            forward_first: self.roots.is_some(),
+            log_level: self.log_level,
+        };
+
+        start(
+            container,
+            implementation,
+            config,
+            &self.config_overrides,
+            &self.trust_anchor,
+            Settings::Forwarder(self.clone()),
+        )
+    }
+
+    /// Adds an upstream server queries are forwarded to
+    pub fn upstream(&mut self, ipv4_addr: Ipv4Addr) -> &mut Self {
+        self.upstreams.push(ipv4_addr);
+        self
+    }
+
+    /// Uses the forward-first policy: if forwarding a query fails, resolve it recursively
+    /// starting from the given root hint, rather than fail
+    ///
+    /// The default policy is forward-only. Can be called several times to add more root hints
+    pub fn forward_first(&mut self, root: Root) -> &mut Self {
+        self.roots.get_or_insert_with(Vec::new).push(root);
+        self
+    }
+
+    /// Appends `snippet` verbatim to the configuration file rendered from the built-in template
+    ///
+    /// See `ResolverSettings::additional_config`
+    pub fn additional_config(&mut self, snippet: impl Into<String>) -> &mut Self {
+        self.config_overrides.snippets.push(snippet.into());
+        self
+    }
+
+    /// Replaces the built-in configuration template with `template`
+    ///
+    /// See `ResolverSettings::config_template`. The forwarder templates are also rendered with
+    /// `upstreams` and `forward_first`
+    pub fn config_template(&mut self, template: impl Into<String>) -> &mut Self {
+        self.config_overrides.template = Some(template.into());
+        self
+    }
+
+    /// Sets how verbose the forwarder's logs are
+    ///
+    /// Defaults to the level set through the `DNS_TEST_LOG_LEVEL` environment variable or
+    /// `LogLevel::Debug` if that's not set
+    pub fn log_level(&mut self, log_level: LogLevel) -> &mut Self {
+        self.log_level = log_level;
+        self
+    }
+
+    /// Adds a DNSKEY record to the trust anchor
+    ///
+    /// A non-empty trust anchor makes the forwarder validate responses itself, on top of any
+    /// validation the upstreams do
+    pub fn trust_anchor_key(&mut self, key: DNSKEY) -> &mut Self {
+        self.trust_anchor.add(key.clone());
+        self
+    }
+
+    /// Adds all the keys in the `other` trust anchor to ours
+    ///
+    /// A non-empty trust anchor makes the forwarder validate responses itself, on top of any
+    /// validation the upstreams do
+    pub fn trust_anchor(&mut self, other: &TrustAnchor) -> &mut Self {
+        for key in other.keys() {
+            self.trust_anchor.add(key.clone());
+        }
+        self
+    }
+}
+
+/// Creates the container of a resolver or forwarder
+///
+/// The `rndc` key is generated here, once per container, so that restarts reuse it
+fn run_container(
+    image: &Image,
+    implementation: &Implementation,
+    network: &Network,
+) -> Result<Container> {
+    let container = Container::run(image, network)?;
+    if implementation.is_bind() {
+        // `named` sets up a control channel for `rndc` on the loopback interface if this key exists
+        container.status_ok(&["rndc-confgen", "-a"])?;
+    }
+    Ok(container)
+}
+
+fn install_root_hints(container: &Container, roots: &[Root]) -> Result<()> {
+    let mut hints = String::new();
+    for root in roots {
+        writeln!(hints, "{root}").unwrap();
+    }
+
+    container.cp("/etc/root.hints", &hints)
+}
+
+/// Configures and starts a server in either the resolver or the forwarder role
+fn start(
+    container: Container,
+    implementation: &Implementation,
+    config: Config,
+    config_overrides: &ConfigOverrides,
+    trust_anchor: &TrustAnchor,
+    settings: Settings,
+) -> Result<Resolver> {
+    container.cp(
+        implementation.conf_file_path(config.role()),
+        &implementation.format_config(config, config_overrides)?,
+    )?;
+
+    if !trust_anchor.is_empty() {
+        let path = if implementation.is_bind() {
+            "/etc/bind/bind.keys"
+        } else {
+            "/etc/trusted-key.key"
+        };
+
+        let contents = if implementation.is_bind() {
+            trust_anchor.delv()
+        } else {
+            trust_anchor.to_string()
+        };
+
+        container.cp(path, &contents)?;
+    }
+
+    let mut child = container.spawn(&implementation.cmd_args(&config))?;
+    let logs = child.collect_logs()?;
+    container.wait_until_ready(
+        &mut child,
+        &logs,
+        implementation.readiness_probe(&config),
+        implementation,
+    )?;
+    let monitor = container.monitor(&mut child, implementation);
+
+    Ok(Resolver {
+        child,
+        container,
+        implementation: implementation.clone(),
+        logs,
+        monitor,
+        settings,
+    })
+}
+
 #[cfg(test)]
 mod tests {
+    use std::panic::{self, AssertUnwindSafe};
+    use std::thread;
+    use std::time::Instant;
+
+    use crate::client::{Client, DigSettings};
+    use crate::record::RecordType;
     use crate::{name_server::NameServer, Repository, FQDN};
 
     use super::*;
@@ -214,8 +683,203 @@ mod tests {
         ))?;
         let logs = resolver.terminate()?;
 
-        // Hickory-DNS start sequence log has been consumed in `ResolverSettings.start`.
-        assert!(logs.is_empty());
+        eprintln!("{logs}");
+        assert!(logs.contains("Server starting up"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn logs_can_be_read_while_running() -> Result<()> {
+        let network = Network::new()?;
+        let ns = NameServer::new(&Implementation::Unbound, FQDN::ROOT, &network)?.start()?;
+        let resolver = Resolver::new(&network, ns.root_hint()).start(&Implementation::Unbound)?;
+
+        let line = resolver.wait_for_log("start of service", Duration::from_secs(1))?;
+        assert!(resolver.logs().contains(&line));
+        assert!(resolver
+            .wait_for_log("not in the logs", Duration::ZERO)
+            .is_err());
+        assert!(resolver.is_alive());
+
+        Ok(())
+    }
+
+    #[test]
+    fn cache_can_be_inspected_and_flushed() -> Result<()> {
+        let network = Network::new()?;
+        let needle_fqdn = FQDN("example.nameservers.com.")?;
+        let mut ns = NameServer::new(&Implementation::Unbound, FQDN::ROOT, &network)?;
+        ns.add(Record::a(needle_fqdn.clone(), Ipv4Addr::new(1, 2, 3, 4)));
+        let ns = ns.start()?;
+
+        for implementation in [Implementation::Bind, Implementation::Unbound] {
+            let resolver = Resolver::new(&network, ns.root_hint()).start(&implementation)?;
+            let client = Client::new(&network)?;
+            let dig = || {
+                client.dig(
+                    *DigSettings::default().recurse(),
+                    resolver.ipv4_addr(),
+                    RecordType::A,
+                    &needle_fqdn,
+                )
+            };
+            assert!(dig()?.status.is_noerror());
+
+            let is_needle =
+                |record: &Record| matches!(record, Record::A(a) if a.fqdn == needle_fqdn);
+            let cache = resolver.dump_cache()?;
+            let Some(Record::A(a)) = cache.iter().find(|record| is_needle(record)) else {
+                panic!("{implementation} did not cache the A record: {cache:?}");
+            };
+            assert!(a.ttl <= crate::DEFAULT_TTL);
+
+            resolver.flush_name(&needle_fqdn)?;
+            assert!(!resolver.dump_cache()?.iter().any(is_needle));
+
+            assert!(dig()?.status.is_noerror());
+            resolver.flush_cache()?;
+            assert!(!resolver.dump_cache()?.iter().any(is_needle));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn restart_keeps_address_and_optionally_cache() -> Result<()> {
+        let network = Network::new()?;
+        let needle_fqdn = FQDN("example.nameservers.com.")?;
+        let mut ns = NameServer::new(&Implementation::Unbound, FQDN::ROOT, &network)?;
+        ns.add(Record::a(needle_fqdn.clone(), Ipv4Addr::new(1, 2, 3, 4)));
+        let ns = ns.start()?;
+
+        let mut settings = Resolver::new(&network, ns.root_hint());
+        let mut resolver = settings.start(&Implementation::Unbound)?;
+        let ipv4_addr = resolver.ipv4_addr();
+
+        let client = Client::new(&network)?;
+        let output = client.dig(
+            *DigSettings::default().recurse(),
+            ipv4_addr,
+            RecordType::A,
+            &needle_fqdn,
+        )?;
+        assert!(output.status.is_noerror());
+
+        let is_cached = |resolver: &Resolver| -> Result<bool> {
+            Ok(resolver
+                .dump_cache()?
+                .iter()
+                .any(|record| matches!(record, Record::A(a) if a.fqdn == needle_fqdn)))
+        };
+        assert!(is_cached(&resolver)?);
+
+        settings.extended_dns_errors();
+        settings.restart(&mut resolver, PersistentState::Keep)?;
+        assert_eq!(ipv4_addr, resolver.ipv4_addr());
+        assert!(resolver.is_alive());
+        assert!(is_cached(&resolver)?);
+
+        settings.restart(&mut resolver, PersistentState::Drop)?;
+        assert_eq!(ipv4_addr, resolver.ipv4_addr());
+        assert!(!is_cached(&resolver)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn restart_removes_dropped_redirects() -> Result<()> {
+        let network = Network::new()?;
+        let needle_fqdn = FQDN("example.nameservers.com.")?;
+        let mut ns = NameServer::new(&Implementation::Unbound, FQDN::ROOT, &network)?;
+        ns.add(Record::a(needle_fqdn.clone(), Ipv4Addr::new(1, 2, 3, 4)));
+        let ns = ns.start()?;
+        // a root zone without the needle
+        let decoy = NameServer::new(&Implementation::Unbound, FQDN::ROOT, &network)?.start()?;
+
+        let settings = Resolver::new(&network, ns.root_hint());
+        let mut redirected = settings.clone();
+        redirected.redirect(ns.ipv4_addr(), decoy.ipv4_addr());
+        let mut resolver = redirected.start(&Implementation::Unbound)?;
+
+        let client = Client::new(&network)?;
+        let dig = |resolver: &Resolver| {
+            client.dig(
+                *DigSettings::default().recurse(),
+                resolver.ipv4_addr(),
+                RecordType::A,
+                &needle_fqdn,
+            )
+        };
+        assert!(dig(&resolver)?.status.is_nxdomain());
+
+        settings.restart(&mut resolver, PersistentState::Drop)?;
+        assert!(dig(&resolver)?.status.is_noerror());
+
+        Ok(())
+    }
+
+    #[test]
+    fn failed_restart_relaunches_the_previous_settings() -> Result<()> {
+        let network = Network::new()?;
+        let ns = NameServer::new(&Implementation::Unbound, FQDN::ROOT, &network)?.start()?;
+        let settings = Resolver::new(&network, ns.root_hint());
+        let mut resolver = settings.start(&Implementation::Unbound)?;
+
+        let mut broken = settings.clone();
+        broken.additional_config("not-an-option: yes");
+        let error = broken
+            .restart(&mut resolver, PersistentState::Drop)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("relaunched with its previous settings"));
+        assert!(error.contains("--- LOGS BEFORE THE RESTART ---\n"));
+        assert!(error.contains("start of service"));
+
+        assert!(resolver.is_alive());
+        let client = Client::new(&network)?;
+        let output = client.dig(
+            *DigSettings::default().recurse(),
+            resolver.ipv4_addr(),
+            RecordType::SOA,
+            &FQDN::ROOT,
+        )?;
+        assert!(output.status.is_noerror());
+
+        Ok(())
+    }
+
+    #[test]
+    fn crash_is_detected() -> Result<()> {
+        let network = Network::new()?;
+        let ns = NameServer::new(&Implementation::Unbound, FQDN::ROOT, &network)?.start()?;
+        let resolver = Resolver::new(&network, ns.root_hint()).start(&Implementation::Unbound)?;
+        assert!(resolver.is_alive());
+
+        let pidfile = resolver.implementation.pidfile(Role::Resolver);
+        resolver
+            .container
+            .status_ok(&["sh", "-c", &format!("kill -KILL $(cat {pidfile})")])?;
+        // the process is reaped asynchronously
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while resolver.is_alive() {
+            assert!(Instant::now() < deadline, "the crash was not detected");
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let client = Client::new(&network)?;
+        let res = client.dig(
+            *DigSettings::default().recurse(),
+            resolver.ipv4_addr(),
+            RecordType::SOA,
+            &FQDN::ROOT,
+        );
+        let error = res.expect_err("dig should fail").to_string();
+        assert!(error.contains("exited with"), "{error}");
+
+        // teardown flags the unexpected exit
+        let res = panic::catch_unwind(AssertUnwindSafe(|| drop(resolver)));
+        assert!(res.is_err());
 
         Ok(())
     }
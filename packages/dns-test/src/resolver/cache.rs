@@ -0,0 +1,192 @@
+//! Parsers for the cache dumps produced by `unbound-control dump_cache` and `rndc dumpdb -cache`
+
+use crate::record::Record;
+use crate::Result;
+
+/// Parses the RRset section of the output of `unbound-control dump_cache`
+///
+/// The records in that section are in presentation format with their remaining TTL
+pub(super) fn parse_unbound(dump: &str) -> Result<Vec<Record>> {
+    const START: &str = "START_RRSET_CACHE";
+    const END: &str = "END_RRSET_CACHE";
+
+    let mut records = vec![];
+    let mut lines = dump.lines().skip_while(|line| line.trim() != START);
+    if lines.next().is_none() {
+        return Err(format!("`{START}` not found in cache dump:\n{dump}").into());
+    }
+
+    for line in lines {
+        let line = line.trim();
+        if line == END {
+            return Ok(records);
+        }
+
+        // `;rrset` lines carry metadata about the RRset that follows
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        push_record(&mut records, line)?;
+    }
+
+    Err(format!("`{END}` not found in cache dump:\n{dump}").into())
+}
+
+/// Parses the cache section of the file written by `rndc dumpdb -cache`
+///
+/// BIND omits the class, omits the owner name when it's the same as the one of the previous
+/// record and splits long records across several lines using parentheses
+pub(super) fn parse_bind(dump: &str) -> Result<Vec<Record>> {
+    // the address database, bad cache, etc. sections that follow do not contain records
+    const END: &str = "; Address database dump";
+
+    let mut records = vec![];
+    let mut owner: Option<String> = None;
+    let mut lines = dump.lines();
+    while let Some(line) = lines.next() {
+        if line.starts_with(END) {
+            break;
+        }
+
+        let mut entry = strip_comment(line).to_string();
+        if entry.trim().is_empty() || entry.starts_with('$') {
+            continue;
+        }
+
+        if entry.contains('(') {
+            while !entry.contains(')') {
+                let Some(line) = lines.next() else {
+                    return Err(format!("unterminated multi-line record: {entry}").into());
+                };
+                entry.push(' ');
+                entry.push_str(strip_comment(line));
+            }
+            entry = entry.replace(['(', ')'], " ");
+        }
+
+        let mut columns = entry.split_whitespace().collect::<Vec<_>>();
+        if !entry.starts_with(char::is_whitespace) {
+            owner = Some(columns.remove(0).to_string());
+        }
+        let Some(owner) = &owner else {
+            return Err(format!("record without owner name: {line}").into());
+        };
+
+        // negative cache entries have types like `\-A`
+        if columns.len() < 2 || columns[1].starts_with('\\') {
+            continue;
+        }
+
+        let ttl = columns[0];
+        let rest = if columns[1] == "IN" {
+            &columns[2..]
+        } else {
+            &columns[1..]
+        };
+        push_record(
+            &mut records,
+            &format!("{owner} {ttl} IN {}", rest.join(" ")),
+        )?;
+    }
+
+    Ok(records)
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split_once(';').map_or(line, |(before, _)| before)
+}
+
+fn push_record(records: &mut Vec<Record>, line: &str) -> Result<()> {
+    // cached records that `Record` cannot represent are skipped
+    if Record::is_modelled(line) {
+        records.push(line.parse()?);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unbound() -> Result<()> {
+        let dump = "START_RRSET_CACHE
+;rrset 86390 1 0 8 3
+com.\t86390\tIN\tNS\tprimary0.nameservers.com.
+;rrset 86390 1 0 8 3
+example.nameservers.com.\t86390\tIN\tAAAA\t::1
+;rrset 86385 1 0 8 3
+example.nameservers.com.\t86385\tIN\tA\t1.2.3.4
+END_RRSET_CACHE
+START_MSG_CACHE
+msg example.nameservers.com. IN A 33152 1 86385 3 1 0 0 -1
+example.nameservers.com. IN A 0
+END_MSG_CACHE
+EOF
+";
+
+        let records = parse_unbound(dump)?;
+
+        assert_eq!(2, records.len());
+        assert!(matches!(&records[0], Record::NS(ns) if ns.ttl == 86390));
+        let Record::A(a) = &records[1] else {
+            panic!("expected an A record");
+        };
+        assert_eq!("example.nameservers.com.", a.fqdn.as_str());
+        assert_eq!(86385, a.ttl);
+
+        Ok(())
+    }
+
+    #[test]
+    fn bind() -> Result<()> {
+        let dump = ";
+; Start view _default
+;
+;
+; Cache dump of view '_default' (cache _default)
+;
+; using a 0 second stale ttl
+$DATE 20240101000000
+; authanswer
+com.\t\t\t86394\tNS\tprimary0.nameservers.com.
+\t\t\t86394\tNS\tprimary1.nameservers.com.
+; authauthority
+nx.com.\t\t\t3594\t\\-A\t;-$NXDOMAIN
+; authanswer
+example.nameservers.com. 86385\tA\t1.2.3.4
+\t\t\t86385\tSOA\tprimary0.nameservers.com. admin0.nameservers.com. (
+\t\t\t\t2024010101 ; serial
+\t\t\t\t1800       ; refresh (30 minutes)
+\t\t\t\t900        ; retry (15 minutes)
+\t\t\t\t604800     ; expire (1 week)
+\t\t\t\t86400      ; minimum (1 day)
+\t\t\t\t)
+;
+; Address database dump
+;
+; [edns success/timeout]
+; primary0.nameservers.com. [v4 TTL 1786] [v6 unexpected] [v4 success] 192.168.0.2 [srtt 10] [flags 00000000] [edns 1/0] [plain 0/0] [udpsize 512] [cookie=0] [ttl 1786]
+;
+; Dump complete
+";
+
+        let records = parse_bind(dump)?;
+
+        assert_eq!(4, records.len());
+        assert!(matches!(&records[1], Record::NS(ns) if ns.ttl == 86394));
+        assert!(
+            matches!(&records[2], Record::A(a) if a.fqdn.as_str() == "example.nameservers.com.")
+        );
+        let Record::SOA(soa) = &records[3] else {
+            panic!("expected a SOA record");
+        };
+        assert_eq!("example.nameservers.com.", soa.zone.as_str());
+        assert_eq!(2024010101, soa.settings.serial);
+        assert_eq!(86385, soa.ttl);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,513 @@
+//! A scriptable authoritative name server that misbehaves on purpose
+//!
+//! A `RogueServer` serves a zone like a regular name server but alters its responses according
+//! to the `Rule`s set by the test, e.g. to truncate them, delay them or not send them at all. It
+//! runs the `rogue-server` binary from the `packages/rogue-server` directory of this repository
+//!
+//! A rogue server can also act as a proxy that forwards queries to a regular name server and
+//! alters its responses, e.g. to tamper with signed responses; see `RogueServer::proxy`
+
+use core::fmt;
+use std::net::Ipv4Addr;
+use std::sync::atomic::{self, AtomicUsize};
+use std::time::Duration;
+
+use crate::client::DigStatus;
+use crate::container::{Child, Container, Image, Logs, Monitor, Network, Probe};
+use crate::message::Section;
+use crate::name_server::{Stopped, ZoneServer};
+use crate::record::{Record, RecordType, SoaSettings, SOA};
+use crate::tshark::Tshark;
+use crate::zone_file::Root;
+use crate::{Result, DEFAULT_TTL, FQDN};
+
+const CONF_FILE_PATH: &str = "/etc/rogue.conf";
+const PIDFILE: &str = "/tmp/rogue-server.pid";
+/// `rogue-server` prints this once it's listening
+const READY_MARKER: &str = "serving `";
+
+pub struct RogueServer<State> {
+    container: Container,
+    fqdn: FQDN,
+    records: Vec<Record>,
+    rules: Vec<Rule>,
+    soa: SOA,
+    state: State,
+    upstream: Option<Ipv4Addr>,
+}
+
+impl RogueServer<Stopped> {
+    /// Spins up a container in a parked state where the rogue server is not yet running
+    ///
+    /// The zone gets a SOA record, a NS record and an A record for the server itself
+    pub fn new(zone: FQDN, network: &Network) -> Result<Self> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let count = COUNT.fetch_add(1, atomic::Ordering::Relaxed);
+        let container = Container::run(&Image::Rogue, network)?;
+
+        // the name of the server is in-bailiwick so the parent zone must include glue
+        let fqdn = FQDN(format!("rogue{count}.{}", zone.as_str()))?;
+        let soa = SOA {
+            zone: zone.clone(),
+            ttl: DEFAULT_TTL,
+            nameserver: fqdn.clone(),
+            admin: FQDN(format!("admin.{}", zone.as_str()))?,
+            settings: SoaSettings::default(),
+        };
+        let records = vec![
+            Record::ns(zone, fqdn.clone()),
+            Record::a(fqdn.clone(), container.ipv4_addr()),
+        ];
+
+        Ok(Self {
+            container,
+            fqdn,
+            records,
+            rules: vec![],
+            soa,
+            state: Stopped,
+            upstream: None,
+        })
+    }
+
+    /// Spins up a proxy that forwards all queries, over TCP, to `upstream` and alters its
+    /// responses according to the rules
+    ///
+    /// Records added with `add` are ignored. The proxy is not part of the zone's NS set, so
+    /// resolvers must be told to use it in place of `upstream`; see
+    /// `ResolverSettings::redirect`
+    pub fn proxy(upstream: &(impl ZoneServer + ?Sized)) -> Result<Self> {
+        let mut proxy = Self::new(upstream.zone().clone(), upstream.network())?;
+        proxy.fqdn = upstream.fqdn().clone();
+        proxy.records.clear();
+        proxy.upstream = Some(upstream.ipv4_addr());
+        Ok(proxy)
+    }
+
+    /// Adds a record to the zone
+    ///
+    /// DNSSEC records are not supported; see `Action` for the supported record types
+    pub fn add(&mut self, record: impl Into<Record>) -> &mut Self {
+        self.records.push(record.into());
+        self
+    }
+
+    /// Adds a rule that alters the responses to the queries it matches
+    ///
+    /// All the rules that match a query are applied in the order they were added
+    pub fn rule(&mut self, rule: Rule) -> &mut Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Starts serving the zone
+    pub fn start(self) -> Result<RogueServer<Running>> {
+        let Self {
+            container,
+            fqdn,
+            records,
+            rules,
+            soa,
+            state: _,
+            upstream,
+        } = self;
+
+        container.cp(CONF_FILE_PATH, &config(&soa, &records, &rules, upstream))?;
+
+        let mut child = container.spawn(&[
+            "sh",
+            "-c",
+            &format!("echo $$ > {PIDFILE}\nexec rogue-server {CONF_FILE_PATH}"),
+        ])?;
+        let logs = child.collect_logs()?;
+        // the readiness probe must not depend on a query as the rules may drop it
+        container.wait_until_ready(
+            &mut child,
+            &logs,
+            Probe::LogMarker(READY_MARKER),
+            &"rogue-server",
+        )?;
+        let monitor = container.monitor(&mut child, &"rogue-server");
+
+        Ok(RogueServer {
+            container,
+            fqdn,
+            records,
+            rules,
+            soa,
+            state: Running {
+                child,
+                logs,
+                monitor,
+            },
+            upstream,
+        })
+    }
+}
+
+pub struct Running {
+    child: Child,
+    logs: Logs,
+    monitor: Monitor,
+}
+
+impl RogueServer<Running> {
+    /// Starts a `tshark` instance that captures DNS messages flowing through this network node
+    pub fn eavesdrop(&self) -> Result<Tshark> {
+        self.container.eavesdrop()
+    }
+
+    /// Returns the logs the server has produced so far; they include one line per query with
+    /// the rules that were applied to it
+    pub fn logs(&self) -> String {
+        self.state.logs.contents()
+    }
+
+    /// Blocks until the server logs a line that contains `pattern` and returns that line
+    ///
+    /// Lines logged before this method was called are also considered. Returns an error, which
+    /// includes all the logs, if no such line is logged within `timeout`
+    pub fn wait_for_log(&self, pattern: &str, timeout: Duration) -> Result<String> {
+        self.state.logs.expect(pattern, timeout, &"rogue-server")
+    }
+
+    /// Returns `false` if the server process has exited, e.g. because it crashed
+    pub fn is_alive(&self) -> bool {
+        self.state.monitor.is_alive()
+    }
+
+    /// Terminates the server and returns its logs
+    pub fn terminate(self) -> Result<String> {
+        self.container
+            .status_ok(&["sh", "-c", &format!("kill -TERM $(cat {PIDFILE})")])?;
+        // `rogue-server` does not handle signals so it won't exit successfully
+        let output = self.state.child.wait()?;
+        Ok(output.stdout)
+    }
+}
+
+impl<S> RogueServer<S> {
+    pub fn container_id(&self) -> &str {
+        self.container.id()
+    }
+
+    pub fn ipv4_addr(&self) -> Ipv4Addr {
+        self.container.ipv4_addr()
+    }
+
+    pub fn zone(&self) -> &FQDN {
+        &self.soa.zone
+    }
+
+    /// The name of this server, i.e. the target of the zone's NS record
+    pub fn fqdn(&self) -> &FQDN {
+        &self.fqdn
+    }
+
+    pub fn a(&self) -> Record {
+        Record::a(self.fqdn.clone(), self.ipv4_addr())
+    }
+
+    /// Returns the [`Root`] hint for this server
+    pub fn root_hint(&self) -> Root {
+        Root::new(self.fqdn.clone(), self.ipv4_addr())
+    }
+}
+
+impl<S> ZoneServer for RogueServer<S> {
+    fn zone(&self) -> &FQDN {
+        self.zone()
+    }
+
+    fn fqdn(&self) -> &FQDN {
+        self.fqdn()
+    }
+
+    fn ipv4_addr(&self) -> Ipv4Addr {
+        self.ipv4_addr()
+    }
+
+    fn network(&self) -> &Network {
+        self.container.network()
+    }
+}
+
+fn config(soa: &SOA, records: &[Record], rules: &[Rule], upstream: Option<Ipv4Addr>) -> String {
+    let mut config = format!("zone {}\n", soa.zone.as_str());
+    if let Some(upstream) = upstream {
+        config.push_str(&format!("upstream {upstream}\n"));
+    } else {
+        config.push_str(&format!("record {soa}\n"));
+        for record in records {
+            let record = single_line(&record.to_string());
+            config.push_str(&format!("record {record}\n"));
+        }
+    }
+    for rule in rules {
+        config.push_str(&format!("rule {rule}\n"));
+    }
+    config
+}
+
+/// Alters the responses to the queries that match it
+///
+/// By default a rule matches every query, any number of times
+#[derive(Debug)]
+pub struct Rule {
+    action: Action,
+    qname: Option<FQDN>,
+    qtype: Option<RecordType>,
+    times: Option<u32>,
+}
+
+impl Rule {
+    pub fn new(action: Action) -> Self {
+        Self {
+            action,
+            qname: None,
+            qtype: None,
+            times: None,
+        }
+    }
+
+    /// Only matches queries for `fqdn`
+    #[must_use]
+    pub fn qname(mut self, fqdn: FQDN) -> Self {
+        self.qname = Some(fqdn);
+        self
+    }
+
+    /// Only matches queries for records of type `record_type`
+    #[must_use]
+    pub fn qtype(mut self, record_type: RecordType) -> Self {
+        self.qtype = Some(record_type);
+        self
+    }
+
+    /// Stops matching once the rule has been applied `times` times, e.g. to only drop the first
+    /// query and check that it's retried
+    #[must_use]
+    pub fn times(mut self, times: u32) -> Self {
+        self.times = Some(times);
+        self
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            action,
+            qname,
+            qtype,
+            times,
+        } = self;
+
+        match qname {
+            Some(qname) => write!(f, "{} ", qname.as_str())?,
+            None => f.write_str("* ")?,
+        }
+        match qtype {
+            Some(qtype) => write!(f, "{qtype} ")?,
+            None => f.write_str("* ")?,
+        }
+        match times {
+            Some(times) => write!(f, "{times} ")?,
+            None => f.write_str("* ")?,
+        }
+        write!(f, "{action}")
+    }
+}
+
+/// How a `Rule` alters a response
+///
+/// Records are given in presentation format, e.g. `Record::to_string()`. The rdata of types
+/// other than A, AAAA, NS, CNAME, PTR, MX, SOA and TXT must use the RFC 3597 form, e.g.
+/// `example.com. 3600 IN TYPE65534 \# 2 abcd`, which can also be used to craft malformed records
+#[derive(Clone, Debug)]
+pub enum Action {
+    /// Do not reply
+    Drop,
+    /// Wait before replying; the delays of all the matching rules add up
+    Delay(Duration),
+    /// Reply with an ID that does not match the query's
+    WrongId,
+    /// Over UDP, reply with an empty response that has the TC flag set; TCP is not affected
+    Truncate,
+    SetFlag(Flag),
+    ClearFlag(Flag),
+    /// Set the response code
+    Rcode(DigStatus),
+    /// Append a record to the answer section
+    Answer(String),
+    /// Append a record to the authority section
+    Authority(String),
+    /// Append a record to the additional section
+    Additional(String),
+    /// Remove all the records, including the ones added by previous rules, from the response
+    Clear,
+    /// Reply with a message whose header announces one more answer record than it contains
+    Malformed,
+    /// Reply with these bytes, verbatim, instead
+    Raw(Vec<u8>),
+    /// Flip the bit at this offset, counted from the start of the encoded response and wrapping
+    /// around at its end
+    FlipBit(usize),
+    /// Remove all the RRSIG records from the response
+    DropRrsigs,
+    /// Reverse the order of the records in each section
+    Reverse,
+    /// Move all the records of the first section to the end of the second one; the question
+    /// section cannot be used
+    Move(Section, Section),
+    /// Set the TTL of all the records
+    Ttl(u32),
+    /// Write the owner names of all the records in uppercase
+    Uppercase,
+    /// Give the records owned by the first name to the second one
+    Rename(FQDN, FQDN),
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Drop => f.write_str("drop"),
+            Self::Delay(delay) => write!(f, "delay {}", delay.as_millis()),
+            Self::WrongId => f.write_str("wrong-id"),
+            Self::Truncate => f.write_str("truncate"),
+            Self::SetFlag(flag) => write!(f, "set-flag {flag}"),
+            Self::ClearFlag(flag) => write!(f, "clear-flag {flag}"),
+            Self::Rcode(status) => write!(f, "rcode {}", status.code()),
+            Self::Answer(record) => write!(f, "answer {}", single_line(record)),
+            Self::Authority(record) => write!(f, "authority {}", single_line(record)),
+            Self::Additional(record) => write!(f, "additional {}", single_line(record)),
+            Self::Clear => f.write_str("clear"),
+            Self::Malformed => f.write_str("malformed"),
+            Self::Raw(bytes) => {
+                f.write_str("raw ")?;
+                for byte in bytes {
+                    write!(f, "{byte:02x}")?;
+                }
+                Ok(())
+            }
+            Self::FlipBit(bit) => write!(f, "flip-bit {bit}"),
+            Self::DropRrsigs => f.write_str("drop-rrsigs"),
+            Self::Reverse => f.write_str("reverse"),
+            Self::Move(from, to) => {
+                let name = |section: &Section| format!("{section:?}").to_lowercase();
+                write!(f, "move {} {}", name(from), name(to))
+            }
+            Self::Ttl(ttl) => write!(f, "ttl {ttl}"),
+            Self::Uppercase => f.write_str("uppercase"),
+            Self::Rename(from, to) => write!(f, "rename {} {}", from.as_str(), to.as_str()),
+        }
+    }
+}
+
+/// `Record`s with long rdata, e.g. RRSIG, are displayed over several lines
+fn single_line(record: &str) -> String {
+    record.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Response header flags
+#[derive(Clone, Copy, Debug)]
+pub enum Flag {
+    /// Authoritative Answer
+    Aa,
+    /// TrunCation
+    Tc,
+    /// Recursion Desired
+    Rd,
+    /// Recursion Available
+    Ra,
+    /// Authentic Data
+    Ad,
+    /// Checking Disabled
+    Cd,
+}
+
+impl fmt::Display for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Aa => "aa",
+            Self::Tc => "tc",
+            Self::Rd => "rd",
+            Self::Ra => "ra",
+            Self::Ad => "ad",
+            Self::Cd => "cd",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::{Client, DigSettings};
+    use crate::name_server::{Graph, Sign};
+    use crate::{Implementation, Resolver};
+
+    use super::*;
+
+    #[test]
+    fn rule_format() -> Result<()> {
+        let needle_fqdn = FQDN("example.nameservers.com.")?;
+        let record = Record::a(needle_fqdn.clone(), Ipv4Addr::new(1, 2, 3, 4));
+
+        let rule = Rule::new(Action::Answer(record.to_string()))
+            .qname(needle_fqdn)
+            .qtype(RecordType::A)
+            .times(2);
+        assert_eq!(
+            "example.nameservers.com. A 2 answer example.nameservers.com. 86400 IN A 1.2.3.4",
+            rule.to_string()
+        );
+
+        let rule = Rule::new(Action::Raw(vec![0xde, 0xad]));
+        assert_eq!("* * * raw dead", rule.to_string());
+
+        let rule = Rule::new(Action::Rcode(DigStatus::SERVFAIL));
+        assert_eq!("* * * rcode 2", rule.to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolver_retries_dropped_query() -> Result<()> {
+        let network = Network::new()?;
+        let zone = FQDN("rogue.com.")?;
+        let needle_fqdn = FQDN("example.rogue.com.")?;
+        let expected_ipv4_addr = Ipv4Addr::new(1, 2, 3, 4);
+
+        let mut rogue = RogueServer::new(zone, &network)?;
+        rogue
+            .add(Record::a(needle_fqdn.clone(), expected_ipv4_addr))
+            .rule(
+                Rule::new(Action::Drop)
+                    .qname(needle_fqdn.clone())
+                    .qtype(RecordType::A)
+                    .times(1),
+            );
+        let rogue = rogue.start()?;
+
+        let Graph {
+            nameservers: _nameservers,
+            root,
+            ..
+        } = Graph::build_above(&rogue, &Implementation::Unbound, Sign::No)?;
+
+        let resolver = Resolver::new(&network, root).start(&Implementation::Unbound)?;
+        let client = Client::new(&network)?;
+        let output = client.dig(
+            *DigSettings::default().recurse(),
+            resolver.ipv4_addr(),
+            RecordType::A,
+            &needle_fqdn,
+        )?;
+
+        assert!(output.status.is_noerror());
+        let [answer] = output.answer.try_into().unwrap();
+        assert_eq!(expected_ipv4_addr, answer.try_into_a().unwrap().ipv4_addr);
+        assert!(rogue.logs().contains("Drop"));
+
+        Ok(())
+    }
+}
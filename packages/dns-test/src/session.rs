@@ -0,0 +1,407 @@
+//! Environments that outlive a single command, as used by the `dns-test` binary
+//!
+//! `Session::up` starts a `Topology` and writes an `Inventory` of its containers to the state
+//! directory. Containers are removed when the process that created them exits so the process
+//! that ran `up` has to stay around: `Session::run` mirrors the logs of the servers into the
+//! state directory until `down` removes the inventory. Other processes find the environment
+//! through `Inventory::load`
+//!
+//! The state directory is the `dns-test` subdirectory of `$DNS_TEST_STATE_DIR` or, if that is not
+//! set, of the temporary directory. Only the files `dns-test` writes there are ever removed.
+//! There is at most one environment per state directory
+
+use core::fmt;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::{Duration, Instant};
+use std::{env, fs, io, thread};
+
+use serde::{Deserialize, Serialize};
+
+use crate::client::Client;
+use crate::container::runtime::{self, Owner};
+use crate::topology::{Deployment, Topology};
+use crate::{Network, Result};
+
+const INVENTORY_FILENAME: &str = "inventory.json";
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const DOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Returns the directory the inventory and the logs are written to
+pub fn state_dir() -> PathBuf {
+    env::var_os("DNS_TEST_STATE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir)
+        .join("dns-test")
+}
+
+/// The containers of a running environment
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Inventory {
+    /// The process that ran `up` and owns the containers
+    owner: Owner,
+    pub topology: PathBuf,
+    pub network: String,
+    pub members: Vec<Member>,
+}
+
+/// A container of the environment
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Member {
+    /// `resolver`, `client`, the zone of a name server, e.g. `example.com.`, or `proxy` followed
+    /// by the zone of the name server it sits in front of
+    pub name: String,
+    pub ipv4_addr: Ipv4Addr,
+    pub container_id: String,
+}
+
+impl Member {
+    fn new(name: impl Into<String>, ipv4_addr: Ipv4Addr, container_id: &str) -> Self {
+        Self {
+            name: name.into(),
+            ipv4_addr,
+            container_id: container_id.to_string(),
+        }
+    }
+
+    /// Runs `command_and_args` inside the member's container with the standard streams of this
+    /// process
+    pub fn exec(&self, command_and_args: &[&str]) -> Result<ExitStatus> {
+        let status = runtime::runtime()
+            .exec(&self.container_id, command_and_args)?
+            .status()?;
+        Ok(status)
+    }
+}
+
+impl Inventory {
+    /// Loads the inventory of the environment that is currently up
+    pub fn load() -> Result<Self> {
+        let path = state_dir().join(INVENTORY_FILENAME);
+        let json = fs::read_to_string(&path)
+            .map_err(|_| "no environment is up; start one with `dns-test up <topology>`")?;
+        let inventory: Self = serde_json::from_str(&json)
+            .map_err(|e| format!("{} is corrupted: {e}", path.display()))?;
+
+        if !inventory.is_alive() {
+            return Err(format!(
+                "the process that started the environment ({}) has exited; run `dns-test down`",
+                inventory.pid()
+            )
+            .into());
+        }
+
+        Ok(inventory)
+    }
+
+    /// PID of the process that ran `up` and owns the containers
+    pub fn pid(&self) -> u32 {
+        self.owner.pid
+    }
+
+    /// Finds a member by name; zones may be written without the trailing dot
+    pub fn member(&self, name: &str) -> Result<&Member> {
+        let with_dot = format!("{}.", name.trim_end_matches('.'));
+        self.members
+            .iter()
+            .find(|member| member.name == name || member.name == with_dot)
+            .ok_or_else(|| {
+                let names = self
+                    .members
+                    .iter()
+                    .map(|member| member.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("unknown member `{name}`; expected one of: {names}").into()
+            })
+    }
+
+    /// Returns the path of the file the logs of `member` are mirrored to
+    pub fn log_path(&self, member: &Member) -> PathBuf {
+        log_path(&member.name)
+    }
+
+    fn is_alive(&self) -> bool {
+        // if we can't tell, err on the side of the environment being up
+        !self.owner.is_dead()
+    }
+
+    fn save(&self) -> Result<()> {
+        let dir = state_dir();
+        fs::create_dir_all(&dir)?;
+        fs::write(
+            dir.join(INVENTORY_FILENAME),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Inventory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "topology {} on network {}",
+            self.topology.display(),
+            self.network
+        )?;
+        for member in &self.members {
+            writeln!(
+                f,
+                "{:<32} {:<16} {}",
+                member.name,
+                member.ipv4_addr.to_string(),
+                member.container_id
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// An environment started by this process
+pub struct Session {
+    inventory: Inventory,
+    deployment: Deployment,
+    _client: Client,
+    _network: Network,
+}
+
+impl Session {
+    /// Starts the topology at `path` and records its inventory
+    ///
+    /// Fails if another environment is up
+    pub fn up(path: &Path) -> Result<Self> {
+        match Inventory::load() {
+            Ok(inventory) => {
+                return Err(format!(
+                    "an environment started by process {} is already up; run `dns-test down` first",
+                    inventory.pid()
+                )
+                .into())
+            }
+            // a stale inventory is overwritten
+            Err(_) => remove_state(&state_dir())?,
+        }
+
+        let topology = Topology::load(path)?;
+        let network = Network::new()?;
+        let deployment = topology.start(&network)?;
+        let client = Client::new(&network)?;
+
+        let resolver = &deployment.resolver;
+        let mut members = vec![
+            Member::new("resolver", resolver.ipv4_addr(), resolver.container_id()),
+            Member::new("client", client.ipv4_addr(), client.container_id()),
+        ];
+        for nameserver in &deployment.graph.nameservers {
+            members.push(Member::new(
+                nameserver.zone().as_str(),
+                nameserver.ipv4_addr(),
+                nameserver.container_id(),
+            ));
+        }
+        for proxy in &deployment.proxies {
+            members.push(Member::new(
+                format!("proxy {}", proxy.zone()),
+                proxy.ipv4_addr(),
+                proxy.container_id(),
+            ));
+        }
+
+        let inventory = Inventory {
+            owner: Owner::current().clone(),
+            topology: path.to_path_buf(),
+            network: network.name().to_string(),
+            members,
+        };
+        inventory.save()?;
+
+        Ok(Self {
+            inventory,
+            deployment,
+            _client: client,
+            _network: network,
+        })
+    }
+
+    pub fn inventory(&self) -> &Inventory {
+        &self.inventory
+    }
+
+    /// Mirrors the logs of the servers into the state directory until `down` is run or
+    /// `interrupted` returns `true`
+    pub fn run(&self, interrupted: impl Fn() -> bool) -> Result<()> {
+        let inventory_path = state_dir().join(INVENTORY_FILENAME);
+        while inventory_path.exists() && !interrupted() {
+            self.mirror_logs()?;
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        Ok(())
+    }
+
+    fn mirror_logs(&self) -> Result<()> {
+        let Deployment {
+            graph,
+            resolver,
+            proxies,
+        } = &self.deployment;
+
+        fs::write(log_path("resolver"), resolver.logs())?;
+        for nameserver in &graph.nameservers {
+            fs::write(log_path(nameserver.zone().as_str()), nameserver.logs())?;
+        }
+        for proxy in proxies {
+            fs::write(log_path(&format!("proxy {}", proxy.zone())), proxy.logs())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = remove_state(&state_dir());
+    }
+}
+
+/// Takes down the environment that is up, waiting for the process that owns it to exit
+pub fn down() -> Result<()> {
+    let path = state_dir().join(INVENTORY_FILENAME);
+    let Ok(json) = fs::read_to_string(&path) else {
+        return Err("no environment is up".into());
+    };
+    let inventory: Option<Inventory> = serde_json::from_str(&json).ok();
+    fs::remove_file(&path)?;
+
+    if let Some(inventory) = inventory {
+        let start = Instant::now();
+        while inventory.is_alive() {
+            if start.elapsed() > DOWN_TIMEOUT {
+                return Err(format!(
+                    "process {} did not take down the environment within {DOWN_TIMEOUT:?}",
+                    inventory.pid()
+                )
+                .into());
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    remove_state(&state_dir())
+}
+
+fn log_path(name: &str) -> PathBuf {
+    let filename = match name {
+        "." => "root".to_string(),
+        _ => name.trim_end_matches('.').replace(' ', "-"),
+    };
+    state_dir().join(format!("{filename}.log"))
+}
+
+/// Removes the inventory and the logs; other files in the state directory are left alone
+fn remove_state(dir: &Path) -> Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    for entry in entries {
+        let path = entry?.path();
+        let is_state = path
+            .file_name()
+            .is_some_and(|name| name == INVENTORY_FILENAME)
+            || path.extension().is_some_and(|extension| extension == "log");
+        if is_state && path.is_file() {
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    // only succeeds if nothing else was put in the directory
+    let _ = fs::remove_dir(dir);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inventory() -> Inventory {
+        Inventory {
+            owner: Owner::current().clone(),
+            topology: PathBuf::from("topologies/bogus-a.toml"),
+            network: "dns-test-1".to_string(),
+            members: vec![
+                Member::new("resolver", Ipv4Addr::new(172, 20, 0, 2), "abc"),
+                Member::new(".", Ipv4Addr::new(172, 20, 0, 3), "def"),
+                Member::new("example.com.", Ipv4Addr::new(172, 20, 0, 4), "ghi"),
+                Member::new("proxy example.com.", Ipv4Addr::new(172, 20, 0, 5), "jkl"),
+            ],
+        }
+    }
+
+    #[test]
+    fn finds_members_by_name() -> Result<()> {
+        let inventory = inventory();
+
+        assert_eq!("abc", inventory.member("resolver")?.container_id);
+        assert_eq!("def", inventory.member(".")?.container_id);
+        assert_eq!("ghi", inventory.member("example.com")?.container_id);
+        assert_eq!("jkl", inventory.member("proxy example.com.")?.container_id);
+        assert!(inventory.member("example.org").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn log_paths() -> Result<()> {
+        let inventory = inventory();
+
+        let root = inventory.log_path(inventory.member(".")?);
+        assert!(root.ends_with("root.log"));
+        let proxy = inventory.log_path(inventory.member("proxy example.com.")?);
+        assert!(proxy.ends_with("proxy-example.com.log"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn only_the_state_files_are_removed() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let keep = dir.path().join("notes.txt");
+        fs::write(dir.path().join(INVENTORY_FILENAME), "{}")?;
+        fs::write(dir.path().join("root.log"), "")?;
+        fs::write(&keep, "")?;
+
+        remove_state(dir.path())?;
+
+        assert!(!dir.path().join(INVENTORY_FILENAME).exists());
+        assert!(!dir.path().join("root.log").exists());
+        assert!(keep.exists());
+
+        // a directory that only held state files is removed
+        fs::remove_file(&keep)?;
+        fs::write(dir.path().join("root.log"), "")?;
+        remove_state(dir.path())?;
+        assert!(!dir.path().exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn inventory_roundtrip() -> Result<()> {
+        let json = serde_json::to_string(&inventory())?;
+        let inventory: Inventory = serde_json::from_str(&json)?;
+
+        assert_eq!(4, inventory.members.len());
+        assert_eq!(Ipv4Addr::new(172, 20, 0, 4), inventory.members[2].ipv4_addr);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,599 @@
+//! Declarative descriptions of DNS graphs
+//!
+//! A `Topology` is loaded from a TOML file that lists the zones, their records and how they are
+//! signed and mutated, plus the settings of the resolver under test. `Topology::start` turns it
+//! into a running `Graph` and `Resolver`
+//!
+//! ``` toml
+//! # zones are signed with these settings unless they set `signed = false`; omit the table to
+//! # leave zones unsigned
+//! [signing]
+//! algorithm = "RSASHA256"
+//! zsk_bits = 1024
+//! ksk_bits = 2048
+//! opt_out = false
+//!
+//! [resolver]
+//! implementation = "subject"
+//! # `start` returns an error if the implementation does not support EDE, e.g. BIND
+//! extended_dns_errors = true
+//!
+//! [[zone]]
+//! name = "example.com."
+//! implementation = "peer"
+//! records = ["www.example.com. 3600 IN A 1.2.3.4"]
+//! # applied after signing, in the syntax of `fuzz::Mutation`
+//! mutations = ["zone drop-rrsigs A"]
+//! ```
+//!
+//! Implementations are `subject` (`DNS_TEST_SUBJECT`), `peer` (`DNS_TEST_PEER`), `unbound`,
+//! `bind` or `hickory $URL`. Zones default to `peer` and the resolver to `subject`.
+//!
+//! The parent zones of the listed zones, up to the root, and `nameservers.com.`, which holds the
+//! A records of all the name servers, are added when missing. Wire mutations are applied by a
+//! `RogueServer::proxy` placed in front of the zone's name server
+
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr};
+
+use crate::fuzz::Mutation;
+use crate::name_server::{Graph, NameServer, SignSettings, Stopped};
+use crate::record::{Record, DS};
+use crate::rogue_server::{self, RogueServer, Rule};
+use crate::{Error, Implementation, LogLevel, Network, Resolver, Result, TrustAnchor, FQDN};
+
+/// A parsed topology file
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Topology {
+    #[serde(default)]
+    signing: Option<Signing>,
+    #[serde(default)]
+    resolver: ResolverSpec,
+    #[serde(default, rename = "zone")]
+    zones: Vec<Zone>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Signing {
+    algorithm: Option<String>,
+    zsk_bits: Option<usize>,
+    ksk_bits: Option<usize>,
+    opt_out: Option<bool>,
+}
+
+impl Signing {
+    fn settings(&self) -> SignSettings {
+        let mut settings = SignSettings::default();
+        if let Some(algorithm) = &self.algorithm {
+            settings.algorithm(algorithm);
+        }
+        if let Some(bits) = self.zsk_bits {
+            settings.zsk_bits(bits);
+        }
+        if let Some(bits) = self.ksk_bits {
+            settings.ksk_bits(bits);
+        }
+        if let Some(opt_out) = self.opt_out {
+            settings.opt_out(opt_out);
+        }
+        settings
+    }
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ResolverSpec {
+    #[serde(default = "subject")]
+    #[serde_as(as = "DisplayFromStr")]
+    implementation: ImplementationSpec,
+    /// whether the root's keys are used as trust anchor; ignored if the root is not signed
+    #[serde(default = "yes")]
+    trust_anchor: bool,
+    #[serde(default)]
+    extended_dns_errors: bool,
+    #[serde(default)]
+    additional_config: Option<String>,
+    #[serde(default)]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    log_level: Option<LogLevel>,
+}
+
+impl Default for ResolverSpec {
+    fn default() -> Self {
+        Self {
+            implementation: subject(),
+            trust_anchor: true,
+            extended_dns_errors: false,
+            additional_config: None,
+            log_level: None,
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Zone {
+    #[serde_as(as = "DisplayFromStr")]
+    name: FQDN,
+    #[serde(default = "peer")]
+    #[serde_as(as = "DisplayFromStr")]
+    implementation: ImplementationSpec,
+    #[serde(default)]
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    records: Vec<Record>,
+    /// defaults to whether the topology has a `[signing]` table
+    #[serde(default)]
+    signed: Option<bool>,
+    /// overrides the topology's `[signing]` table and implies `signed = true`
+    #[serde(default)]
+    signing: Option<Signing>,
+    #[serde(default)]
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    mutations: Vec<Mutation>,
+}
+
+impl Zone {
+    fn new(name: FQDN) -> Self {
+        Self {
+            name,
+            implementation: peer(),
+            records: vec![],
+            signed: None,
+            signing: None,
+            mutations: vec![],
+        }
+    }
+}
+
+/// An implementation as written in a topology file; `subject` and `peer` are resolved when the
+/// topology is started
+#[derive(Clone, Debug)]
+enum ImplementationSpec {
+    Subject,
+    Peer,
+    Other(Implementation),
+}
+
+impl ImplementationSpec {
+    fn resolve(&self) -> Implementation {
+        match self {
+            Self::Subject => crate::SUBJECT.clone(),
+            Self::Peer => crate::PEER.clone(),
+            Self::Other(implementation) => implementation.clone(),
+        }
+    }
+}
+
+impl FromStr for ImplementationSpec {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let spec = match input {
+            "subject" => Self::Subject,
+            "peer" => Self::Peer,
+            "unbound" => Self::Other(Implementation::Unbound),
+            "bind" => Self::Other(Implementation::Bind),
+            _ => match input.strip_prefix("hickory ") {
+                Some(url) => Self::Other(Implementation::Hickory(crate::parse_hickory_repository(
+                    url.trim(),
+                ))),
+                None => return Err(format!("unknown implementation: {input}").into()),
+            },
+        };
+
+        Ok(spec)
+    }
+}
+
+fn subject() -> ImplementationSpec {
+    ImplementationSpec::Subject
+}
+
+fn peer() -> ImplementationSpec {
+    ImplementationSpec::Peer
+}
+
+fn yes() -> bool {
+    true
+}
+
+/// A running `Topology`
+pub struct Deployment {
+    pub graph: Graph,
+    pub resolver: Resolver,
+    /// The proxies that apply the wire mutations, if any
+    pub proxies: Vec<RogueServer<rogue_server::Running>>,
+}
+
+impl Topology {
+    /// Loads the topology file at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let input = fs::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {e}", path.display()))?;
+        input
+            .parse()
+            .map_err(|e| format!("{}: {e}", path.display()).into())
+    }
+
+    /// The zones of the graph, including the ones that are added implicitly, sorted from the
+    /// leaf-most zone to the root
+    pub fn zones(&self) -> Vec<FQDN> {
+        self.complete_zones()
+            .into_iter()
+            .map(|zone| zone.name)
+            .collect()
+    }
+
+    /// Starts the name servers, and the proxies of the zones that have wire mutations, on
+    /// `network` and then a resolver that uses them
+    pub fn start(&self, network: &Network) -> Result<Deployment> {
+        let spec = &self.resolver;
+        let resolver_implementation = spec.implementation.resolve();
+        // checked before any container is started
+        if spec.extended_dns_errors && !resolver_implementation.supports_ede() {
+            return Err(format!(
+                "`resolver.extended_dns_errors` is not supported by `{resolver_implementation}`"
+            )
+            .into());
+        }
+
+        let zones = self.complete_zones();
+
+        let mut nameservers = zones
+            .iter()
+            .map(|zone| {
+                let mut nameserver =
+                    NameServer::new(&zone.implementation.resolve(), zone.name.clone(), network)?;
+                for record in &zone.records {
+                    nameserver.add(record.clone());
+                }
+                Ok(nameserver)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // the nameserver covering `FQDN::NAMESERVERS` needs A records about all the nameservers
+        let glue = nameservers
+            .iter()
+            .filter(|nameserver| nameserver.zone() != &FQDN::NAMESERVERS)
+            .map(NameServer::a)
+            .collect::<Vec<_>>();
+        let nameservers_ns = nameservers
+            .iter_mut()
+            .find(|nameserver| nameserver.zone() == &FQDN::NAMESERVERS)
+            .expect("unreachable: `complete_zones` adds `nameservers.com.`");
+        for a in glue {
+            nameservers_ns.add(a);
+        }
+
+        // the zones are sorted leaf-most first so parents come after their children
+        for index in 0..nameservers.len() - 1 {
+            let (left, right) = nameservers.split_at_mut(index + 1);
+            let child = &left[index];
+            let parent = find_parent(right, child.zone());
+            parent.referral_nameserver(child);
+        }
+
+        let root = nameservers.last().unwrap().root_hint();
+
+        let mut running = vec![];
+        // the DS records of the signed children, by parent zone
+        let mut pending_ds: Vec<(FQDN, DS)> = vec![];
+        let mut trust_anchor = None;
+        let mut proxies = vec![];
+        let mut redirects = vec![];
+        for (zone, mut nameserver) in zones.iter().zip(nameservers) {
+            for (_, ds) in pending_ds.extract_if(.., |(parent, _)| parent == &zone.name) {
+                nameserver.add(ds);
+            }
+
+            let nameserver = match self.sign_settings(zone) {
+                Some(settings) => {
+                    let mut nameserver = nameserver.sign_with(&settings)?;
+                    if let Some(parent) = zone.name.parent() {
+                        pending_ds.push((parent, nameserver.ds().clone()));
+                    } else {
+                        let mut anchor = TrustAnchor::empty();
+                        anchor.add(nameserver.key_signing_key().clone());
+                        anchor.add(nameserver.zone_signing_key().clone());
+                        trust_anchor = Some(anchor);
+                    }
+
+                    for mutation in &zone.mutations {
+                        mutation.amend(&zone.name, &mut nameserver.signed_zone_file_mut().records);
+                    }
+
+                    nameserver.start()?
+                }
+
+                None => {
+                    if zone.mutations.iter().any(|m| m.action().is_none()) {
+                        return Err(format!(
+                            "zone mutations require a signed zone but `{}` is not signed",
+                            zone.name
+                        )
+                        .into());
+                    }
+                    nameserver.start()?
+                }
+            };
+
+            let rules = zone
+                .mutations
+                .iter()
+                .filter_map(Mutation::action)
+                .collect::<Vec<_>>();
+            if !rules.is_empty() {
+                let mut proxy = RogueServer::proxy(&nameserver)?;
+                for action in rules {
+                    proxy.rule(Rule::new(action));
+                }
+                let proxy = proxy.start()?;
+                redirects.push((nameserver.ipv4_addr(), proxy.ipv4_addr()));
+                proxies.push(proxy);
+            }
+
+            running.push(nameserver);
+        }
+
+        let mut settings = Resolver::new(network, root.clone());
+        if let (true, Some(trust_anchor)) = (spec.trust_anchor, &trust_anchor) {
+            settings.trust_anchor(trust_anchor);
+        }
+        if spec.extended_dns_errors {
+            settings.extended_dns_errors();
+        }
+        if let Some(snippet) = &spec.additional_config {
+            settings.additional_config(snippet.clone());
+        }
+        if let Some(log_level) = spec.log_level {
+            settings.log_level(log_level);
+        }
+        for (from, to) in redirects {
+            settings.redirect(from, to);
+        }
+        let resolver = settings.start(&resolver_implementation)?;
+
+        Ok(Deployment {
+            graph: Graph {
+                nameservers: running,
+                root,
+                trust_anchor,
+            },
+            resolver,
+            proxies,
+        })
+    }
+
+    fn sign_settings(&self, zone: &Zone) -> Option<SignSettings> {
+        if let Some(signing) = &zone.signing {
+            return Some(signing.settings());
+        }
+
+        let signed = zone.signed.unwrap_or(self.signing.is_some());
+        signed.then(|| self.signing.clone().unwrap_or_default().settings())
+    }
+
+    /// The listed zones plus the missing ancestors and `nameservers.com.`, sorted from the
+    /// leaf-most zone to the root
+    fn complete_zones(&self) -> Vec<Zone> {
+        let mut zones = self.zones.clone();
+        let implied = self
+            .zones
+            .iter()
+            .flat_map(|zone| ancestors_and_self(&zone.name))
+            .chain(ancestors_and_self(&FQDN::NAMESERVERS))
+            .collect::<Vec<_>>();
+        for name in implied {
+            if !zones.iter().any(|zone| zone.name == name) {
+                zones.push(Zone::new(name));
+            }
+        }
+
+        // stable sort: siblings keep the order of the file
+        zones.sort_by_key(|zone| core::cmp::Reverse(zone.name.num_labels()));
+        zones
+    }
+}
+
+impl FromStr for Topology {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let topology: Self = toml::from_str(input)?;
+
+        for (index, zone) in topology.zones.iter().enumerate() {
+            if topology.zones[..index]
+                .iter()
+                .any(|other| other.name == zone.name)
+            {
+                return Err(format!("zone `{}` is listed more than once", zone.name).into());
+            }
+
+            if let Some(record) = zone
+                .records
+                .iter()
+                .find(|record| !is_in_zone(record.owner(), &zone.name))
+            {
+                return Err(format!("record `{record}` is outside of zone `{}`", zone.name).into());
+            }
+        }
+
+        Ok(topology)
+    }
+}
+
+fn ancestors_and_self(name: &FQDN) -> Vec<FQDN> {
+    let mut names = vec![name.clone()];
+    let mut name = name.clone();
+    while let Some(parent) = name.parent() {
+        names.push(parent.clone());
+        name = parent;
+    }
+    names
+}
+
+fn is_in_zone(owner: &FQDN, zone: &FQDN) -> bool {
+    ancestors_and_self(owner).contains(zone)
+}
+
+fn find_parent<'a>(
+    candidates: &'a mut [NameServer<Stopped>],
+    child: &FQDN,
+) -> &'a mut NameServer<Stopped> {
+    let parent = child.parent().expect("unreachable: the root zone is last");
+    candidates
+        .iter_mut()
+        .find(|nameserver| nameserver.zone() == &parent)
+        .expect("unreachable: `complete_zones` adds all ancestors")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::{Client, DigSettings};
+    use crate::record::RecordType;
+
+    use super::*;
+
+    const EXAMPLE: &str = r#"
+[signing]
+algorithm = "RSASHA256"
+
+[resolver]
+implementation = "unbound"
+extended_dns_errors = true
+
+[[zone]]
+name = "example.com."
+implementation = "bind"
+records = ["www.example.com. 3600 IN A 1.2.3.4"]
+mutations = ["zone drop-rrsigs A", "wire uppercase"]
+
+[[zone]]
+name = "insecure.example.com."
+signed = false
+"#;
+
+    #[test]
+    fn parses_and_completes_zones() -> Result<()> {
+        let topology: Topology = EXAMPLE.parse()?;
+
+        let zones = topology
+            .complete_zones()
+            .into_iter()
+            .map(|zone| zone.name.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            [
+                "insecure.example.com.",
+                "example.com.",
+                "nameservers.com.",
+                "com.",
+                "."
+            ],
+            zones.as_slice()
+        );
+
+        let example = &topology.zones[0];
+        assert!(matches!(
+            example.implementation,
+            ImplementationSpec::Other(Implementation::Bind)
+        ));
+        assert_eq!(2, example.mutations.len());
+        assert!(topology.sign_settings(example).is_some());
+        assert!(topology.sign_settings(&topology.zones[1]).is_none());
+        assert!(matches!(
+            topology.resolver.implementation,
+            ImplementationSpec::Other(Implementation::Unbound)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_topologies() {
+        let out_of_zone = r#"
+[[zone]]
+name = "example.com."
+records = ["www.example.org. 3600 IN A 1.2.3.4"]
+"#;
+        assert!(out_of_zone.parse::<Topology>().is_err());
+
+        let duplicate = r#"
+[[zone]]
+name = "example.com."
+
+[[zone]]
+name = "example.com."
+"#;
+        assert!(duplicate.parse::<Topology>().is_err());
+
+        let unknown_field = r#"
+[[zone]]
+name = "example.com."
+sign = true
+"#;
+        assert!(unknown_field.parse::<Topology>().is_err());
+
+        let unknown_mutation = r#"
+[[zone]]
+name = "example.com."
+mutations = ["zone explode"]
+"#;
+        assert!(unknown_mutation.parse::<Topology>().is_err());
+    }
+
+    #[test]
+    fn unsupported_resolver_settings_are_errors() -> Result<()> {
+        let topology: Topology = r#"
+[resolver]
+implementation = "bind"
+extended_dns_errors = true
+"#
+        .parse()?;
+
+        let error = topology.start(&Network::new()?).err().unwrap().to_string();
+        assert!(error.contains("`resolver.extended_dns_errors`"), "{error}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn starts_signed_topology() -> Result<()> {
+        let topology: Topology = r#"
+[signing]
+
+[[zone]]
+name = "example.com."
+records = ["www.example.com. 3600 IN A 1.2.3.4"]
+"#
+        .parse()?;
+
+        let network = Network::new()?;
+        let Deployment {
+            graph, resolver, ..
+        } = topology.start(&network)?;
+        assert!(graph.trust_anchor.is_some());
+
+        let client = Client::new(&network)?;
+        let output = client.dig(
+            *DigSettings::default().recurse().authentic_data(),
+            resolver.ipv4_addr(),
+            RecordType::A,
+            &FQDN("www.example.com.")?,
+        )?;
+
+        assert!(output.status.is_noerror());
+        assert!(output.flags.authenticated_data);
+
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@ use core::fmt;
 
 use crate::record::DNSKEY;
 
+#[derive(Clone)]
 pub struct TrustAnchor {
     keys: Vec<DNSKEY>,
 }
@@ -0,0 +1,43 @@
+use base64::prelude::*;
+
+use crate::{Result, FQDN};
+
+/// A TSIG (RFC8945) key used to authenticate zone transfers and dynamic updates
+#[derive(Clone, Debug)]
+pub struct TsigKey {
+    name: FQDN,
+    secret: String,
+}
+
+impl TsigKey {
+    /// The HMAC algorithm used by all keys
+    pub const ALGORITHM: &'static str = "hmac-sha256";
+
+    /// Generates a key named `name` with a random 256-bit secret
+    ///
+    /// The secret comes from a non-cryptographic generator: it's good enough to authenticate the
+    /// traffic of a test but must not be used to protect anything else
+    pub fn generate(name: FQDN) -> Result<Self> {
+        let mut secret = [0; 32];
+        secret.fill_with(|| fastrand::u8(..));
+
+        Ok(Self {
+            name,
+            secret: BASE64_STANDARD.encode(secret),
+        })
+    }
+
+    pub fn name(&self) -> &FQDN {
+        &self.name
+    }
+
+    /// The base64-encoded secret
+    pub fn secret(&self) -> &str {
+        &self.secret
+    }
+
+    /// formats the key in the `algorithm:name:secret` form `dig -y` and `nsupdate -y` expect
+    pub(crate) fn as_arg(&self) -> String {
+        format!("{}:{}:{}", Self::ALGORITHM, self.name, self.secret)
+    }
+}
@@ -42,6 +42,30 @@ impl ZoneFile {
     pub(crate) fn origin(&self) -> &FQDN {
         &self.origin
     }
+
+    /// Parses the output of `dig +noall +answer AXFR`
+    ///
+    /// Records that `Record` cannot represent, e.g. those of types it does not model, are skipped
+    pub(crate) fn parse_axfr(output: &str) -> Result<Self> {
+        let mut lines = output
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+
+        // errors, like "; Transfer failed.", are reported as comments
+        if let Some(error) = lines.iter().find(|line| line.starts_with(';')) {
+            return Err(error.trim_start_matches(';').trim().into());
+        }
+
+        // the SOA record is both the first and the last record of the transfer
+        if lines.len() > 1 {
+            lines.pop();
+        }
+        lines.retain(|line| Record::is_modelled(line));
+
+        lines.join("\n").parse()
+    }
 }
 
 impl fmt::Display for ZoneFile {
@@ -223,4 +247,49 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn axfr() -> Result<()> {
+        // $ dig +noall +answer @1.2.3.4 AXFR com.
+        let input = "
+com.			86400	IN	SOA	primary0.nameservers.com. admin0.nameservers.com. 2024010101 1800 900 604800 86400
+com.			86400	IN	NS	primary0.nameservers.com.
+primary0.nameservers.com. 86400	IN	A	172.21.0.2
+com.			86400	IN	SOA	primary0.nameservers.com. admin0.nameservers.com. 2024010101 1800 900 604800 86400
+";
+
+        let zone = ZoneFile::parse_axfr(input)?;
+        assert_eq!(FQDN::COM, zone.origin);
+        assert_eq!(2024010101, zone.soa.settings.serial);
+        assert_eq!(2, zone.records.len());
+
+        let Err(error) = ZoneFile::parse_axfr("; Transfer failed.") else {
+            panic!("a failed transfer should be reported as an error")
+        };
+        assert_eq!("Transfer failed.", error.to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn axfr_skips_unmodelled_records() -> Result<()> {
+        // the signing-state records of an inline-signed BIND zone, and the records that refer to
+        // their type
+        let input = "
+com.			86400	IN	SOA	primary0.nameservers.com. admin0.nameservers.com. 2024010101 1800 900 604800 86400
+com.			0	IN	TYPE65534 \\# 5 0D4A4B0001
+com.			0	IN	RRSIG	TYPE65534 8 1 0 20240201000000 20240101000000 19018 com. c2lnbmF0dXJl
+com.			86400	IN	NS	primary0.nameservers.com.
+com.			86400	IN	MX	10 mail.com.
+q8d6amsnpgbv5ri0u1nikknfn7l5v9v0.com. 86400 IN NSEC3 1 0 0 - Q8D6AMSNPGBV5RI0U1NIKKNFN7L5V9V1 NS SOA RRSIG DNSKEY NSEC3PARAM TYPE65534
+com.			86400	IN	SOA	primary0.nameservers.com. admin0.nameservers.com. 2024010101 1800 900 604800 86400
+";
+
+        let zone = ZoneFile::parse_axfr(input)?;
+        let [Record::NS(_)] = zone.records.as_slice() else {
+            panic!("expected only the NS record; got {:?}", zone.records)
+        };
+
+        Ok(())
+    }
 }
@@ -0,0 +1,327 @@
+//! The configuration file: the zone to serve and the rules that alter the responses
+//!
+//! Each line contains one directive; empty lines and lines that start with `#` are ignored
+//!
+//! ``` text
+//! zone example.com.
+//! record example.com. 86400 IN SOA ns.example.com. admin.example.com. 1 10800 3600 604800 86400
+//! record ns.example.com. 86400 IN A 192.0.2.1
+//! # rule <qname|*> <qtype|*> <times|*> <action> [arguments]
+//! rule www.example.com. A 1 drop
+//! rule * * * delay 500
+//! ```
+//!
+//! All the rules that match a query are applied, in order. A rule with a `times` limit stops
+//! matching once it has been applied that many times
+//!
+//! With the `upstream <ipv4 address>` directive the server acts as a proxy: queries are
+//! forwarded, over TCP, to that server and the rules alter its responses. The zone then needs no
+//! records
+
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use crate::wire::{self, Flag, Record, Result, Section};
+
+pub struct Config {
+    /// lowercase, with a trailing dot
+    pub zone: String,
+    pub records: Vec<Record>,
+    pub rules: Vec<Rule>,
+    /// the server queries are forwarded to, if any
+    pub upstream: Option<Ipv4Addr>,
+}
+
+impl Config {
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut zone = None;
+        let mut records = vec![];
+        let mut rules = vec![];
+        let mut upstream = None;
+
+        for (index, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (directive, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+            let result = match directive {
+                "zone" => {
+                    zone = Some(rest.to_ascii_lowercase());
+                    Ok(())
+                }
+                "record" => Record::parse(rest).map(|record| records.push(record)),
+                "rule" => Rule::parse(rest).map(|rule| rules.push(rule)),
+                "upstream" => rest
+                    .parse()
+                    .map(|addr| upstream = Some(addr))
+                    .map_err(|_| format!("invalid upstream address `{rest}`")),
+                _ => Err(format!("unknown directive `{directive}`")),
+            };
+
+            result.map_err(|e| format!("line {}: {e}", index + 1))?;
+        }
+
+        let zone = zone.ok_or("missing `zone` directive")?;
+        if upstream.is_none()
+            && !records
+                .iter()
+                .any(|record| record.name == zone && record.rtype == SOA)
+        {
+            return Err(format!("zone `{zone}` has no SOA record"));
+        }
+
+        Ok(Self {
+            zone,
+            records,
+            rules,
+            upstream,
+        })
+    }
+}
+
+const SOA: u16 = 6;
+
+pub struct Rule {
+    /// `None` matches any name
+    qname: Option<String>,
+    /// `None` matches any type
+    qtype: Option<u16>,
+    /// `None` means no limit
+    pub remaining: Option<u32>,
+    pub action: Action,
+}
+
+impl Rule {
+    fn parse(input: &str) -> Result<Self> {
+        let mut columns = input.split_whitespace();
+        let (Some(qname), Some(qtype), Some(times), Some(action)) = (
+            columns.next(),
+            columns.next(),
+            columns.next(),
+            columns.next(),
+        ) else {
+            return Err(format!("expected at least 4 columns: {input}"));
+        };
+
+        let qname = (qname != "*").then(|| qname.to_ascii_lowercase());
+        let qtype = if qtype == "*" {
+            None
+        } else {
+            Some(wire::parse_type(qtype)?)
+        };
+        let remaining = if times == "*" {
+            None
+        } else {
+            Some(
+                times
+                    .parse()
+                    .map_err(|_| format!("invalid number of times `{times}`"))?,
+            )
+        };
+
+        let arguments = columns.collect::<Vec<_>>();
+        let argument = || {
+            arguments
+                .first()
+                .copied()
+                .ok_or_else(|| format!("action `{action}` requires an argument"))
+        };
+        // the record is the rest of the line, after the action
+        let record = || Record::parse(wire::skip_columns(input, 4));
+
+        let action = match action {
+            "drop" => Action::Drop,
+            "delay" => {
+                let millis = argument()?;
+                let millis = millis
+                    .parse()
+                    .map_err(|_| format!("invalid delay `{millis}`"))?;
+                Action::Delay(Duration::from_millis(millis))
+            }
+            "wrong-id" => Action::WrongId,
+            "truncate" => Action::Truncate,
+            "set-flag" => Action::SetFlag(Flag::parse(argument()?)?),
+            "clear-flag" => Action::ClearFlag(Flag::parse(argument()?)?),
+            "rcode" => {
+                let rcode = argument()?;
+                let rcode = rcode
+                    .parse()
+                    .ok()
+                    .filter(|rcode| *rcode < 16)
+                    .ok_or_else(|| format!("invalid rcode `{rcode}`"))?;
+                Action::Rcode(rcode)
+            }
+            "answer" => Action::Answer(record()?),
+            "authority" => Action::Authority(record()?),
+            "additional" => Action::Additional(record()?),
+            "clear" => Action::Clear,
+            "malformed" => Action::Malformed,
+            "raw" => Action::Raw(wire::decode_hex(&arguments.concat())?),
+            "flip-bit" => {
+                let bit = argument()?;
+                let bit = bit
+                    .parse()
+                    .map_err(|_| format!("invalid bit offset `{bit}`"))?;
+                Action::FlipBit(bit)
+            }
+            "drop-rrsigs" => Action::DropRrsigs,
+            "reverse" => Action::Reverse,
+            "move" => {
+                let [from, to] = arguments[..] else {
+                    return Err("action `move` requires 2 sections".to_string());
+                };
+                Action::Move(Section::parse(from)?, Section::parse(to)?)
+            }
+            "ttl" => {
+                let ttl = argument()?;
+                let ttl = ttl.parse().map_err(|_| format!("invalid TTL `{ttl}`"))?;
+                Action::Ttl(ttl)
+            }
+            "uppercase" => Action::Uppercase,
+            "rename" => {
+                let [from, to] = arguments[..] else {
+                    return Err("action `rename` requires 2 names".to_string());
+                };
+                Action::Rename(from.to_ascii_lowercase(), to.to_string())
+            }
+            _ => return Err(format!("unknown action `{action}`")),
+        };
+
+        Ok(Self {
+            qname,
+            qtype,
+            remaining,
+            action,
+        })
+    }
+
+    /// Returns `true` if the rule applies to the query, in which case its use is counted
+    pub fn matches(&mut self, qname: &str, qtype: u16) -> bool {
+        let matches = self.qname.as_deref().is_none_or(|name| name == qname)
+            && self.qtype.is_none_or(|rtype| rtype == qtype)
+            && self.remaining != Some(0);
+
+        if matches {
+            if let Some(remaining) = &mut self.remaining {
+                *remaining -= 1;
+            }
+        }
+
+        matches
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// Do not reply
+    Drop,
+    /// Wait before replying
+    Delay(Duration),
+    /// Reply with an ID that does not match the query's
+    WrongId,
+    /// Over UDP, reply with an empty response that has the TC flag set
+    Truncate,
+    SetFlag(Flag),
+    ClearFlag(Flag),
+    Rcode(u8),
+    /// Append a record to the answer section
+    Answer(Record),
+    /// Append a record to the authority section
+    Authority(Record),
+    /// Append a record to the additional section
+    Additional(Record),
+    /// Remove all the records from the response
+    Clear,
+    /// Reply with a message whose header announces one more answer record than it contains
+    Malformed,
+    /// Reply with these bytes instead
+    Raw(Vec<u8>),
+    /// Flip the bit at this offset, counted from the start of the encoded message, wrapping
+    /// around at its end
+    FlipBit(usize),
+    /// Remove all the RRSIG records from the response
+    DropRrsigs,
+    /// Reverse the order of the records in each section
+    Reverse,
+    /// Move all the records of a section to the end of another
+    Move(Section, Section),
+    /// Set the TTL of all the records
+    Ttl(u32),
+    /// Write the owner names of all the records in uppercase
+    Uppercase,
+    /// Rename the records owned by the first name
+    Rename(String, String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_config() -> Result<()> {
+        let config = Config::parse(
+            "zone Example.COM.
+# comment
+record example.com. 86400 IN SOA ns.example.com. admin.example.com. 1 2 3 4 5
+
+rule www.example.com. A 1 drop
+rule * * * delay 250
+rule * TXT * answer example.com. 60 IN TXT \"hello world\"
+rule * * * set-flag tc
+rule * * * raw dead beef
+rule * * * move answer authority",
+        )?;
+
+        assert_eq!("example.com.", config.zone);
+        assert_eq!(1, config.records.len());
+
+        let [drop, delay, answer, flag, raw, move_] =
+            config.rules.try_into().map_err(|_| "6 rules")?;
+        assert_eq!(Action::Drop, drop.action);
+        assert_eq!(Some(1), drop.remaining);
+        assert_eq!(Action::Delay(Duration::from_millis(250)), delay.action);
+        let Action::Answer(record) = answer.action else {
+            return Err("expected an answer action".into());
+        };
+        assert_eq!(b"\x0bhello world".to_vec(), record.rdata);
+        assert_eq!(Action::SetFlag(Flag::Tc), flag.action);
+        assert_eq!(Action::Raw(vec![0xde, 0xad, 0xbe, 0xef]), raw.action);
+        assert_eq!(
+            Action::Move(Section::Answer, Section::Authority),
+            move_.action
+        );
+        assert!(config.upstream.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn limited_rule_stops_matching() -> Result<()> {
+        let mut rule = Rule::parse("www.example.com. A 2 drop")?;
+
+        assert!(!rule.matches("www.example.com.", 28));
+        assert!(!rule.matches("example.com.", 1));
+        assert!(rule.matches("www.example.com.", 1));
+        assert!(rule.matches("www.example.com.", 1));
+        assert!(!rule.matches("www.example.com.", 1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn zone_requires_soa() {
+        assert!(Config::parse("zone example.com.").is_err());
+    }
+
+    #[test]
+    fn proxy_requires_no_records() -> Result<()> {
+        let config = Config::parse("zone example.com.\nupstream 192.0.2.1")?;
+
+        assert_eq!(Some(Ipv4Addr::new(192, 0, 2, 1)), config.upstream);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,367 @@
+//! A misbehaving authoritative name server
+//!
+//! It serves a single zone over UDP and TCP, or forwards the queries to another server, and
+//! alters its responses according to the rules in its configuration file; see the `config`
+//! module for the file format. `dns-test` uses it to check how resolvers cope with broken
+//! authoritative servers
+
+mod config;
+mod wire;
+
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::{env, fmt, fs, mem, process, thread};
+
+use crate::config::{Action, Config};
+use crate::wire::{Flag, Query, Response};
+
+const DEFAULT_CONFIG_PATH: &str = "/etc/rogue.conf";
+const PORT: u16 = 53;
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(2);
+
+const RCODE_SERVFAIL: u8 = 2;
+const RCODE_NXDOMAIN: u8 = 3;
+const RCODE_REFUSED: u8 = 5;
+const TYPE_CNAME: u16 = 5;
+const TYPE_SOA: u16 = 6;
+const TYPE_ANY: u16 = 255;
+
+fn main() {
+    if let Err(e) = run() {
+        // `dns-test` only collects stdout
+        println!("rogue-server: {e}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
+    let config = fs::read_to_string(&path).map_err(|e| format!("could not read {path}: {e}"))?;
+    let config = Config::parse(&config).map_err(|e| format!("{path}: {e}"))?;
+    let zone = config.zone.clone();
+    let config = Arc::new(Mutex::new(config));
+
+    let address = SocketAddr::from(([0, 0, 0, 0], PORT));
+    let udp = UdpSocket::bind(address).map_err(|e| format!("could not bind UDP socket: {e}"))?;
+    let tcp = TcpListener::bind(address).map_err(|e| format!("could not bind TCP socket: {e}"))?;
+
+    {
+        let config = config.clone();
+        thread::spawn(move || {
+            for stream in tcp.incoming().flatten() {
+                let config = config.clone();
+                thread::spawn(move || {
+                    if let Err(e) = serve_tcp(&config, stream) {
+                        println!("TCP connection error: {e}");
+                    }
+                });
+            }
+        });
+    }
+
+    println!("serving `{zone}` on port {PORT}");
+
+    let mut buffer = [0; u16::MAX as usize];
+    loop {
+        let (len, peer) = udp
+            .recv_from(&mut buffer)
+            .map_err(|e| format!("could not receive: {e}"))?;
+
+        let Some(reply) = handle(&config, &buffer[..len], Transport::Udp, peer) else {
+            continue;
+        };
+
+        if reply.delay.is_zero() {
+            let _ = udp.send_to(&reply.bytes, peer);
+        } else {
+            let udp = udp
+                .try_clone()
+                .map_err(|e| format!("could not clone socket: {e}"))?;
+            thread::spawn(move || {
+                thread::sleep(reply.delay);
+                let _ = udp.send_to(&reply.bytes, peer);
+            });
+        }
+    }
+}
+
+fn serve_tcp(config: &Mutex<Config>, mut stream: TcpStream) -> io::Result<()> {
+    let peer = stream.peer_addr()?;
+    loop {
+        let mut len = [0; 2];
+        match stream.read_exact(&mut len) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            result => result?,
+        }
+
+        let mut message = vec![0; usize::from(u16::from_be_bytes(len))];
+        stream.read_exact(&mut message)?;
+
+        if let Some(reply) = handle(config, &message, Transport::Tcp, peer) {
+            thread::sleep(reply.delay);
+            let len = u16::try_from(reply.bytes.len()).unwrap_or(u16::MAX);
+            stream.write_all(&len.to_be_bytes())?;
+            stream.write_all(&reply.bytes)?;
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Transport {
+    Tcp,
+    Udp,
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Tcp => "TCP",
+            Self::Udp => "UDP",
+        })
+    }
+}
+
+struct Reply {
+    bytes: Vec<u8>,
+    delay: Duration,
+}
+
+/// Answers `message` from the zone and then applies the rules that match it
+///
+/// Returns `None` if no reply must be sent
+fn handle(
+    config: &Mutex<Config>,
+    message: &[u8],
+    transport: Transport,
+    peer: SocketAddr,
+) -> Option<Reply> {
+    let query = match Query::parse(message) {
+        Ok(query) => query,
+        Err(e) => {
+            println!("{peer} {transport}: ignoring message: {e}");
+            return None;
+        }
+    };
+
+    // do not hold the lock while waiting for the upstream server
+    let upstream = config.lock().unwrap().upstream;
+    let mut response = match upstream {
+        Some(upstream) => forward(upstream, message, &query),
+        None => answer(&config.lock().unwrap(), &query),
+    };
+
+    let mut config = config.lock().unwrap();
+
+    let mut no_reply = false;
+    let mut delay = Duration::ZERO;
+    let mut wrong_id = false;
+    let mut malformed = false;
+    let mut raw = None;
+    let mut flipped_bits = vec![];
+    let mut applied = vec![];
+    for rule in &mut config.rules {
+        if !rule.matches(&query.qname, query.qtype) {
+            continue;
+        }
+
+        applied.push(format!("{:?}", rule.action));
+        match &rule.action {
+            Action::Drop => no_reply = true,
+            Action::Delay(duration) => delay += *duration,
+            Action::WrongId => wrong_id = true,
+            Action::Truncate => {
+                if transport == Transport::Udp {
+                    response.truncate();
+                }
+            }
+            Action::SetFlag(flag) => response.set_flag(*flag, true),
+            Action::ClearFlag(flag) => response.set_flag(*flag, false),
+            Action::Rcode(rcode) => response.rcode = *rcode,
+            Action::Answer(record) => response.answer.push(record.clone()),
+            Action::Authority(record) => response.authority.push(record.clone()),
+            Action::Additional(record) => response.additional.push(record.clone()),
+            Action::Clear => {
+                response.answer.clear();
+                response.authority.clear();
+                response.additional.clear();
+            }
+            Action::Malformed => malformed = true,
+            Action::Raw(bytes) => raw = Some(bytes.clone()),
+            Action::FlipBit(bit) => flipped_bits.push(*bit),
+            Action::DropRrsigs => {
+                for section in [
+                    &mut response.answer,
+                    &mut response.authority,
+                    &mut response.additional,
+                ] {
+                    section.retain(|record| record.rtype != wire::TYPE_RRSIG);
+                }
+            }
+            Action::Reverse => {
+                response.answer.reverse();
+                response.authority.reverse();
+                response.additional.reverse();
+            }
+            Action::Move(from, to) => {
+                let records = mem::take(response.section_mut(*from));
+                response.section_mut(*to).extend(records);
+            }
+            Action::Ttl(ttl) => response.records_mut().for_each(|record| record.ttl = *ttl),
+            Action::Uppercase => response
+                .records_mut()
+                .for_each(|record| record.name.make_ascii_uppercase()),
+            Action::Rename(from, to) => response
+                .records_mut()
+                .filter(|record| record.name == *from)
+                .for_each(|record| record.name.clone_from(to)),
+        }
+    }
+    drop(config);
+
+    println!(
+        "{peer} {transport} {} {}: applied rules: [{}]",
+        query.qname,
+        wire::type_name(query.qtype),
+        applied.join(", ")
+    );
+
+    if no_reply {
+        return None;
+    }
+
+    let bytes = if let Some(raw) = raw {
+        raw
+    } else {
+        if wrong_id {
+            response.id = !response.id;
+        }
+
+        let mut bytes = encode(&response)?;
+        if transport == Transport::Udp && bytes.len() > query.max_udp_size() {
+            response.truncate();
+            bytes = encode(&response)?;
+        }
+
+        if malformed {
+            // announce one more answer record than there is
+            let ancount = u16::from_be_bytes([bytes[6], bytes[7]]).wrapping_add(1);
+            bytes[6..8].copy_from_slice(&ancount.to_be_bytes());
+        }
+
+        for bit in flipped_bits {
+            let bit = bit % (8 * bytes.len());
+            bytes[bit / 8] ^= 0x80 >> (bit % 8);
+        }
+
+        bytes
+    };
+
+    Some(Reply { bytes, delay })
+}
+
+fn encode(response: &Response) -> Option<Vec<u8>> {
+    response
+        .encode()
+        .map_err(|e| println!("could not encode response: {e}"))
+        .ok()
+}
+
+/// Forwards `message`, i.e. `query`, to `upstream` over TCP and returns its response, or a
+/// SERVFAIL response if that fails
+fn forward(upstream: Ipv4Addr, message: &[u8], query: &Query) -> Response {
+    let exchange = || -> Result<Vec<u8>, String> {
+        let address = SocketAddr::from((upstream, PORT));
+        let mut stream = TcpStream::connect_timeout(&address, UPSTREAM_TIMEOUT)
+            .map_err(|e| format!("could not connect: {e}"))?;
+        stream
+            .set_read_timeout(Some(UPSTREAM_TIMEOUT))
+            .map_err(|e| e.to_string())?;
+
+        let len = u16::try_from(message.len()).map_err(|_| "query is too long")?;
+        stream
+            .write_all(&len.to_be_bytes())
+            .and_then(|()| stream.write_all(message))
+            .map_err(|e| format!("could not send query: {e}"))?;
+
+        let mut len = [0; 2];
+        stream
+            .read_exact(&mut len)
+            .map_err(|e| format!("could not receive response: {e}"))?;
+        let mut reply = vec![0; usize::from(u16::from_be_bytes(len))];
+        stream
+            .read_exact(&mut reply)
+            .map_err(|e| format!("could not receive response: {e}"))?;
+        Ok(reply)
+    };
+
+    match exchange().and_then(|reply| Response::parse(&reply, query)) {
+        Ok(response) => response,
+        Err(e) => {
+            println!("upstream {upstream}: {e}");
+            let mut response = Response::new(query);
+            response.rcode = RCODE_SERVFAIL;
+            response
+        }
+    }
+}
+
+/// Returns the response a well-behaved authoritative server would send
+fn answer(config: &Config, query: &Query) -> Response {
+    let mut response = Response::new(query);
+
+    let in_zone = query.qname == config.zone
+        || config.zone == "."
+        || query.qname.ends_with(&format!(".{}", config.zone));
+    if !in_zone {
+        response.rcode = RCODE_REFUSED;
+        return response;
+    }
+
+    response.set_flag(Flag::Aa, true);
+
+    let at_name = config
+        .records
+        .iter()
+        .filter(|record| record.name == query.qname)
+        .collect::<Vec<_>>();
+    let matching = at_name
+        .iter()
+        .filter(|record| query.qtype == TYPE_ANY || record.rtype == query.qtype)
+        .map(|record| (*record).clone())
+        .collect::<Vec<_>>();
+
+    if !matching.is_empty() {
+        response.answer = matching;
+        return response;
+    }
+
+    if let Some(cname) = at_name.iter().find(|record| record.rtype == TYPE_CNAME) {
+        response.answer.push((*cname).clone());
+        return response;
+    }
+
+    // names that own no records but have descendants (empty non-terminals) exist
+    let suffix = format!(".{}", query.qname);
+    let exists = !at_name.is_empty()
+        || config
+            .records
+            .iter()
+            .any(|record| record.name.ends_with(&suffix));
+    if !exists {
+        response.rcode = RCODE_NXDOMAIN;
+    }
+
+    if let Some(soa) = config
+        .records
+        .iter()
+        .find(|record| record.name == config.zone && record.rtype == TYPE_SOA)
+    {
+        response.authority.push(soa.clone());
+    }
+
+    response
+}
@@ -0,0 +1,780 @@
+//! DNS wire format: parsing queries and responses and encoding responses
+//!
+//! Only what a (misbehaving) authoritative server, or proxy, needs is implemented. Responses are
+//! encoded without name compression
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+pub type Result<T> = std::result::Result<T, String>;
+
+const HEADER_LEN: usize = 12;
+const CLASS_IN: u16 = 1;
+const TYPE_OPT: u16 = 41;
+pub const TYPE_RRSIG: u16 = 46;
+/// DNSSEC OK flag, in the TTL field of the OPT record
+const EDNS_DO: u32 = 0x8000;
+
+/// Payload size advertised in the OPT record of responses
+const EDNS_UDP_SIZE: u16 = 1232;
+/// Maximum size of a UDP response to a query without an OPT record
+const DEFAULT_UDP_SIZE: usize = 512;
+
+const TYPES: &[(&str, u16)] = &[
+    ("A", 1),
+    ("NS", 2),
+    ("CNAME", 5),
+    ("SOA", 6),
+    ("PTR", 12),
+    ("MX", 15),
+    ("TXT", 16),
+    ("AAAA", 28),
+    ("SRV", 33),
+    ("DS", 43),
+    ("RRSIG", 46),
+    ("NSEC", 47),
+    ("DNSKEY", 48),
+    ("NSEC3", 50),
+    ("NSEC3PARAM", 51),
+    ("AXFR", 252),
+    ("ANY", 255),
+];
+
+/// Parses a record type mnemonic, e.g. `A`, or its RFC 3597 form, e.g. `TYPE1`
+pub fn parse_type(input: &str) -> Result<u16> {
+    if let Some((_, code)) = TYPES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(input))
+    {
+        return Ok(*code);
+    }
+
+    input
+        .strip_prefix("TYPE")
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| format!("unknown record type: {input}"))
+}
+
+pub fn type_name(code: u16) -> String {
+    TYPES
+        .iter()
+        .find(|(_, candidate)| *candidate == code)
+        .map_or_else(|| format!("TYPE{code}"), |(name, _)| name.to_string())
+}
+
+/// A query, i.e. the header and the first entry of the question section
+pub struct Query {
+    pub id: u16,
+    pub rd: bool,
+    /// The question name in lowercase, for matching against the zone and the rules
+    pub qname: String,
+    /// The question name as sent, which responses echo; resolvers that randomise its case (DNS
+    /// 0x20) check that it's preserved
+    pub question_name: String,
+    pub qtype: u16,
+    pub qclass: u16,
+    /// UDP payload size advertised in the OPT record, if any
+    pub udp_size: Option<u16>,
+    /// The DO flag of the OPT record
+    pub dnssec_ok: bool,
+}
+
+impl Query {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_LEN {
+            return Err(format!("message is too short: {} bytes", bytes.len()));
+        }
+
+        let id = u16::from_be_bytes([bytes[0], bytes[1]]);
+        let flags = u16::from_be_bytes([bytes[2], bytes[3]]);
+        let qdcount = u16::from_be_bytes([bytes[4], bytes[5]]);
+        let ancount = u16::from_be_bytes([bytes[6], bytes[7]]);
+        let nscount = u16::from_be_bytes([bytes[8], bytes[9]]);
+        let arcount = u16::from_be_bytes([bytes[10], bytes[11]]);
+
+        if flags & 0x8000 != 0 {
+            return Err("message is a response".to_string());
+        }
+
+        if qdcount != 1 {
+            return Err(format!("expected one question; got {qdcount}"));
+        }
+
+        let mut offset = HEADER_LEN;
+        let question_name = parse_name(bytes, &mut offset)?;
+        let qtype = read_u16(bytes, &mut offset)?;
+        let qclass = read_u16(bytes, &mut offset)?;
+
+        // look for an OPT record in the additional section
+        for _ in 0..u32::from(ancount) + u32::from(nscount) {
+            skip_record(bytes, &mut offset)?;
+        }
+
+        let mut udp_size = None;
+        let mut dnssec_ok = false;
+        for _ in 0..arcount {
+            let start = offset;
+            parse_name(bytes, &mut offset)?;
+            let rtype = read_u16(bytes, &mut offset)?;
+            let class = read_u16(bytes, &mut offset)?;
+            let ttl = read_u32(bytes, &mut offset)?;
+            offset = start;
+            skip_record(bytes, &mut offset)?;
+
+            if rtype == TYPE_OPT {
+                udp_size = Some(class);
+                dnssec_ok = ttl & EDNS_DO != 0;
+            }
+        }
+
+        Ok(Self {
+            id,
+            rd: flags & 0x0100 != 0,
+            qname: question_name.to_ascii_lowercase(),
+            question_name,
+            qtype,
+            qclass,
+            udp_size,
+            dnssec_ok,
+        })
+    }
+
+    /// Maximum size of a response to this query sent over UDP
+    pub fn max_udp_size(&self) -> usize {
+        self.udp_size.map_or(DEFAULT_UDP_SIZE, |size| {
+            usize::from(size).max(DEFAULT_UDP_SIZE)
+        })
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: &mut usize) -> Result<u16> {
+    let slice = bytes
+        .get(*offset..*offset + 2)
+        .ok_or("unexpected end of message")?;
+    *offset += 2;
+    Ok(u16::from_be_bytes([slice[0], slice[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: &mut usize) -> Result<u32> {
+    let high = read_u16(bytes, offset)?;
+    let low = read_u16(bytes, offset)?;
+    Ok(u32::from(high) << 16 | u32::from(low))
+}
+
+/// Parses a possibly compressed domain name and returns it, as is, with a trailing dot
+fn parse_name(bytes: &[u8], offset: &mut usize) -> Result<String> {
+    const MAX_POINTERS: usize = 64;
+
+    let mut name = String::new();
+    let mut position = *offset;
+    let mut jumped = false;
+    let mut pointers = 0;
+    loop {
+        let len = *bytes.get(position).ok_or("unexpected end of message")?;
+        match len {
+            0 => {
+                if !jumped {
+                    *offset = position + 1;
+                }
+                break;
+            }
+
+            len if len & 0xc0 == 0xc0 => {
+                let low = *bytes.get(position + 1).ok_or("unexpected end of message")?;
+                if !jumped {
+                    *offset = position + 2;
+                }
+                jumped = true;
+                pointers += 1;
+                if pointers > MAX_POINTERS {
+                    return Err("too many compression pointers".to_string());
+                }
+                position = usize::from(u16::from_be_bytes([len & 0x3f, low]));
+            }
+
+            len => {
+                let start = position + 1;
+                let label = bytes
+                    .get(start..start + usize::from(len))
+                    .ok_or("unexpected end of message")?;
+                name.push_str(&String::from_utf8_lossy(label));
+                name.push('.');
+                position = start + usize::from(len);
+            }
+        }
+    }
+
+    if name.is_empty() {
+        name.push('.');
+    }
+
+    Ok(name)
+}
+
+fn skip_record(bytes: &[u8], offset: &mut usize) -> Result<()> {
+    parse_name(bytes, offset)?;
+    // type, class and TTL
+    *offset += 8;
+    let rdlength = read_u16(bytes, offset)?;
+    *offset += usize::from(rdlength);
+    if *offset > bytes.len() {
+        return Err("unexpected end of message".to_string());
+    }
+    Ok(())
+}
+
+fn encode_name(name: &str, buffer: &mut Vec<u8>) -> Result<()> {
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() {
+            continue;
+        }
+
+        let len = u8::try_from(label.len())
+            .ok()
+            .filter(|len| *len < 64)
+            .ok_or_else(|| format!("label is too long: {label}"))?;
+        buffer.push(len);
+        buffer.extend_from_slice(label.as_bytes());
+    }
+    buffer.push(0);
+    Ok(())
+}
+
+/// A resource record
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    /// lowercase, with a trailing dot
+    pub name: String,
+    pub ttl: u32,
+    pub rtype: u16,
+    pub rdata: Vec<u8>,
+}
+
+impl Record {
+    /// Parses a record in presentation format, e.g. `example.com. 3600 IN A 192.0.2.1`
+    ///
+    /// The rdata of A, AAAA, NS, CNAME, PTR, MX, SOA and TXT records can be given in their usual
+    /// form; the rdata of any type can be given in the RFC 3597 form, e.g. `\# 4 c0000201`
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut columns = input.split_whitespace();
+        let (Some(name), Some(ttl), Some(class), Some(rtype)) = (
+            columns.next(),
+            columns.next(),
+            columns.next(),
+            columns.next(),
+        ) else {
+            return Err(format!("expected at least 4 columns: {input}"));
+        };
+
+        if class != "IN" {
+            return Err(format!("unsupported class `{class}`: {input}"));
+        }
+
+        let ttl = ttl
+            .parse()
+            .map_err(|e| format!("invalid TTL `{ttl}`: {e}"))?;
+        let rtype_code = parse_type(rtype)?;
+        let rdata = columns.collect::<Vec<_>>();
+        let rdata = encode_rdata(rtype, &rdata, input).map_err(|e| format!("{e}: {input}"))?;
+
+        Ok(Self {
+            name: name.to_ascii_lowercase(),
+            ttl,
+            rtype: rtype_code,
+            rdata,
+        })
+    }
+
+    /// Decodes the record at `offset`; returns `None` for OPT records
+    fn decode(bytes: &[u8], offset: &mut usize) -> Result<Option<Self>> {
+        // lowercase, like the names of the records in the zone, so that rules match them
+        let name = parse_name(bytes, offset)?.to_ascii_lowercase();
+        let rtype = read_u16(bytes, offset)?;
+        let _class = read_u16(bytes, offset)?;
+        let ttl = read_u32(bytes, offset)?;
+        let rdlength = usize::from(read_u16(bytes, offset)?);
+        let start = *offset;
+        let end = start + rdlength;
+        if end > bytes.len() {
+            return Err("unexpected end of message".to_string());
+        }
+        *offset = end;
+
+        if rtype == TYPE_OPT {
+            return Ok(None);
+        }
+
+        // names in these types of rdata may be compressed (RFC 3597 section 4)
+        let mut rdata = vec![];
+        let mut position = start;
+        match type_name(rtype).as_str() {
+            "NS" | "CNAME" | "PTR" => {
+                encode_name(&parse_name(bytes, &mut position)?, &mut rdata)?;
+            }
+            "MX" => {
+                rdata.extend_from_slice(&read_u16(bytes, &mut position)?.to_be_bytes());
+                encode_name(&parse_name(bytes, &mut position)?, &mut rdata)?;
+            }
+            "SOA" => {
+                encode_name(&parse_name(bytes, &mut position)?, &mut rdata)?;
+                encode_name(&parse_name(bytes, &mut position)?, &mut rdata)?;
+                rdata.extend_from_slice(bytes.get(position..end).ok_or("invalid SOA rdata")?);
+            }
+            _ => rdata.extend_from_slice(&bytes[start..end]),
+        }
+
+        Ok(Some(Self {
+            name,
+            ttl,
+            rtype,
+            rdata,
+        }))
+    }
+
+    fn encode(&self, buffer: &mut Vec<u8>) -> Result<()> {
+        encode_name(&self.name, buffer)?;
+        buffer.extend_from_slice(&self.rtype.to_be_bytes());
+        buffer.extend_from_slice(&CLASS_IN.to_be_bytes());
+        buffer.extend_from_slice(&self.ttl.to_be_bytes());
+        let rdlength = u16::try_from(self.rdata.len()).map_err(|_| "rdata is too long")?;
+        buffer.extend_from_slice(&rdlength.to_be_bytes());
+        buffer.extend_from_slice(&self.rdata);
+        Ok(())
+    }
+}
+
+fn encode_rdata(rtype: &str, columns: &[&str], input: &str) -> Result<Vec<u8>> {
+    fn parse<T: std::str::FromStr>(column: &str) -> Result<T> {
+        column
+            .parse()
+            .map_err(|_| format!("invalid rdata field `{column}`"))
+    }
+
+    let mut rdata = vec![];
+    if columns.first() == Some(&"\\#") {
+        let len: usize = parse(columns.get(1).ok_or("missing rdata length")?)?;
+        let hex = columns[2..].concat();
+        rdata = decode_hex(&hex)?;
+        if rdata.len() != len {
+            return Err(format!(
+                "rdata length is {len} but {} bytes were given",
+                rdata.len()
+            ));
+        }
+        return Ok(rdata);
+    }
+
+    match (rtype, columns) {
+        ("A", [addr]) => rdata.extend_from_slice(&parse::<Ipv4Addr>(addr)?.octets()),
+        ("AAAA", [addr]) => rdata.extend_from_slice(&parse::<Ipv6Addr>(addr)?.octets()),
+        ("NS" | "CNAME" | "PTR", [name]) => encode_name(name, &mut rdata)?,
+        ("MX", [preference, exchange]) => {
+            rdata.extend_from_slice(&parse::<u16>(preference)?.to_be_bytes());
+            encode_name(exchange, &mut rdata)?;
+        }
+        ("SOA", [mname, rname, serial, refresh, retry, expire, minimum]) => {
+            encode_name(mname, &mut rdata)?;
+            encode_name(rname, &mut rdata)?;
+            for field in [serial, refresh, retry, expire, minimum] {
+                rdata.extend_from_slice(&parse::<u32>(field)?.to_be_bytes());
+            }
+        }
+        ("TXT", _) => {
+            // re-read the rdata from the input to preserve the whitespace in quoted strings
+            for string in parse_character_strings(skip_columns(input, 4)) {
+                let len = u8::try_from(string.len()).map_err(|_| "TXT string is too long")?;
+                rdata.push(len);
+                rdata.extend_from_slice(string.as_bytes());
+            }
+        }
+        _ => {
+            return Err(format!(
+                "unsupported rdata for type {rtype}; use the RFC 3597 form"
+            ))
+        }
+    }
+
+    Ok(rdata)
+}
+
+/// Returns what follows the first `n` whitespace-separated columns of `input`
+pub fn skip_columns(input: &str, n: usize) -> &str {
+    let mut rest = input.trim_start();
+    for _ in 0..n {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        rest = rest[end..].trim_start();
+    }
+    rest
+}
+
+/// Splits TXT rdata into strings; quoted strings may contain whitespace
+fn parse_character_strings(input: &str) -> Vec<String> {
+    let mut strings = vec![];
+    let mut chars = input.trim().chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut string = String::new();
+            for c in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+                string.push(c);
+            }
+            strings.push(string);
+        } else {
+            let mut string = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                string.push(c);
+                chars.next();
+            }
+            strings.push(string);
+        }
+    }
+    strings
+}
+
+pub fn decode_hex(input: &str) -> Result<Vec<u8>> {
+    if !input.len().is_multiple_of(2) {
+        return Err(format!("odd number of hex digits: {input}"));
+    }
+
+    (0..input.len())
+        .step_by(2)
+        .map(|index| {
+            u8::from_str_radix(&input[index..index + 2], 16)
+                .map_err(|_| format!("invalid hex: {input}"))
+        })
+        .collect()
+}
+
+/// Header flags that rules can set or clear
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flag {
+    Aa,
+    Tc,
+    Rd,
+    Ra,
+    Ad,
+    Cd,
+}
+
+impl Flag {
+    pub fn parse(input: &str) -> Result<Self> {
+        let flag = match input.to_ascii_lowercase().as_str() {
+            "aa" => Self::Aa,
+            "tc" => Self::Tc,
+            "rd" => Self::Rd,
+            "ra" => Self::Ra,
+            "ad" => Self::Ad,
+            "cd" => Self::Cd,
+            _ => return Err(format!("unknown flag: {input}")),
+        };
+        Ok(flag)
+    }
+
+    fn mask(self) -> u16 {
+        match self {
+            Self::Aa => 0x0400,
+            Self::Tc => 0x0200,
+            Self::Rd => 0x0100,
+            Self::Ra => 0x0080,
+            Self::Ad => 0x0020,
+            Self::Cd => 0x0010,
+        }
+    }
+}
+
+/// The sections of a response that contain records
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Section {
+    Answer,
+    Authority,
+    Additional,
+}
+
+impl Section {
+    pub fn parse(input: &str) -> Result<Self> {
+        let section = match input.to_ascii_lowercase().as_str() {
+            "answer" => Self::Answer,
+            "authority" => Self::Authority,
+            "additional" => Self::Additional,
+            _ => return Err(format!("unknown section: {input}")),
+        };
+        Ok(section)
+    }
+}
+
+/// A response under construction
+pub struct Response {
+    pub id: u16,
+    flags: u16,
+    pub rcode: u8,
+    question: (String, u16, u16),
+    pub answer: Vec<Record>,
+    pub authority: Vec<Record>,
+    pub additional: Vec<Record>,
+    /// `Some` if the response has an OPT record, in which case it indicates whether its DO flag
+    /// is set
+    edns: Option<bool>,
+}
+
+impl Response {
+    /// Returns an empty response to `query` with the QR flag set and the RD flag copied from it
+    pub fn new(query: &Query) -> Self {
+        let mut response = Self {
+            id: query.id,
+            flags: 0x8000,
+            rcode: 0,
+            question: (query.question_name.clone(), query.qtype, query.qclass),
+            answer: vec![],
+            authority: vec![],
+            additional: vec![],
+            edns: query.udp_size.map(|_| query.dnssec_ok),
+        };
+        response.set_flag(Flag::Rd, query.rd);
+        response
+    }
+
+    /// Parses `bytes`, the response of another server to `query`
+    ///
+    /// The question of `query` is used, as is its OPT record, if any; the OPT records of `bytes`
+    /// are discarded. Compressed names are decompressed, including the ones in the rdata of the
+    /// record types defined in RFC 1035
+    pub fn parse(bytes: &[u8], query: &Query) -> Result<Self> {
+        if bytes.len() < HEADER_LEN {
+            return Err(format!("message is too short: {} bytes", bytes.len()));
+        }
+
+        let mut response = Self::new(query);
+        let flags = u16::from_be_bytes([bytes[2], bytes[3]]);
+        response.id = u16::from_be_bytes([bytes[0], bytes[1]]);
+        // keep the QR, AA, TC, RD, RA, AD and CD flags
+        response.flags = flags & 0x87b0;
+        response.rcode = (flags & 0xf) as u8;
+
+        let count = |index: usize| u16::from_be_bytes([bytes[index], bytes[index + 1]]);
+        let qdcount = count(4);
+        let counts = [
+            (Section::Answer, count(6)),
+            (Section::Authority, count(8)),
+            (Section::Additional, count(10)),
+        ];
+
+        let mut offset = HEADER_LEN;
+        for _ in 0..qdcount {
+            parse_name(bytes, &mut offset)?;
+            // type and class
+            offset += 4;
+        }
+
+        for (section, count) in counts {
+            for _ in 0..count {
+                if let Some(record) = Record::decode(bytes, &mut offset)? {
+                    response.section_mut(section).push(record);
+                }
+            }
+        }
+
+        Ok(response)
+    }
+
+    pub fn section_mut(&mut self, section: Section) -> &mut Vec<Record> {
+        match section {
+            Section::Answer => &mut self.answer,
+            Section::Authority => &mut self.authority,
+            Section::Additional => &mut self.additional,
+        }
+    }
+
+    /// Returns all the records of the response
+    pub fn records_mut(&mut self) -> impl Iterator<Item = &mut Record> {
+        self.answer
+            .iter_mut()
+            .chain(&mut self.authority)
+            .chain(&mut self.additional)
+    }
+
+    pub fn set_flag(&mut self, flag: Flag, value: bool) {
+        if value {
+            self.flags |= flag.mask();
+        } else {
+            self.flags &= !flag.mask();
+        }
+    }
+
+    /// Removes all the records and sets the TC flag
+    pub fn truncate(&mut self) {
+        self.answer.clear();
+        self.authority.clear();
+        self.additional.clear();
+        self.set_flag(Flag::Tc, true);
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let count =
+            |records: &[Record]| u16::try_from(records.len()).map_err(|_| "too many records");
+        let arcount = count(&self.additional)? + u16::from(self.edns.is_some());
+
+        let mut buffer = vec![];
+        buffer.extend_from_slice(&self.id.to_be_bytes());
+        buffer.extend_from_slice(&(self.flags | u16::from(self.rcode & 0xf)).to_be_bytes());
+        buffer.extend_from_slice(&1u16.to_be_bytes());
+        buffer.extend_from_slice(&count(&self.answer)?.to_be_bytes());
+        buffer.extend_from_slice(&count(&self.authority)?.to_be_bytes());
+        buffer.extend_from_slice(&arcount.to_be_bytes());
+
+        let (qname, qtype, qclass) = &self.question;
+        encode_name(qname, &mut buffer)?;
+        buffer.extend_from_slice(&qtype.to_be_bytes());
+        buffer.extend_from_slice(&qclass.to_be_bytes());
+
+        for record in self
+            .answer
+            .iter()
+            .chain(&self.authority)
+            .chain(&self.additional)
+        {
+            record.encode(&mut buffer)?;
+        }
+
+        if let Some(dnssec_ok) = self.edns {
+            // root name, type OPT, payload size, extended rcode & flags, no options
+            let flags = if dnssec_ok { EDNS_DO } else { 0 };
+            buffer.push(0);
+            buffer.extend_from_slice(&TYPE_OPT.to_be_bytes());
+            buffer.extend_from_slice(&EDNS_UDP_SIZE.to_be_bytes());
+            buffer.extend_from_slice(&flags.to_be_bytes());
+            buffer.extend_from_slice(&0u16.to_be_bytes());
+        }
+
+        Ok(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `dig +norecurse @192.0.2.1 A example.com.` with an OPT record
+    const QUERY: &str =
+        "abcd00000001000000000001076578616d706c6503636f6d00000100010000291000000000000000";
+
+    #[test]
+    fn parses_query() -> Result<()> {
+        let query = Query::parse(&decode_hex(QUERY)?)?;
+
+        assert_eq!(0xabcd, query.id);
+        assert!(!query.rd);
+        assert_eq!("example.com.", query.qname);
+        assert_eq!(1, query.qtype);
+        assert_eq!(CLASS_IN, query.qclass);
+        assert_eq!(Some(4096), query.udp_size);
+        assert_eq!(4096, query.max_udp_size());
+
+        Ok(())
+    }
+
+    #[test]
+    fn echoes_the_case_of_the_question() -> Result<()> {
+        let mut bytes = decode_hex(QUERY)?;
+        // `ExAmple.Com.`, as sent by a resolver that uses DNS 0x20
+        for index in [HEADER_LEN + 1, HEADER_LEN + 3, HEADER_LEN + 9] {
+            bytes[index].make_ascii_uppercase();
+        }
+        let query = Query::parse(&bytes)?;
+
+        assert_eq!("example.com.", query.qname);
+        assert_eq!("ExAmple.Com.", query.question_name);
+
+        let response = Response::new(&query).encode()?;
+        let question = HEADER_LEN..HEADER_LEN + 13;
+        assert_eq!(bytes[question.clone()], response[question]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encodes_response() -> Result<()> {
+        let query = Query::parse(&decode_hex(QUERY)?)?;
+        let mut response = Response::new(&query);
+        response.set_flag(Flag::Aa, true);
+        response
+            .answer
+            .push(Record::parse("example.com.\t3600\tIN\tA\t192.0.2.1")?);
+
+        let bytes = response.encode()?;
+
+        let expected = decode_hex(
+            "abcd84000001000100000001\
+             076578616d706c6503636f6d0000010001\
+             076578616d706c6503636f6d00000100010000 0e100004c0000201"
+                .replace(' ', "")
+                .as_str(),
+        )?;
+        assert_eq!(expected, bytes[..expected.len()]);
+        // OPT record
+        assert_eq!(&[0, 0, 41, 0x04, 0xd0], &bytes[expected.len()..][..5]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parses_compressed_response() -> Result<()> {
+        let query = Query::parse(&decode_hex(QUERY)?)?;
+        let bytes = decode_hex(
+            "abcd84000001000200000001\
+             076578616d706c6503636f6d0000010001\
+             c00c000100010000 0e100004c0000201\
+             c00c000200010000 0e100005026e73c00c\
+             0000291000000080000000"
+                .replace(' ', "")
+                .as_str(),
+        )?;
+
+        let mut response = Response::parse(&bytes, &query)?;
+
+        assert_eq!(0xabcd, response.id);
+        assert_eq!(
+            vec![
+                Record::parse("example.com. 3600 IN A 192.0.2.1")?,
+                Record::parse("example.com. 3600 IN NS ns.example.com.")?,
+            ],
+            response.answer
+        );
+        // the OPT record is not a regular record
+        assert!(response.additional.is_empty());
+
+        // the re-encoded response uses no compression
+        response.answer.reverse();
+        let reencoded = Response::parse(&response.encode()?, &query)?;
+        assert_eq!("ns.example.com.".len() + 1, reencoded.answer[0].rdata.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parses_records() -> Result<()> {
+        let soa = Record::parse(
+            "example.com. 86400 IN SOA ns.example.com. admin.example.com. 1 2 3 4 5",
+        )?;
+        assert_eq!(6, soa.rtype);
+        assert_eq!(16 + 19 + 20, soa.rdata.len());
+
+        let txt = Record::parse("example.com.\t60\tIN  TXT \"hello world\" again")?;
+        assert_eq!(b"\x0bhello world\x05again".to_vec(), txt.rdata);
+
+        let generic = Record::parse(r"example.com. 60 IN TYPE65534 \# 3 abcdef")?;
+        assert_eq!(65534, generic.rtype);
+        assert_eq!(vec![0xab, 0xcd, 0xef], generic.rdata);
+
+        assert!(Record::parse(r"example.com. 60 IN A \# 3 abcdef00").is_err());
+        assert!(Record::parse("example.com. 60 IN DS 1 2 3 abcd").is_err());
+
+        Ok(())
+    }
+}